@@ -0,0 +1,81 @@
+//! Benchmarks for `render`, reported in pixels per second.
+//!
+//! Run them with `cargo bench`; criterion compares each run with the last, so
+//! a slowdown in the escape-time loop shows up as a regression here.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use mandelbrot::{pixel_to_point, render, thread_pool, Iteration};
+use num::Complex;
+use rayon::ThreadPool;
+
+const BOUNDS : (usize, usize) = (400, 300);
+const LIMIT : usize = 1000;
+const THREADS : usize = 4;
+
+/// Time rendering the view from `upper_left` to `lower_right` as `name`.
+fn bench_view(c : &mut Criterion, name : &str, upper_left : Complex<f64>, lower_right : Complex<f64>) {
+
+    let iteration = Iteration { limit: LIMIT, ..Iteration::default() };
+
+    let mut group = c.benchmark_group("render");
+    group.throughput(Throughput::Elements((BOUNDS.0 * BOUNDS.1) as u64));
+    group.sample_size(10);
+    group.bench_function(name, |b| b.iter(|| render(BOUNDS, upper_left, lower_right, iteration)));
+    group.finish();
+}
+
+/// Render the view as equal bands of rows, one per thread of `pools`, each
+/// pool having one thread, the way `render` divided the work before its
+/// threads shared a row counter.
+fn render_static_bands(pools : &[ThreadPool], upper_left : Complex<f64>, lower_right : Complex<f64>,
+                       iteration : Iteration) {
+
+    let band = BOUNDS.1.div_ceil(pools.len());
+    std::thread::scope(|scope| {
+        for (pool, top) in pools.iter().zip((0..BOUNDS.1).step_by(band)) {
+            let rows = band.min(BOUNDS.1 - top);
+            let band_upper_left = pixel_to_point(BOUNDS, (0, top), upper_left, lower_right);
+            let band_lower_right = pixel_to_point(BOUNDS, (BOUNDS.0, top + rows), upper_left, lower_right);
+            scope.spawn(move || {
+                pool.install(|| render((BOUNDS.0, rows), band_upper_left, band_lower_right, iteration))
+            });
+        }
+    });
+}
+
+/// Compare `render` on `THREADS` threads with static banding, on most of the
+/// set, whose rows through the middle take far longer than those above and
+/// below it. The view is off center, so that `render` mirrors no rows across
+/// the real axis, which the bands, each rendered alone, couldn't either.
+fn bench_balance(c : &mut Criterion) {
+
+    let upper_left = Complex { re: -2.0, im: 1.2 };
+    let lower_right = Complex { re: 1.0, im: -1.0 };
+    let iteration = Iteration { limit: LIMIT, ..Iteration::default() };
+    let pool = thread_pool(THREADS).unwrap();
+    let pools : Vec<ThreadPool> = (0..THREADS).map(|_| thread_pool(1).unwrap()).collect();
+
+    let mut group = c.benchmark_group("balance");
+    group.throughput(Throughput::Elements((BOUNDS.0 * BOUNDS.1) as u64));
+    group.sample_size(10);
+    group.bench_function("shared_counter", |b| {
+        b.iter(|| pool.install(|| render(BOUNDS, upper_left, lower_right, iteration)))
+    });
+    group.bench_function("static_bands", |b| {
+        b.iter(|| render_static_bands(&pools, upper_left, lower_right, iteration))
+    });
+    group.finish();
+}
+
+fn bench_render(c : &mut Criterion) {
+
+    // The whole set, where the cardioid and bulb checks do most of the work.
+    bench_view(c, "full_set", Complex { re: -2.0, im: 1.2 }, Complex { re: 1.0, im: -1.2 });
+
+    // Seahorse valley, where most points take many iterations to escape.
+    bench_view(c, "deep_zoom", Complex { re: -0.7436447, im: 0.1318252 },
+               Complex { re: -0.7436437, im: 0.1318245 });
+}
+
+criterion_group!(benches, bench_render, bench_balance);
+criterion_main!(benches);
@@ -0,0 +1,81 @@
+//! Quick previews of a view as text, for checking it in a terminal before
+//! rendering the full image.
+
+use num::Complex;
+
+use crate::escape::Iteration;
+use crate::render::pixel_to_point;
+
+/// The characters `ascii_preview` draws with, from the fastest escaping
+/// points to the points in the set.
+pub const ASCII_RAMP : &[u8] = b" .:-=+*#%@";
+
+/// Return a picture of the rectangle from `upper_left` to `lower_right` as
+/// `bounds.1` lines of `bounds.0` characters each, one per point, counted
+/// out by `iteration`.
+///
+/// Escaping points choose a character from `ASCII_RAMP` by the logarithm of
+/// their count, so that slower points are denser and the few characters
+/// aren't all spent near the boundary; only points in the set get the last,
+/// densest one, and points that escape at once are left blank. Terminal characters are about twice as tall as
+/// they are wide, so `bounds` should be about twice as wide as the image.
+pub fn ascii_preview(bounds : (usize, usize),
+                     upper_left : Complex<f64>,
+                     lower_right : Complex<f64>,
+                     iteration : Iteration) -> String {
+
+    let steps = ASCII_RAMP.len() - 1;
+    let mut text = String::with_capacity((bounds.0 + 1) * bounds.1);
+
+    for row in 0..bounds.1 {
+        for column in 0..bounds.0 {
+            let point = pixel_to_point(bounds, (column, row), upper_left, lower_right);
+            let index = match iteration.escape_time(point) {
+                None => steps,
+                Some(count) => {
+                    let fraction = (count.max(1) as f64).ln() / (iteration.limit as f64).ln();
+                    ((fraction * steps as f64) as usize).min(steps - 1)
+                }
+            };
+            text.push(ASCII_RAMP[index] as char);
+        }
+        text.push('\n');
+    }
+
+    text
+}
+
+#[test]
+fn test_ascii_preview() {
+
+    let preview = ascii_preview((80, 40), Complex { re: -3.0, im: 2.0 }, Complex { re: 2.0, im: -2.0 },
+                                Iteration::default());
+    let lines : Vec<&str> = preview.lines().collect();
+    assert_eq!(lines.len(), 40);
+    assert!(lines.iter().all(|line| line.len() == 80));
+
+    // The main cardioid fills the middle of the center rows, and the far
+    // corners escape at once.
+    for line in &lines[19..21] {
+        assert!(line[44..52].bytes().all(|c| c == b'@'), "{:?}", line);
+    }
+    for line in [lines[0], lines[39]] {
+        assert!(line.starts_with(' ') && line.ends_with(' '), "{:?}", line);
+    }
+}
+
+#[test]
+fn test_ascii_preview_precision() {
+
+    use crate::Precision;
+
+    // So deep a view that f32 rounds every column to the same few points,
+    // while f64 still tells them apart.
+    let (upper_left, lower_right) = (Complex { re: -0.7436439, im: 0.1318259 },
+                                     Complex { re: -0.7436438, im: 0.1318258 });
+    let preview = |precision| {
+        ascii_preview((40, 20), upper_left, lower_right,
+                      Iteration { limit: 2000, precision, ..Iteration::default() })
+    };
+    assert!(preview(Precision::Single) != preview(Precision::Double));
+}
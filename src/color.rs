@@ -0,0 +1,1326 @@
+//! Mapping escape counts to pixel colors.
+
+use num::Complex;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+use std::fs;
+use std::str::FromStr;
+
+use crate::error::MandelError;
+use crate::render::{lerp_clamped, Counts};
+
+/// Map the result of `escape_time` to a grayscale value.
+///
+/// Points in the set are black; escaping points get brighter the faster they
+/// escape, with the count rescaled so that the full `0..=255` range is used
+/// whatever the iteration `limit` is.
+pub fn shade(count : Option<usize>, limit : usize) -> u8 {
+
+    shade_smooth(count.map(|count| count as f64), limit)
+}
+
+/// Like `shade`, but for the fractional counts of `escape_time_smooth`.
+pub fn shade_smooth(count : Option<f64>, limit : usize) -> u8 {
+
+    match count {
+        None => 0,
+        Some(count) => 255 - (count.clamp(0.0, limit as f64) * 255.0 / limit as f64) as u8
+    }
+}
+
+/// Like `shade_smooth`, but with brightness falling with the logarithm of the
+/// count: `255 - ln(count + 1) / ln(limit + 1) * 255`. Counts spike near the
+/// boundary of the set, and the logarithm squeezes those high counts
+/// together, leaving more of the range for the low counts far from it. The
+/// `+ 1` keeps a count of 0 clear of `ln(0)`.
+pub fn shade_log(count : Option<f64>, limit : usize) -> u8 {
+
+    match count {
+        None => 0,
+        Some(count) => {
+            let fraction = (count.clamp(0.0, limit as f64) + 1.0).ln() / (limit as f64 + 1.0).ln();
+            255 - (fraction * 255.0) as u8
+        }
+    }
+}
+
+/// Like `shade_smooth`, but spread over the 16-bit range `0..=65535`, which
+/// keeps the gradient between neighbouring counts at high limits.
+pub fn shade_wide(count : Option<f64>, limit : usize) -> u16 {
+
+    match count {
+        None => 0,
+        Some(count) => 65535 - (count.clamp(0.0, limit as f64) * 65535.0 / limit as f64) as u16
+    }
+}
+
+/// Turn the escape counts from `render` into a 16-bit grayscale image, one
+/// sample per pixel, shaded linearly as `shade_wide` does. Trap distances
+/// and distance estimates are shaded as `shade_trap` and `shade_distance`
+/// do, over the wider range.
+pub fn map_gray16(counts : &Counts, limit : usize) -> Vec<u16> {
+
+    match counts {
+        Counts::Whole(counts) =>
+            counts.iter().map(|&count| shade_wide(count.map(|count| count as f64), limit)).collect(),
+        Counts::Smooth(counts) => counts.iter().map(|&count| shade_wide(count, limit)).collect(),
+        Counts::Trapped(distances) => distances.iter()
+            .map(|distance| ((1.0 - distance.clamp(0.0, 1.0).sqrt()) * 65535.0) as u16)
+            .collect(),
+        Counts::Distance(distances) => distances.iter()
+            .map(|distance| distance.map_or(0, |distance| (distance.clamp(0.0, 1.0).sqrt() * 65535.0) as u16))
+            .collect(),
+        Counts::Final(orbits) =>
+            orbits.iter().map(|&(count, _)| shade_wide(count.map(|count| count as f64), limit)).collect(),
+        Counts::Periodic(orbits) =>
+            orbits.iter().map(|&(count, _)| shade_wide(count.map(|count| count as f64), limit)).collect()
+    }
+}
+
+/// Like `map_gray16`, but at 8 bits, as `shade` and `shade_smooth` do: the
+/// same shades `map_colors` gives with the default `ColorScheme`, one byte
+/// per pixel rather than three.
+pub fn map_gray(counts : &Counts, limit : usize) -> Vec<u8> {
+
+    match counts {
+        Counts::Whole(counts) => counts.iter().map(|&count| shade(count, limit)).collect(),
+        Counts::Smooth(counts) => counts.iter().map(|&count| shade_smooth(count, limit)).collect(),
+        Counts::Trapped(distances) => distances.iter().map(|&distance| shade_trap(distance)).collect(),
+        Counts::Distance(distances) => distances.iter().map(|&distance| shade_distance(distance)).collect(),
+        Counts::Final(orbits) => orbits.iter().map(|&(count, _)| shade(count, limit)).collect(),
+        Counts::Periodic(orbits) => orbits.iter().map(|&(count, _)| shade(count, limit)).collect()
+    }
+}
+
+/// Map a point's estimated distance from the set, in pixels, to a grayscale
+/// value: black within the set and on its boundary, brightening to white a
+/// pixel away. Even filaments far thinner than a pixel darken the pixels
+/// they pass through, which keeps them visible.
+pub fn shade_distance(distance : Option<f64>) -> u8 {
+
+    match distance {
+        None => 0,
+        Some(distance) => (distance.clamp(0.0, 1.0).sqrt() * 255.0) as u8
+    }
+}
+
+/// Map the closest an orbit came to its trap to a grayscale value: white at
+/// the trap itself, fading to black at a distance of 1. The square root
+/// spends more of the range near the trap, where the interesting detail is.
+pub fn shade_trap(distance : f64) -> u8 {
+
+    ((1.0 - distance.clamp(0.0, 1.0).sqrt()) * 255.0) as u8
+}
+
+/// The color schemes `colorize` knows how to produce.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    /// Black through gray to white.
+    Grayscale,
+    /// Black through red and yellow to white.
+    Fire,
+    /// Black through blue and cyan to white.
+    Ocean
+}
+
+impl FromStr for Palette {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Palette, String> {
+
+        match s {
+            "grayscale" => Ok(Palette::Grayscale),
+            "fire" => Ok(Palette::Fire),
+            "ocean" => Ok(Palette::Ocean),
+            _ => Err(format!("unknown palette '{}'", s))
+        }
+    }
+}
+
+/// Map the result of `escape_time` to an RGB color from `palette`.
+///
+/// Points in the set are black under every palette. Escaping points follow
+/// the same brightness ramp as `shade`, which the colored palettes spread
+/// across three overlapping channels so that each channel saturates in turn.
+pub fn colorize(count : Option<usize>, limit : usize, palette : Palette) -> [u8; 3] {
+
+    ramp(shade(count, limit), palette)
+}
+
+/// Return an RGB image of `bounds` pixels showing `palette` as `colorize`
+/// spreads it over the counts: a horizontal strip running from a count of 0
+/// at the left edge to `limit` at the right, the same down every column.
+pub fn palette_strip(bounds : (usize, usize), limit : usize, palette : Palette) -> Vec<u8> {
+
+    let row : Vec<u8> = (0..bounds.0)
+        .flat_map(|x| {
+            let count = (x as f64 * limit as f64 / (bounds.0 - 1).max(1) as f64).round() as usize;
+            colorize(Some(count), limit, palette)
+        })
+        .collect();
+    row.repeat(bounds.1)
+}
+
+/// Like `colorize`, but for the fractional counts of `escape_time_smooth`.
+pub fn colorize_smooth(count : Option<f64>, limit : usize, palette : Palette) -> [u8; 3] {
+
+    ramp(shade_smooth(count, limit), palette)
+}
+
+/// How escape counts are turned into brightness.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Coloring {
+    /// Brightness falls evenly with the count, as `shade` computes it.
+    Linear,
+    /// Brightness follows the distribution of counts in the image, as
+    /// `histogram_colors` computes it.
+    Histogram,
+    /// Brightness grows with the estimated distance from the set, as
+    /// `shade_distance` computes it. This needs the counts rendered with
+    /// `Iteration::distance` set.
+    Distance,
+    /// Brightness falls with the logarithm of the count, as `shade_log`
+    /// computes it.
+    Log,
+    /// The hue cycles around the color wheel with the count, as `rainbow`
+    /// computes it, ignoring the palette.
+    Rainbow,
+    /// Brightness falls evenly from the lowest count of any escaping pixel
+    /// in the image to the highest, so that together they span the whole
+    /// range, however narrow a band of counts they fall in.
+    Normalize,
+    /// The hue follows the angle of the point each orbit escapes at, as
+    /// `external_angle` computes it, ignoring the palette, which traces the
+    /// set's external rays. This needs the counts rendered with
+    /// `Iteration::interior` set, and falls back to linear coloring without.
+    ExternalAngle
+}
+
+impl FromStr for Coloring {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Coloring, String> {
+
+        match s {
+            "linear" => Ok(Coloring::Linear),
+            "histogram" => Ok(Coloring::Histogram),
+            "distance" => Ok(Coloring::Distance),
+            "log" => Ok(Coloring::Log),
+            "rainbow" => Ok(Coloring::Rainbow),
+            "normalize" => Ok(Coloring::Normalize),
+            "external-angle" => Ok(Coloring::ExternalAngle),
+            _ => Err(format!("unknown coloring '{}'", s))
+        }
+    }
+}
+
+/// How points in the set are colored, from the last point of their orbit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interior {
+    /// Every point in the set is black.
+    Black,
+    /// Brightness follows the angle of the final `z` around the origin.
+    Angle,
+    /// Brightness follows the distance of the final `z` from the origin.
+    Magnitude,
+    /// Brightness follows the period of the cycle the orbit settles into,
+    /// as `shade_period` gives it. Only counts rendered with
+    /// `Iteration::max_period` set carry periods; from the last point of an
+    /// orbit alone, this is black.
+    Period
+}
+
+impl FromStr for Interior {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Interior, String> {
+
+        match s {
+            "black" => Ok(Interior::Black),
+            "angle" => Ok(Interior::Angle),
+            "magnitude" => Ok(Interior::Magnitude),
+            "period" => Ok(Interior::Period),
+            _ => Err(format!("unknown interior coloring '{}'", s))
+        }
+    }
+}
+
+/// Map the last point `z` of an orbit that never escaped to a grayscale
+/// value, as `interior` says. Angles run from black at `-π` to white at `π`;
+/// magnitudes from black at the origin to white at 2, beyond which a
+/// Mandelbrot orbit would have escaped.
+pub fn shade_interior(z : Complex<f64>, interior : Interior) -> u8 {
+
+    match interior {
+        Interior::Black | Interior::Period => 0,
+        Interior::Angle => ((z.arg() / TAU + 0.5) * 255.0).round() as u8,
+        Interior::Magnitude => ((z.norm() / 2.0).clamp(0.0, 1.0) * 255.0).round() as u8
+    }
+}
+
+/// Map the period of the cycle an orbit settled into to a grayscale value:
+/// white for the main cardioid's fixed points, half as bright for the
+/// period-2 bulb, a third for period 3, and so on, so each bulb stands out
+/// from the bulbs it sprouts from. Orbits with no period found are black.
+pub fn shade_period(period : Option<usize>) -> u8 {
+
+    period.map_or(0, |period| (255 / period.max(1)) as u8)
+}
+
+/// Convert a color given as hue, saturation and value to red, green and blue.
+/// The hue `h` is in degrees, and is taken modulo 360; `s` and `v` run from 0
+/// to 1, so that `hsv_to_rgb(120.0, 1.0, 1.0)` is pure green.
+pub fn hsv_to_rgb(h : f64, s : f64, v : f64) -> [u8; 3] {
+
+    // The wheel is cut into six sectors of 60 degrees, in each of which one
+    // channel is at `v`, one at `v - chroma`, and one moving between them.
+    let sector = h.rem_euclid(360.0) / 60.0;
+    let chroma = v * s;
+    let moving = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, moving, 0.0),
+        1 => (moving, chroma, 0.0),
+        2 => (0.0, chroma, moving),
+        3 => (0.0, moving, chroma),
+        4 => (moving, 0.0, chroma),
+        _ => (chroma, 0.0, moving)
+    };
+
+    let lowest = v - chroma;
+    [r, g, b].map(|channel| ((channel + lowest) * 255.0).round() as u8)
+}
+
+/// Map a fractional escape count to a fully saturated color whose hue is
+/// `count * scale` degrees around the color wheel, so the colors cycle every
+/// `360 / scale` iterations. Points in the set are black.
+pub fn rainbow(count : Option<f64>, scale : f64) -> [u8; 3] {
+
+    match count {
+        None => [0, 0, 0],
+        Some(count) => hsv_to_rgb(count * scale, 1.0, 1.0)
+    }
+}
+
+/// Map the point `z` an orbit escaped at after `count` iterations to a fully
+/// saturated color whose hue is its angle around the origin: red along the
+/// positive real axis, turning through the color wheel counterclockwise.
+/// Points in the set, with no count, are black.
+pub fn external_angle(count : Option<usize>, z : Complex<f64>) -> [u8; 3] {
+
+    match count {
+        None => [0, 0, 0],
+        Some(_) => hsv_to_rgb(z.arg().to_degrees(), 1.0, 1.0)
+    }
+}
+
+/// Color a whole image of `escape_time` results by histogram equalization,
+/// returning one RGB pixel from `palette` per count.
+///
+/// Most escaping points escape within a few iterations, so `colorize` spends
+/// most of its range on counts that hardly any pixels have. Instead, each
+/// escaping pixel here is shaded by the fraction of escaping pixels with a
+/// lower count than its own, so that the shades are spread evenly over the
+/// pixels of the image. Points in the set are black, as with `colorize`.
+pub fn histogram_colors(counts : &[Option<usize>], limit : usize, palette : Palette) -> Vec<u8> {
+
+    histogram_shades(counts, limit).into_iter().flat_map(|shade| ramp(shade, palette)).collect()
+}
+
+/// The grayscale values `histogram_colors` ramps through its palette.
+///
+/// Rather than a histogram with a bin for every count up to `limit`, which
+/// may be far too many to allocate, each pixel finds how many escaping
+/// pixels have a lower count by binary search through all their counts,
+/// sorted. The ranks are scaled to 255 in `u64`, so they can't overflow.
+fn histogram_shades(counts : &[Option<usize>], limit : usize) -> Vec<u8> {
+
+    let mut sorted : Vec<usize> = counts.iter().flatten().map(|&count| count.min(limit)).collect();
+    sorted.sort_unstable();
+    let total = sorted.len() as u64;
+
+    counts.iter()
+        .map(|count| match count {
+            None => 0,
+            Some(count) => {
+                let lower = sorted.partition_point(|&other| other < (*count).min(limit)) as u64;
+                255 - (lower * 255 / total) as u8
+            }
+        })
+        .collect()
+}
+
+/// The grayscale values for normalized coloring: the escaping pixel with the
+/// lowest count is white, the highest black, and the rest fall evenly between.
+/// Points in the set are black. If every escaping pixel has the same count,
+/// they're all white.
+fn normalized_shades(counts : &[Option<f64>]) -> Vec<u8> {
+
+    let escaped = counts.iter().flatten();
+    let lowest = escaped.clone().copied().fold(f64::INFINITY, f64::min);
+    let highest = escaped.copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = (highest - lowest).max(f64::MIN_POSITIVE);
+
+    counts.iter()
+        .map(|count| match count {
+            None => 0,
+            Some(count) => 255 - ((count - lowest) / range * 255.0).round() as u8
+        })
+        .collect()
+}
+
+/// Flip every sample of `samples` end for end, so that `v` becomes
+/// `255 - v` for bytes, or `65535 - v` for 16-bit samples. Inverting twice
+/// gives back what you started with.
+pub fn invert<T: num::Bounded + std::ops::Sub<Output = T> + Copy>(samples : &mut [T]) {
+
+    for sample in samples {
+        *sample = T::max_value() - *sample;
+    }
+}
+
+/// Return the lookup table for gamma correction by `gamma`, which takes each
+/// byte `v` to `255 * (v / 255)^(1 / gamma)`, rounded. A gamma above 1
+/// brightens the midtones, leaving black and white where they are, and a
+/// gamma of 1 changes nothing.
+pub fn gamma_table(gamma : f64) -> [u8; 256] {
+
+    let mut table = [0; 256];
+    for (v, entry) in table.iter_mut().enumerate() {
+        *entry = (255.0 * (v as f64 / 255.0).powf(1.0 / gamma)).round() as u8;
+    }
+    table
+}
+
+/// Replace every byte of `samples` with its entry in `table`, as
+/// `gamma_table` builds it.
+pub fn apply_gamma(samples : &mut [u8], table : &[u8; 256]) {
+
+    for sample in samples {
+        *sample = table[*sample as usize];
+    }
+}
+
+/// One color of a `Gradient`, and where along it that color falls.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stop {
+    /// How far along the gradient this stop is, from 0 at the set to 1 for
+    /// the fastest-escaping points.
+    pub position: f64,
+
+    /// The stop's red, green and blue.
+    pub color: [u8; 3]
+}
+
+/// A palette of its own, blending evenly from each color stop to the next.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    /// The stops, in order of position. There's always at least one.
+    pub stops: Vec<Stop>
+}
+
+impl Gradient {
+
+    /// Parse a gradient file's `text`: one stop per line, giving its position
+    /// then its red, green and blue, separated by commas or spaces, like
+    /// `0.5, 255, 128, 0`. Blank lines and lines starting with `#` are
+    /// skipped. The stops may come in any order.
+    pub fn parse(text : &str) -> Result<Gradient, String> {
+
+        let mut stops = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let bad = || format!("line {}: expected POSITION R G B, got '{}'", number + 1, line);
+            let fields : Vec<_> = line.split(|c : char| c == ',' || c.is_whitespace())
+                .filter(|field| !field.is_empty())
+                .collect();
+            if fields.len() != 4 {
+                return Err(bad());
+            }
+
+            let position : f64 = fields[0].parse().map_err(|_| bad())?;
+            if !position.is_finite() {
+                return Err(bad());
+            }
+            let mut color = [0; 3];
+            for (channel, field) in color.iter_mut().zip(&fields[1..]) {
+                *channel = field.parse().map_err(|_| bad())?;
+            }
+
+            stops.push(Stop { position, color });
+        }
+
+        if stops.is_empty() {
+            return Err("a gradient needs at least one stop".to_string());
+        }
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+        Ok(Gradient { stops })
+    }
+}
+
+/// Read the gradient in the file at `path`, reporting a missing, unreadable
+/// or malformed file as `MandelError::BadGradient`.
+pub fn load_gradient(path : &str) -> Result<Gradient, MandelError> {
+
+    let bad = |message : String| MandelError::BadGradient { path: path.to_string(), message };
+
+    let text = fs::read_to_string(path).map_err(|err| bad(err.to_string()))?;
+    Gradient::parse(&text).map_err(bad)
+}
+
+/// Return the color `t` of the way along the gradient of `stops`, which must
+/// be in order of position, blending each channel linearly between the stops
+/// on either side. Before the first stop or past the last one, `t` is held to
+/// that stop's color. `stops` must not be empty.
+pub fn sample_gradient(stops : &[Stop], t : f64) -> [u8; 3] {
+
+    let after = stops.iter().position(|stop| stop.position > t).unwrap_or(stops.len());
+    if after == 0 {
+        return stops[0].color;
+    }
+    if after == stops.len() {
+        return stops[stops.len() - 1].color;
+    }
+
+    let (from, to) = (stops[after - 1], stops[after]);
+    let u = (t - from.position) / (to.position - from.position);
+    [0, 1, 2].map(|c| lerp_clamped(from.color[c] as f64, to.color[c] as f64, u).round() as u8)
+}
+
+/// The number of colors along a palette or gradient: one for each shade.
+pub const PALETTE_LENGTH : usize = 256;
+
+/// Everything `map_colors` needs to know to choose a pixel's color.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorScheme {
+    /// The colors to use for escaping points.
+    pub palette: Palette,
+
+    /// A gradient to use instead of `palette`, if there is one.
+    pub gradient: Option<Gradient>,
+
+    /// How escape counts choose colors from `palette`.
+    pub coloring: Coloring,
+
+    /// Whether to invert each pixel's grayscale value before it's looked up
+    /// in `palette`, so that points in the set come out white, or whatever
+    /// color the top of the palette is.
+    pub invert: bool,
+
+    /// With rainbow coloring, the degrees of hue each iteration moves on.
+    pub color_scale: f64,
+
+    /// How many of the `PALETTE_LENGTH` entries to rotate escaping points'
+    /// colors along the palette or gradient by, for palette cycling.
+    pub palette_offset: usize,
+
+    /// How to color points in the set, when the counts were rendered with
+    /// `Iteration::interior` set.
+    pub interior: Interior,
+
+    /// Draw points whose whole escape counts are positive multiples of this
+    /// in `CONTOUR_COLOR`, over whatever color they'd otherwise be.
+    pub contour: Option<usize>,
+
+    /// The width in pixels of the image the counts make up, to dither linear
+    /// and log shades with `dither` rather than round them down; `None` to
+    /// round them down.
+    pub dither: Option<usize>
+}
+
+/// The color `ColorScheme::contour` draws its lines in.
+pub const CONTOUR_COLOR : [u8; 3] = [0, 0, 0];
+
+impl Default for ColorScheme {
+
+    fn default() -> ColorScheme {
+        ColorScheme { palette: Palette::Grayscale, gradient: None, coloring: Coloring::Linear,
+                      invert: false, color_scale: 10.0, palette_offset: 0,
+                      interior: Interior::Black, contour: None, dither: None }
+    }
+}
+
+/// Turn the escape counts from `render` into an RGB image, three bytes per
+/// pixel, using `scheme`. `limit` must be the iteration limit the counts were
+/// computed with.
+///
+/// Histogram coloring only looks at whole counts, so smooth counts are
+/// rounded down for it. Trap distances and distance estimates are always
+/// shaded by `shade_trap` and `shade_distance`, whatever the coloring, and
+/// distance coloring of anything else falls back to linear coloring. With
+/// `scheme.invert` set, each shade is inverted before the palette lookup. A
+/// gradient is sampled at the shade's fraction of white: 0 for black, 1 for
+/// white. A palette offset rotates escaping points' shades, wrapping around
+/// past white, before they're inverted; points in the set stay black, unless
+/// the counts carry their orbits' last points, when `scheme.interior` shades
+/// them.
+///
+/// Rainbow and external angle coloring have no shades, so they skip the
+/// palette and gradient, and inverting complements the finished colors
+/// instead.
+///
+/// Contour lines are drawn last, over the finished colors, and only where
+/// there are whole counts to find multiples among: not over smooth counts,
+/// trap distances or distance estimates, nor rainbow or external angle
+/// colors.
+pub fn map_colors(counts : &Counts, limit : usize, scheme : &ColorScheme) -> Vec<u8> {
+
+    let rainbow_colors : Option<Vec<u8>> = match (counts, scheme.coloring) {
+        (Counts::Whole(counts), Coloring::Rainbow) => Some(counts.iter()
+            .flat_map(|&count| rainbow(count.map(|count| count as f64), scheme.color_scale))
+            .collect()),
+        (Counts::Smooth(counts), Coloring::Rainbow) =>
+            Some(counts.iter().flat_map(|&count| rainbow(count, scheme.color_scale)).collect()),
+        (Counts::Final(orbits), Coloring::ExternalAngle) =>
+            Some(orbits.iter().flat_map(|&(count, z)| external_angle(count, z)).collect()),
+        _ => None
+    };
+    if let Some(mut colors) = rainbow_colors {
+        if scheme.invert {
+            invert(&mut colors);
+        }
+        return colors;
+    }
+
+    // Escape counts under rainbow coloring were dealt with above.
+    let mut shades = shades(counts, limit, scheme);
+
+    let offset = (scheme.palette_offset % PALETTE_LENGTH) as u8;
+    if offset != 0 {
+        for (shade, escaped) in shades.iter_mut().zip(escaped(counts)) {
+            if escaped {
+                *shade = shade.wrapping_add(offset);
+            }
+        }
+    }
+
+    // Inverting the shade rather than the finished color keeps the result
+    // within the palette.
+    if scheme.invert {
+        invert(&mut shades);
+    }
+
+    let mut colors : Vec<u8> = match &scheme.gradient {
+        Some(gradient) => shades.into_iter()
+            .flat_map(|shade| sample_gradient(&gradient.stops, shade as f64 / 255.0))
+            .collect(),
+        None => shades.into_iter().flat_map(|shade| ramp(shade, scheme.palette)).collect()
+    };
+
+    if let Some(spacing) = scheme.contour {
+        let counts : Vec<Option<usize>> = match counts {
+            Counts::Whole(counts) => counts.clone(),
+            Counts::Final(orbits) => orbits.iter().map(|&(count, _)| count).collect(),
+            Counts::Periodic(orbits) => orbits.iter().map(|&(count, _)| count).collect(),
+            _ => Vec::new()
+        };
+        for (color, count) in colors.chunks_mut(3).zip(counts) {
+            if count.is_some_and(|count| count > 0 && count.is_multiple_of(spacing)) {
+                color.copy_from_slice(&CONTOUR_COLOR);
+            }
+        }
+    }
+
+    colors
+}
+
+/// The grayscale value `map_colors` gives each pixel of `counts`, before any
+/// palette offset or inversion.
+fn shades(counts : &Counts, limit : usize, scheme : &ColorScheme) -> Vec<u8> {
+
+    if let (Some(width), Some(levels)) = (scheme.dither, levels(counts, limit, scheme.coloring)) {
+        let width = width.max(1);
+        return levels.iter().enumerate()
+            .map(|(i, level)| level.map_or(0, |level| dither(level, (i % width, i / width))))
+            .collect();
+    }
+
+    match (counts, scheme.coloring) {
+        (Counts::Whole(counts),
+         Coloring::Linear | Coloring::Distance | Coloring::Rainbow | Coloring::ExternalAngle) =>
+            counts.iter().map(|&count| shade(count, limit)).collect(),
+        (Counts::Smooth(counts),
+         Coloring::Linear | Coloring::Distance | Coloring::Rainbow | Coloring::ExternalAngle) =>
+            counts.iter().map(|&count| shade_smooth(count, limit)).collect(),
+        (Counts::Whole(counts), Coloring::Log) =>
+            counts.iter().map(|&count| shade_log(count.map(|count| count as f64), limit)).collect(),
+        (Counts::Smooth(counts), Coloring::Log) =>
+            counts.iter().map(|&count| shade_log(count, limit)).collect(),
+        (Counts::Whole(counts), Coloring::Histogram) => histogram_shades(counts, limit),
+        (Counts::Whole(counts), Coloring::Normalize) => {
+            let counts : Vec<_> = counts.iter().map(|count| count.map(|count| count as f64)).collect();
+            normalized_shades(&counts)
+        }
+        (Counts::Smooth(counts), Coloring::Normalize) => normalized_shades(counts),
+        (Counts::Smooth(counts), Coloring::Histogram) => {
+            let whole : Vec<_> = counts.iter()
+                .map(|count| count.map(|count| count.max(0.0) as usize))
+                .collect();
+            histogram_shades(&whole, limit)
+        }
+        (Counts::Trapped(distances), _) => distances.iter().map(|&distance| shade_trap(distance)).collect(),
+        (Counts::Distance(distances), _) =>
+            distances.iter().map(|&distance| shade_distance(distance)).collect(),
+        (Counts::Final(orbits), _) => {
+            let counts = Counts::Whole(orbits.iter().map(|&(count, _)| count).collect());
+            let mut shades = shades(&counts, limit, scheme);
+            for (shade, &(count, z)) in shades.iter_mut().zip(orbits) {
+                if count.is_none() {
+                    *shade = shade_interior(z, scheme.interior);
+                }
+            }
+            shades
+        }
+        (Counts::Periodic(orbits), _) => {
+            let counts = Counts::Whole(orbits.iter().map(|&(count, _)| count).collect());
+            let mut shades = shades(&counts, limit, scheme);
+            for (shade, &(count, period)) in shades.iter_mut().zip(orbits) {
+                if count.is_none() {
+                    *shade = shade_period(period);
+                }
+            }
+            shades
+        }
+    }
+}
+
+/// The shades linear and log `coloring` give each pixel of `counts`, as
+/// `shade_smooth` and `shade_log` compute them but before they're rounded
+/// down to bytes, with `None` for points in the set. Other colorings, and
+/// counts other than whole or smooth ones, give `None` altogether.
+fn levels(counts : &Counts, limit : usize, coloring : Coloring) -> Option<Vec<Option<f64>>> {
+
+    let counts : Vec<Option<f64>> = match counts {
+        Counts::Whole(counts) => counts.iter().map(|count| count.map(|count| count as f64)).collect(),
+        Counts::Smooth(counts) => counts.clone(),
+        _ => return None
+    };
+    let limit = limit as f64;
+    let fraction = |count : f64| match coloring {
+        Coloring::Log => (count.clamp(0.0, limit) + 1.0).ln() / (limit + 1.0).ln(),
+        _ => count.clamp(0.0, limit) / limit
+    };
+
+    match coloring {
+        Coloring::Linear | Coloring::Distance | Coloring::Log => Some(counts.into_iter()
+            .map(|count| count.map(|count| 255.0 - fraction(count) * 255.0))
+            .collect()),
+        _ => None
+    }
+}
+
+/// The 4x4 Bayer matrix `dither` takes its thresholds from, by row and then
+/// column: each of 0 to 15 once, spread so that neighbouring thresholds are
+/// as far apart as they can be.
+pub const BAYER_4X4 : [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Quantize the shade `level`, from 0 to 255, to a byte by ordered
+/// dithering: round it up or down as its fractional part clears the
+/// threshold `BAYER_4X4` sets for the pixel at `(x, y)`, so a shade between
+/// two bytes comes out as a fine pattern of both, in proportion, rather than
+/// a band of one. The same pixel always dithers the same way, and a whole
+/// number comes through unchanged.
+pub fn dither(level : f64, (x, y) : (usize, usize)) -> u8 {
+
+    let threshold = (BAYER_4X4[y % 4][x % 4] as f64 + 0.5) / 16.0;
+    (level + threshold).floor().clamp(0.0, 255.0) as u8
+}
+
+/// Return whether each point of `counts` escaped. Every point escapes an
+/// orbit trap, since its distance is measured either way.
+fn escaped(counts : &Counts) -> Vec<bool> {
+
+    match counts {
+        Counts::Whole(counts) => counts.iter().map(Option::is_some).collect(),
+        Counts::Final(orbits) => orbits.iter().map(|(count, _)| count.is_some()).collect(),
+        Counts::Periodic(orbits) => orbits.iter().map(|(count, _)| count.is_some()).collect(),
+        Counts::Smooth(counts) | Counts::Distance(counts) => counts.iter().map(Option::is_some).collect(),
+        Counts::Trapped(distances) => vec![true; distances.len()]
+    }
+}
+
+/// Like `map_colors`, but with a fourth, opaque alpha byte after each
+/// pixel's red, green and blue, as a browser canvas's `ImageData` holds them.
+pub fn map_rgba(counts : &Counts, limit : usize, scheme : &ColorScheme) -> Vec<u8> {
+
+    map_colors(counts, limit, scheme).chunks(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect()
+}
+
+/// Like `map_rgba`, but with points in the set left fully transparent, for
+/// laying the image over a background: their alpha byte is 0, and every
+/// escaping point's is 255. Every point escapes an orbit trap, so a trapped
+/// image comes out opaque.
+pub fn map_rgba_transparent(counts : &Counts, limit : usize, scheme : &ColorScheme) -> Vec<u8> {
+
+    map_colors(counts, limit, scheme).chunks(3).zip(escaped(counts))
+        .flat_map(|(rgb, escaped)| [rgb[0], rgb[1], rgb[2], if escaped { 255 } else { 0 }])
+        .collect()
+}
+
+/// Paint each pixel of `pixels` that `mask` leaves clear, one entry for each
+/// pixel in row-major order, in `fill`, as `render_masked` leaves them
+/// unrendered. Pixels may be RGB or RGBA, worked out from the buffer's
+/// length; RGBA ones are also made fully transparent.
+pub fn fill_masked_out(pixels : &mut [u8], mask : &[bool], fill : [u8; 3]) {
+
+    let channels = pixels.len() / mask.len().max(1);
+    assert!(pixels.len() == mask.len() * channels && matches!(channels, 3 | 4));
+
+    for (pixel, _) in pixels.chunks_mut(channels).zip(mask).filter(|(_, &set)| !set) {
+        pixel[..3].copy_from_slice(&fill);
+        if channels == 4 {
+            pixel[3] = 0;
+        }
+    }
+}
+
+/// Map the grayscale value `v` from `shade` to a color from `palette`.
+fn ramp(v : u8, palette : Palette) -> [u8; 3] {
+
+    // Each channel of a three-stage ramp peaks a third of the way after the
+    // previous one.
+    let stage = |n : u32| (v as u32 * 3).saturating_sub(n * 255).min(255) as u8;
+
+    match palette {
+        Palette::Grayscale => [v, v, v],
+        Palette::Fire => [stage(0), stage(1), stage(2)],
+        Palette::Ocean => [stage(2), stage(1), stage(0)]
+    }
+}
+
+#[test]
+fn test_shade() {
+
+    assert_eq!(shade(None, 255), 0);
+    assert_eq!(shade(Some(0), 255), 255);
+    assert_eq!(shade(Some(100), 255), 155);
+    assert_eq!(shade(Some(999), 1000), 1);
+}
+
+#[test]
+fn test_shade_at_largest_limit() {
+
+    // Neither the shades nor the histogram may overflow, or try to allocate
+    // a bin for every possible count, at the largest limit --limit takes.
+    let limit = u32::MAX as usize;
+    assert_eq!(shade(Some(0), limit), 255);
+    assert_eq!(shade(Some(limit - 1), limit), 1);
+    assert_eq!(shade(Some(limit), limit), 0);
+    assert_eq!(shade(Some(limit / 2), limit), 128);
+    assert_eq!(shade_smooth(Some(limit as f64 * 2.0), limit), 0);
+    assert_eq!(shade_log(Some(0.0), limit), 255);
+    assert_eq!(shade_wide(Some(limit as f64 / 2.0), limit), 32768);
+    assert_eq!(colorize(Some(limit), limit, Palette::Grayscale), [0, 0, 0]);
+
+    let counts = [Some(0), Some(limit / 2), None, Some(limit - 1), Some(limit)];
+    assert_eq!(histogram_shades(&counts, limit), vec![255, 192, 0, 128, 64]);
+}
+
+#[test]
+fn test_higher_limit_reveals_boundary() {
+
+    use crate::escape_time;
+    use num::Complex;
+
+    // This point takes 315 iterations to escape, so a limit of 255 paints it
+    // as part of the set.
+    let point = Complex { re: -0.75, im: 0.01 };
+    let origin = Complex { re: 0.0, im: 0.0 };
+    assert_eq!(shade(escape_time(origin, point, 255, 2.0), 255), 0);
+    assert_eq!(escape_time(origin, point, 1000, 2.0), Some(315));
+    assert!(shade(escape_time(origin, point, 1000, 2.0), 1000) > 0);
+}
+
+#[test]
+fn test_colorize() {
+
+    for palette in [Palette::Grayscale, Palette::Fire, Palette::Ocean] {
+        assert_eq!(colorize(None, 255, palette), [0, 0, 0]);
+        assert_eq!(colorize(Some(0), 255, palette), [255, 255, 255]);
+    }
+
+    assert_eq!(colorize(Some(100), 255, Palette::Grayscale), [155, 155, 155]);
+    assert_eq!(colorize(Some(170), 255, Palette::Fire), [255, 0, 0]);
+    assert_eq!(colorize(Some(170), 255, Palette::Ocean), [0, 0, 255]);
+    assert_eq!(colorize(Some(85), 255, Palette::Fire), [255, 255, 0]);
+}
+
+#[test]
+fn test_parse_palette() {
+
+    assert_eq!("grayscale".parse(), Ok(Palette::Grayscale));
+    assert_eq!("fire".parse(), Ok(Palette::Fire));
+    assert_eq!("ocean".parse(), Ok(Palette::Ocean));
+    assert!("Fire".parse::<Palette>().is_err());
+}
+
+#[test]
+fn test_shade_smooth() {
+
+    assert_eq!(shade_smooth(None, 255), 0);
+    assert_eq!(shade_smooth(Some(100.0), 255), shade(Some(100), 255));
+    assert_eq!(shade_smooth(Some(100.5), 255), 155);
+    assert_eq!(shade_smooth(Some(99.5), 255), 156);
+    assert_eq!(shade_smooth(Some(-0.5), 255), 255);
+    assert_eq!(shade_smooth(Some(300.0), 255), 0);
+}
+
+#[test]
+fn test_parse_coloring() {
+
+    assert_eq!("linear".parse(), Ok(Coloring::Linear));
+    assert_eq!("histogram".parse(), Ok(Coloring::Histogram));
+    assert_eq!("distance".parse(), Ok(Coloring::Distance));
+    assert_eq!("log".parse(), Ok(Coloring::Log));
+    assert_eq!("rainbow".parse(), Ok(Coloring::Rainbow));
+    assert_eq!("normalize".parse(), Ok(Coloring::Normalize));
+    assert!("sqrt".parse::<Coloring>().is_err());
+}
+
+#[test]
+fn test_histogram_colors_use_full_range() {
+
+    use crate::{render, Iteration};
+    use num::Complex;
+
+    let bounds = (80, 60);
+    let iteration = Iteration { limit: 1000, ..Iteration::default() };
+    let Counts::Whole(counts) = render(bounds, Complex { re: -2.0, im: 1.2 },
+                                       Complex { re: 1.0, im: -1.2 }, iteration) else {
+        panic!("expected whole counts");
+    };
+
+    let linear : Vec<u8> = counts.iter().filter(|count| count.is_some())
+        .map(|&count| shade(count, iteration.limit)).collect();
+    let histogram : Vec<u8> = histogram_colors(&counts, iteration.limit, Palette::Grayscale)
+        .chunks(3).zip(&counts).filter(|(_, count)| count.is_some())
+        .map(|(pixel, _)| pixel[0]).collect();
+
+    // Nearly every escaping pixel escapes quickly, so the linear shades
+    // crowd together at the bright end; the histogram shades don't.
+    let range = |shades : &[u8]| shades.iter().max().unwrap() - shades.iter().min().unwrap();
+    let darker_than_half = |shades : &[u8]| shades.iter().filter(|&&v| v < 128).count();
+    assert!(range(&histogram) >= 250, "histogram shades span only {}", range(&histogram));
+    assert!(darker_than_half(&histogram) > darker_than_half(&linear) * 10);
+
+    assert_eq!(histogram_colors(&[None, Some(3)], 10, Palette::Fire), [0, 0, 0, 255, 255, 255]);
+}
+
+#[test]
+fn test_map_colors() {
+
+    let whole = Counts::Whole(vec![None, Some(0), Some(100), Some(170)]);
+    let fire = ColorScheme { palette: Palette::Fire, ..ColorScheme::default() };
+    assert_eq!(map_colors(&whole, 255, &fire),
+               [colorize(None, 255, Palette::Fire), colorize(Some(0), 255, Palette::Fire),
+                colorize(Some(100), 255, Palette::Fire), colorize(Some(170), 255, Palette::Fire)]
+               .concat());
+
+    let smooth = Counts::Smooth(vec![None, Some(99.5)]);
+    assert_eq!(map_colors(&smooth, 255, &ColorScheme::default()), [0, 0, 0, 156, 156, 156]);
+
+    let histogram = ColorScheme { coloring: Coloring::Histogram, ..ColorScheme::default() };
+    let Counts::Whole(counts) = &whole else { unreachable!() };
+    assert_eq!(map_colors(&whole, 255, &histogram), histogram_colors(counts, 255, Palette::Grayscale));
+    let smooth = Counts::Smooth(vec![None, Some(0.5), Some(100.25), Some(170.0)]);
+    assert_eq!(map_colors(&smooth, 255, &histogram),
+               histogram_colors(counts, 255, Palette::Grayscale));
+
+    assert_eq!(map_colors(&Counts::Trapped(vec![0.0, 1.5]), 255, &histogram), [255, 255, 255, 0, 0, 0]);
+}
+
+#[test]
+fn test_shade_distance() {
+
+    assert_eq!(shade_distance(None), 0);
+    assert_eq!(shade_distance(Some(0.0)), 0);
+    assert_eq!(shade_distance(Some(0.25)), 127);
+    assert_eq!(shade_distance(Some(5.0)), 255);
+
+    let distance = ColorScheme { coloring: Coloring::Distance, ..ColorScheme::default() };
+    assert_eq!(map_colors(&Counts::Distance(vec![None, Some(1.0)]), 255, &distance),
+               [0, 0, 0, 255, 255, 255]);
+}
+
+#[test]
+fn test_shade_trap() {
+
+    assert_eq!(shade_trap(0.0), 255);
+    assert_eq!(shade_trap(0.25), 127);
+    assert_eq!(shade_trap(1.0), 0);
+    assert_eq!(shade_trap(f64::INFINITY), 0);
+}
+
+#[test]
+fn test_map_rgba() {
+
+    let counts = Counts::Whole(vec![None, Some(0), Some(170)]);
+    let fire = ColorScheme { palette: Palette::Fire, ..ColorScheme::default() };
+    assert_eq!(map_rgba(&counts, 255, &fire), [0, 0, 0, 255, 255, 255, 255, 255, 255, 0, 0, 255]);
+}
+
+#[test]
+fn test_dither() {
+
+    // Every pixel of a 4x4 block rounds a fraction its own way, so a block
+    // of one shade averages out to it.
+    let block = |level| (0..16).map(|i| dither(level, (i % 4, i / 4)) as f64).sum::<f64>() / 16.0;
+    for level in [0.0, 17.25, 100.5, 254.9375] {
+        assert_eq!(block(level), level);
+    }
+
+    for i in 0..1000 {
+        let level = i as f64 * 0.2551;
+        let position = (i * 7, i / 3);
+        assert!((dither(level, position) as f64 - level.round()).abs() <= 1.0);
+        assert_eq!(dither(level.floor(), position), level.floor() as u8);
+        assert_eq!(dither(level, (position.0 + 4, position.1 + 8)), dither(level, position));
+    }
+    assert_eq!(dither(255.0, (3, 3)), 255);
+
+    // Dithering the linear shades of a gentle gradient changes some pixels,
+    // by at most one from the shade rounded down, and leaves points in the
+    // set black.
+    let counts = Counts::Smooth((0..64).map(|i| (i < 60).then_some(i as f64 * 0.1)).collect());
+    let plain = map_colors(&counts, 255, &ColorScheme::default());
+    let dithered = map_colors(&counts, 255, &ColorScheme { dither: Some(8), ..ColorScheme::default() });
+    assert_ne!(plain, dithered);
+    assert!(plain.iter().zip(&dithered).all(|(&a, &b)| (a as i32 - b as i32).abs() <= 1));
+    assert!(dithered[60 * 3..].iter().all(|&byte| byte == 0));
+
+    // Other colorings round as they always have.
+    let histogram = ColorScheme { coloring: Coloring::Histogram, ..ColorScheme::default() };
+    assert_eq!(map_colors(&counts, 255, &ColorScheme { dither: Some(8), ..histogram.clone() }),
+               map_colors(&counts, 255, &histogram));
+}
+
+#[test]
+fn test_fill_masked_out() {
+
+    let mut rgb = vec![1, 2, 3, 4, 5, 6];
+    fill_masked_out(&mut rgb, &[true, false], [9, 8, 7]);
+    assert_eq!(rgb, [1, 2, 3, 9, 8, 7]);
+
+    let mut rgba = vec![1, 2, 3, 255, 4, 5, 6, 255];
+    fill_masked_out(&mut rgba, &[false, true], [0, 0, 0]);
+    assert_eq!(rgba, [0, 0, 0, 0, 4, 5, 6, 255]);
+}
+
+#[test]
+fn test_map_rgba_transparent() {
+
+    use crate::{render, Iteration};
+    use num::Complex;
+
+    let iteration = Iteration { smooth: true, ..Iteration::default() };
+    let counts = render((35, 30), Complex { re: -2.5, im: 1.5 }, Complex { re: 1.0, im: -1.5 }, iteration);
+    let scheme = ColorScheme { palette: Palette::Ocean, ..ColorScheme::default() };
+    let rgba = map_rgba_transparent(&counts, 255, &scheme);
+    let Counts::Smooth(values) = &counts else { panic!("expected smooth counts") };
+
+    for (pixel, value) in rgba.chunks(4).zip(values) {
+        assert_eq!(pixel[3], if value.is_some() { 255 } else { 0 });
+    }
+    assert!(values.iter().any(Option::is_none) && values.iter().any(Option::is_some));
+
+    // The colors themselves are as `map_rgba` gives them.
+    let opaque = map_rgba(&counts, 255, &scheme);
+    assert!(rgba.chunks(4).zip(opaque.chunks(4)).all(|(a, b)| a[..3] == b[..3]));
+}
+
+#[test]
+fn test_map_gray16() {
+
+    assert_eq!(shade_wide(None, 1000), 0);
+    assert_eq!(shade_wide(Some(0.0), 1000), 65535);
+    assert_eq!(shade_wide(Some(500.0), 1000), 32768);
+
+    // Counts that share an 8-bit shade get distinct 16-bit ones.
+    assert_eq!(shade(Some(998), 1000), shade(Some(999), 1000));
+    assert!(shade_wide(Some(998.0), 1000) > shade_wide(Some(999.0), 1000));
+
+    assert_eq!(map_gray16(&Counts::Whole(vec![None, Some(0), Some(500)]), 1000), [0, 65535, 32768]);
+    assert_eq!(map_gray16(&Counts::Trapped(vec![0.0, 2.0]), 1000), [65535, 0]);
+    assert_eq!(map_gray16(&Counts::Distance(vec![None, Some(0.25), Some(2.0)]), 1000), [0, 32767, 65535]);
+}
+
+#[test]
+fn test_invert() {
+
+    let original : Vec<u8> = (0..=255).collect();
+    let mut pixels = original.clone();
+    invert(&mut pixels);
+    assert_eq!((pixels[0], pixels[255]), (255, 0));
+    invert(&mut pixels);
+    assert_eq!(pixels, original);
+
+    let mut wide = [0u16, 65535, 1000];
+    invert(&mut wide);
+    assert_eq!(wide, [65535, 0, 64535]);
+
+    // Under a palette, the shade is inverted before the lookup, so points in
+    // the set take the palette's brightest color rather than its complement.
+    let counts = Counts::Whole(vec![None, Some(0)]);
+    let inverted = ColorScheme { palette: Palette::Fire, invert: true, ..ColorScheme::default() };
+    assert_eq!(map_colors(&counts, 255, &inverted), [colorize(Some(0), 255, Palette::Fire),
+                                                    colorize(None, 255, Palette::Fire)].concat());
+}
+
+#[test]
+fn test_sample_gradient() {
+
+    let black_to_white = [Stop { position: 0.0, color: [0, 0, 0] },
+                          Stop { position: 1.0, color: [255, 255, 255] }];
+    assert_eq!(sample_gradient(&black_to_white, 0.5), [128, 128, 128]);
+    assert_eq!(sample_gradient(&black_to_white, 0.0), [0, 0, 0]);
+    assert_eq!(sample_gradient(&black_to_white, 1.0), [255, 255, 255]);
+    assert_eq!(sample_gradient(&black_to_white, -3.0), [0, 0, 0]);
+    assert_eq!(sample_gradient(&black_to_white, 7.0), [255, 255, 255]);
+
+    let stops = [Stop { position: 0.25, color: [255, 0, 0] }, Stop { position: 0.75, color: [0, 0, 255] }];
+    assert_eq!(sample_gradient(&stops, 0.1), [255, 0, 0]);
+    assert_eq!(sample_gradient(&stops, 0.5), [128, 0, 128]);
+}
+
+#[test]
+fn test_parse_gradient() {
+
+    let gradient = Gradient::parse("# sunset\n1.0, 255, 255, 0\n\n0 0 0 64\n0.5,200 40,0\n").unwrap();
+    assert_eq!(gradient.stops, [Stop { position: 0.0, color: [0, 0, 64] },
+                                Stop { position: 0.5, color: [200, 40, 0] },
+                                Stop { position: 1.0, color: [255, 255, 0] }]);
+
+    assert_eq!(Gradient::parse("0 0 0\n").unwrap_err(), "line 1: expected POSITION R G B, got '0 0 0'");
+    assert!(Gradient::parse("0.5 256 0 0").is_err());
+    assert!(Gradient::parse("nan 0 0 0").is_err());
+    assert!(Gradient::parse("# nothing\n").is_err());
+
+    // Gradients stand in for the palette in `map_colors`.
+    let scheme = ColorScheme { gradient: Some(gradient), ..ColorScheme::default() };
+    assert_eq!(map_colors(&Counts::Whole(vec![None, Some(0)]), 255, &scheme), [0, 0, 64, 255, 255, 0]);
+}
+
+#[test]
+fn test_shade_log() {
+
+    assert_eq!(shade_log(None, 1000), 0);
+    assert_eq!(shade_log(Some(0.0), 1000), 255);
+    assert_eq!(shade_log(Some(1000.0), 1000), 0);
+    assert_eq!(shade_log(Some(-1.0), 1000), 255);
+
+    // Near the boundary, where counts run high, the logarithm leaves much
+    // less difference between them than the linear shading does.
+    let linear = shade(Some(500), 1000) - shade(Some(900), 1000);
+    let log = shade_log(Some(500.0), 1000) - shade_log(Some(900.0), 1000);
+    assert!(log * 4 < linear, "log spread {} should be well under linear spread {}", log, linear);
+
+    let log = ColorScheme { coloring: Coloring::Log, ..ColorScheme::default() };
+    assert_eq!(map_colors(&Counts::Whole(vec![None, Some(0), Some(1000)]), 1000, &log),
+               [0, 0, 0, 255, 255, 255, 0, 0, 0]);
+}
+
+#[test]
+fn test_hsv_to_rgb() {
+
+    assert_eq!(hsv_to_rgb(0.0, 1.0, 1.0), [255, 0, 0]);
+    assert_eq!(hsv_to_rgb(60.0, 1.0, 1.0), [255, 255, 0]);
+    assert_eq!(hsv_to_rgb(120.0, 1.0, 1.0), [0, 255, 0]);
+    assert_eq!(hsv_to_rgb(240.0, 1.0, 1.0), [0, 0, 255]);
+    assert_eq!(hsv_to_rgb(300.0, 1.0, 1.0), [255, 0, 255]);
+    assert_eq!(hsv_to_rgb(30.0, 1.0, 1.0), [255, 128, 0]);
+    assert_eq!(hsv_to_rgb(480.0, 1.0, 1.0), [0, 255, 0]);
+    assert_eq!(hsv_to_rgb(-120.0, 1.0, 1.0), [0, 0, 255]);
+    assert_eq!(hsv_to_rgb(200.0, 0.0, 0.5), [128, 128, 128]);
+
+    assert_eq!(rainbow(None, 10.0), [0, 0, 0]);
+    assert_eq!(rainbow(Some(12.0), 10.0), [0, 255, 0]);
+    assert_eq!(rainbow(Some(36.0), 10.0), [255, 0, 0]);
+
+    let scheme = ColorScheme { coloring: Coloring::Rainbow, color_scale: 60.0, ..ColorScheme::default() };
+    assert_eq!(map_colors(&Counts::Smooth(vec![None, Some(2.0), Some(4.0)]), 255, &scheme),
+               [0, 0, 0, 0, 255, 0, 0, 0, 255]);
+    let inverted = ColorScheme { invert: true, ..scheme };
+    assert_eq!(map_colors(&Counts::Whole(vec![None, Some(0)]), 255, &inverted), [255, 255, 255, 0, 255, 255]);
+}
+
+#[test]
+fn test_normalize_uses_full_range() {
+
+    use crate::{render, Iteration};
+    use num::Complex;
+
+    // Deep in the seahorse valley, the escaping pixels' counts all lie
+    // within a narrow band.
+    let iteration = Iteration { limit: 2000, ..Iteration::default() };
+    let counts = render((60, 40), Complex { re: -0.7454, im: 0.1130 }, Complex { re: -0.7451, im: 0.1128 },
+                        iteration);
+    let normalize = ColorScheme { coloring: Coloring::Normalize, ..ColorScheme::default() };
+    let Counts::Whole(whole) = &counts else { unreachable!() };
+    let escaped = |pixels : &[u8]| -> Vec<u8> {
+        pixels.chunks(3).zip(whole).filter(|(_, count)| count.is_some()).map(|(pixel, _)| pixel[0]).collect()
+    };
+
+    let normalized = escaped(&map_colors(&counts, iteration.limit, &normalize));
+    assert_eq!((normalized.iter().min(), normalized.iter().max()), (Some(&0), Some(&255)));
+    let linear = escaped(&map_colors(&counts, iteration.limit, &ColorScheme::default()));
+    let spread = linear.iter().max().unwrap() - linear.iter().min().unwrap();
+    assert!(spread < 200, "linear shades already span {}", spread);
+
+    assert_eq!(map_colors(&Counts::Smooth(vec![None, Some(10.0), Some(12.5), Some(15.0)]), 1000, &normalize),
+               [0, 0, 0, 255, 255, 255, 127, 127, 127, 0, 0, 0]);
+    assert_eq!(map_colors(&Counts::Whole(vec![Some(7), None]), 1000, &normalize), [255, 255, 255, 0, 0, 0]);
+}
+
+#[test]
+fn test_palette_offset() {
+
+    let counts = Counts::Whole(vec![None, Some(0), Some(100), Some(254)]);
+    let fire = ColorScheme { palette: Palette::Fire, ..ColorScheme::default() };
+    let offset = |palette_offset| ColorScheme { palette_offset, ..fire.clone() };
+
+    assert_eq!(map_colors(&counts, 255, &offset(0)), map_colors(&counts, 255, &fire));
+    assert_eq!(map_colors(&counts, 255, &offset(PALETTE_LENGTH)), map_colors(&counts, 255, &fire));
+    assert_eq!(map_colors(&counts, 255, &offset(PALETTE_LENGTH + 3)), map_colors(&counts, 255, &offset(3)));
+
+    // Escaping points move along the palette, wrapping from white to black,
+    // while points in the set stay black.
+    let gray = ColorScheme { palette_offset: 1, ..ColorScheme::default() };
+    assert_eq!(map_colors(&counts, 255, &gray), [0, 0, 0, 0, 0, 0, 156, 156, 156, 2, 2, 2]);
+}
+
+#[test]
+fn test_palette_strip() {
+
+    let bounds = (512, 64);
+    for palette in [Palette::Grayscale, Palette::Fire, Palette::Ocean] {
+        let strip = palette_strip(bounds, 1000, palette);
+        assert_eq!(strip.len(), bounds.0 * bounds.1 * 3);
+
+        // The palette runs from its brightest color for a count of 0 to
+        // black at the limit, in every row.
+        let last = strip.len() - 3;
+        assert_eq!(strip[..3], colorize(Some(0), 1000, palette));
+        assert_eq!(strip[..3], ramp(255, palette));
+        assert_eq!(strip[bounds.0 * 3 - 3..bounds.0 * 3], colorize(Some(1000), 1000, palette));
+        assert_eq!(strip[last..], [0, 0, 0]);
+        assert_eq!(strip[bounds.0 * 3..bounds.0 * 6], strip[..bounds.0 * 3]);
+    }
+    assert_ne!(palette_strip(bounds, 10, Palette::Fire), palette_strip(bounds, 10, Palette::Ocean));
+}
+
+#[test]
+fn test_gamma() {
+
+    let identity = gamma_table(1.0);
+    assert!(identity.iter().enumerate().all(|(v, &entry)| entry as usize == v));
+
+    let bright = gamma_table(2.2);
+    assert_eq!((bright[0], bright[255]), (0, 255));
+    assert_eq!(bright[128], 186);
+    assert!(bright.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    let mut samples = [0, 64, 128, 255];
+    apply_gamma(&mut samples, &identity);
+    assert_eq!(samples, [0, 64, 128, 255]);
+    apply_gamma(&mut samples, &bright);
+    assert_eq!(samples, [0, bright[64], 186, 255]);
+}
+
+#[test]
+fn test_interior_coloring() {
+
+    use crate::Iteration;
+
+    // An orbit in the main cardioid settles on a single point, and one in the
+    // bulb to its left alternates between two, so though both stay in the
+    // set, they end up at different angles.
+    let iteration = Iteration { interior: true, ..Iteration::default() };
+    let points = [Complex { re: -0.1, im: 0.1 }, Complex { re: -1.0, im: 0.1 }, Complex { re: 1.0, im: 1.0 }];
+    let orbits : Vec<_> = points.iter().map(|&point| iteration.escape_time_final(point)).collect();
+    assert_eq!(orbits.iter().map(|&(count, _)| count).collect::<Vec<_>>(),
+               points.map(|point| iteration.escape_time(point)));
+    let counts = Counts::Final(orbits);
+
+    let angle = ColorScheme { interior: Interior::Angle, ..ColorScheme::default() };
+    let colors = map_colors(&counts, 255, &angle);
+    assert_ne!(colors[0..3], colors[3..6]);
+
+    // Black interiors color exactly as whole counts do.
+    let whole = Counts::Whole(points.map(|point| iteration.escape_time(point)).to_vec());
+    let black = ColorScheme::default();
+    assert_eq!(map_colors(&counts, 255, &black), map_colors(&whole, 255, &black));
+    assert_eq!(colors[6..9], map_colors(&whole, 255, &angle)[6..9]);
+
+    // By period, the cardioid is white and the bulb half as bright, while
+    // the escaping point keeps its escape-time shade.
+    let iteration = Iteration { max_period: 8, ..iteration };
+    let orbits = points.iter().map(|&point| iteration.escape_time_periodic(point)).collect();
+    let counts = Counts::Periodic(orbits);
+    let period = ColorScheme { interior: Interior::Period, ..ColorScheme::default() };
+    let colors = map_colors(&counts, 255, &period);
+    assert_eq!(colors[0..6], [255, 255, 255, 127, 127, 127]);
+    assert_eq!(colors[6..9], map_colors(&whole, 255, &period)[6..9]);
+    assert_eq!(shade_period(None), 0);
+    assert_eq!(shade_period(Some(3)), 85);
+    assert_eq!(shade_interior(Complex { re: 0.5, im: 0.5 }, Interior::Period), 0);
+    assert_eq!("period".parse(), Ok(Interior::Period));
+}
+
+#[test]
+fn test_contour_lines() {
+
+    use crate::Counts;
+
+    // Against an inverted palette, where they'd be light, only the positive
+    // multiples of 5 are drawn in.
+    let counts = Counts::Whole(vec![Some(4), Some(5), Some(6), Some(10), Some(0), None, Some(15), Some(14)]);
+    let scheme = ColorScheme { invert: true, contour: Some(5), ..ColorScheme::default() };
+    let colors = map_colors(&counts, 16, &scheme);
+    let plain = map_colors(&counts, 16, &ColorScheme { contour: None, ..scheme.clone() });
+    for (n, (color, plain)) in colors.chunks(3).zip(plain.chunks(3)).enumerate() {
+        if [1, 3, 6].contains(&n) {
+            assert_eq!(color, CONTOUR_COLOR, "pixel {}", n);
+            assert_ne!(plain, CONTOUR_COLOR, "pixel {}", n);
+        } else {
+            assert_eq!(color, plain, "pixel {}", n);
+        }
+    }
+
+    // Smooth counts have no whole multiples to draw.
+    let smooth = Counts::Smooth(vec![Some(5.0), Some(10.0)]);
+    assert_eq!(map_colors(&smooth, 16, &scheme), map_colors(&smooth, 16, &ColorScheme { contour: None,
+                                                                                     ..scheme }));
+}
+
+#[test]
+fn test_external_angle() {
+
+    // Two points escaping in opposite quadrants get opposite hues, whatever
+    // their counts, and points in the set stay black.
+    let counts = Counts::Final(vec![(Some(3), Complex { re: 2.5, im: 1.5 }),
+                                    (Some(3), Complex { re: -2.5, im: -1.5 }),
+                                    (Some(7), Complex { re: 3.0, im: 0.0 }),
+                                    (None, Complex { re: 0.5, im: 0.5 })]);
+    let scheme = ColorScheme { coloring: Coloring::ExternalAngle, ..ColorScheme::default() };
+    let colors = map_colors(&counts, 255, &scheme);
+    assert_ne!(colors[0..3], colors[3..6]);
+    let complement = colors[0..3].iter().map(|&channel| 255 - channel).collect::<Vec<_>>();
+    assert_eq!(colors[3..6], complement[..]);
+    assert_eq!(colors[6..12], [255, 0, 0, 0, 0, 0]);
+    assert_eq!(external_angle(Some(1), Complex { re: 0.0, im: 2.5 }), hsv_to_rgb(90.0, 1.0, 1.0));
+
+    // Without the final points to take angles from, the coloring is linear.
+    let whole = Counts::Whole(vec![Some(3), Some(7), None]);
+    assert_eq!(map_colors(&whole, 255, &scheme), map_colors(&whole, 255, &ColorScheme::default()));
+    assert_eq!("external-angle".parse(), Ok(Coloring::ExternalAngle));
+}
@@ -0,0 +1,84 @@
+//! Reading render settings from a TOML scene file.
+
+use serde::Deserialize;
+use std::fs;
+
+use crate::error::MandelError;
+
+/// The settings for a scene, as read by `load_config`.
+///
+/// Every field is optional, and is written just as it would be on the command
+/// line: `pixels = "1024x768"`, `upper_left = "-1.20,0.35"`, `palette = "fire"`
+/// and so on. Anything left out keeps its usual default, or must be given on
+/// the command line instead.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The image file to write.
+    pub filename: Option<String>,
+
+    /// The width and height of the image, as `WIDTHxHEIGHT`.
+    pub pixels: Option<String>,
+
+    /// The point at the upper-left corner of the image, as `RE,IM`.
+    pub upper_left: Option<String>,
+
+    /// The point at the lower-right corner of the image, as `RE,IM`.
+    pub lower_right: Option<String>,
+
+    /// The iteration limit for each point.
+    pub limit: Option<usize>,
+
+    /// The name of the palette to color escaping points with.
+    pub palette: Option<String>,
+
+    /// The name of the fractal to draw.
+    pub fractal: Option<String>
+}
+
+impl Config {
+
+    /// Parse the TOML in `text` as a scene, describing what's wrong with it if
+    /// it can't be parsed or has fields we don't know.
+    pub fn parse(text : &str) -> Result<Config, String> {
+
+        toml::from_str(text).map_err(|err| err.to_string())
+    }
+}
+
+/// Read the scene in the TOML file at `path`, reporting a missing, unreadable
+/// or malformed file as `MandelError::BadConfig`.
+pub fn load_config(path : &str) -> Result<Config, MandelError> {
+
+    let bad = |message : String| MandelError::BadConfig { path: path.to_string(), message };
+
+    let text = fs::read_to_string(path).map_err(|err| bad(err.to_string()))?;
+    Config::parse(&text).map_err(bad)
+}
+
+#[test]
+fn test_parse_config() {
+
+    let config = Config::parse(r#"
+        filename = "scene.png"
+        pixels = "1024x768"
+        upper_left = "-1.20,0.35"
+        lower_right = "-1,0.2"
+        limit = 1000
+        palette = "fire"
+    "#).unwrap();
+
+    assert_eq!(config, Config { filename: Some("scene.png".to_string()),
+                                pixels: Some("1024x768".to_string()),
+                                upper_left: Some("-1.20,0.35".to_string()),
+                                lower_right: Some("-1,0.2".to_string()),
+                                limit: Some(1000),
+                                palette: Some("fire".to_string()),
+                                fractal: None });
+
+    assert_eq!(Config::parse("").unwrap(), Config::default());
+    assert!(Config::parse("colour = \"fire\"").is_err());
+    assert!(Config::parse("limit = \"lots\"").is_err());
+    assert!(matches!(load_config("/nonexistent/scene.toml"),
+                     Err(MandelError::BadConfig { ref path, .. }) if path == "/nonexistent/scene.toml"));
+}
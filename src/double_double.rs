@@ -0,0 +1,154 @@
+//! Double-double arithmetic, for zooming deeper than `f64` alone allows.
+//!
+//! A `DoubleDouble` holds a number as the unevaluated sum of two `f64`s, the
+//! second far smaller than the first, for about 106 bits of mantissa instead
+//! of 53. Sums and products are computed exactly with the two-sum and
+//! two-product tricks, then rounded back into a pair.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// A number held as `hi + lo`, where `lo` is no more than half a unit in the
+/// last place of `hi`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DoubleDouble {
+    /// The `f64` nearest the number.
+    pub hi: f64,
+    /// What's left over: the number minus `hi`.
+    pub lo: f64
+}
+
+impl DoubleDouble {
+
+    /// Return `a + b` exactly.
+    pub fn sum(a : f64, b : f64) -> DoubleDouble {
+
+        let hi = a + b;
+        let b_part = hi - a;
+        DoubleDouble { hi, lo: (a - (hi - b_part)) + (b - b_part) }
+    }
+
+    /// Return `a * b` exactly, using a fused multiply-add for the part of
+    /// the product that rounding leaves out.
+    pub fn product(a : f64, b : f64) -> DoubleDouble {
+
+        let hi = a * b;
+        DoubleDouble { hi, lo: a.mul_add(b, -hi) }
+    }
+
+    /// Return the `f64` nearest this number.
+    pub fn to_f64(self) -> f64 {
+
+        self.hi
+    }
+
+    /// Return `hi + lo` as a pair again, when `lo` may have grown too large
+    /// to leave `hi` the nearest `f64`. `|hi|` must be at least `|lo|`.
+    fn renormalize(hi : f64, lo : f64) -> DoubleDouble {
+
+        let sum = hi + lo;
+        DoubleDouble { hi: sum, lo: lo - (sum - hi) }
+    }
+}
+
+impl From<f64> for DoubleDouble {
+
+    fn from(x : f64) -> DoubleDouble {
+        DoubleDouble { hi: x, lo: 0.0 }
+    }
+}
+
+impl Add for DoubleDouble {
+
+    type Output = DoubleDouble;
+
+    fn add(self, other : DoubleDouble) -> DoubleDouble {
+
+        let high = DoubleDouble::sum(self.hi, other.hi);
+        let low = DoubleDouble::sum(self.lo, other.lo);
+        let sum = DoubleDouble::renormalize(high.hi, high.lo + low.hi);
+        DoubleDouble::renormalize(sum.hi, sum.lo + low.lo)
+    }
+}
+
+impl Neg for DoubleDouble {
+
+    type Output = DoubleDouble;
+
+    fn neg(self) -> DoubleDouble {
+        DoubleDouble { hi: -self.hi, lo: -self.lo }
+    }
+}
+
+impl Sub for DoubleDouble {
+
+    type Output = DoubleDouble;
+
+    fn sub(self, other : DoubleDouble) -> DoubleDouble {
+        self + -other
+    }
+}
+
+impl Mul for DoubleDouble {
+
+    type Output = DoubleDouble;
+
+    fn mul(self, other : DoubleDouble) -> DoubleDouble {
+
+        let product = DoubleDouble::product(self.hi, other.hi);
+        DoubleDouble::renormalize(product.hi, product.lo + (self.hi * other.lo + self.lo * other.hi))
+    }
+}
+
+impl Mul<f64> for DoubleDouble {
+
+    type Output = DoubleDouble;
+
+    fn mul(self, other : f64) -> DoubleDouble {
+
+        let product = DoubleDouble::product(self.hi, other);
+        DoubleDouble::renormalize(product.hi, product.lo + self.lo * other)
+    }
+}
+
+impl Div<f64> for DoubleDouble {
+
+    type Output = DoubleDouble;
+
+    /// Divide in two steps: the first quotient is `hi / other`, and the
+    /// second divides what that leaves of the whole number.
+    fn div(self, other : f64) -> DoubleDouble {
+
+        let first = self.hi / other;
+        let product = DoubleDouble::product(first, other);
+        let remainder = DoubleDouble::sum(self.hi, -product.hi);
+        let second = (remainder.hi + (remainder.lo - product.lo + self.lo)) / other;
+        DoubleDouble::renormalize(first, second)
+    }
+}
+
+#[test]
+fn test_double_double_arithmetic() {
+
+    // A tenth can't be held exactly in either, but a double-double holds it
+    // about 2^53 times more closely.
+    let tenth = DoubleDouble::from(1.0) / 10.0;
+    assert_eq!(tenth.hi, 0.1);
+    assert!(tenth.lo != 0.0);
+    let error = (tenth * 10.0 - DoubleDouble::from(1.0)).to_f64().abs();
+    assert!(error < 1e-30, "{}", error);
+
+    // Sums and products keep what f64 would round away.
+    let tiny = 1e-20;
+    let sum = DoubleDouble::from(1.0) + DoubleDouble::from(tiny);
+    assert_eq!((sum.hi, sum.lo), (1.0, tiny));
+    assert_eq!((sum - DoubleDouble::from(1.0)).to_f64(), tiny);
+    assert_eq!(DoubleDouble::sum(1.0, tiny), sum);
+    let third = DoubleDouble::from(1.0) / 3.0;
+    let nine = (third * DoubleDouble::from(3.0)) * (third * DoubleDouble::from(27.0));
+    assert!((nine - DoubleDouble::from(9.0)).to_f64().abs() < 1e-29);
+    let product = DoubleDouble::product(1.0 + f64::EPSILON, 1.0 - f64::EPSILON);
+    assert_eq!((product.hi, product.lo), (1.0, -f64::EPSILON * f64::EPSILON));
+
+    assert_eq!(-DoubleDouble { hi: 1.0, lo: -tiny }, DoubleDouble { hi: -1.0, lo: tiny });
+    assert_eq!(DoubleDouble::from(0.5) * DoubleDouble::from(4.0), DoubleDouble::from(2.0));
+}
@@ -0,0 +1,232 @@
+//! The errors the command-line tool and library can report.
+
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong between reading the command line and writing
+/// the finished image.
+#[derive(Debug)]
+pub enum MandelError {
+    /// The image dimensions couldn't be parsed, or one of them is zero;
+    /// holds the offending text.
+    BadDimensions(String),
+
+    /// The image, at `bounds` pixels rendered `supersample` times over along
+    /// each side, would need more than `max` pixels, and maybe more memory
+    /// than there is.
+    TooManyPixels { bounds: (usize, usize), supersample: usize, max: usize },
+
+    /// A complex number couldn't be parsed. `argument` names what it was
+    /// meant to be, like `"upper-left corner"`, `value` holds the text, and
+    /// `reason` says what was wrong with it.
+    BadComplex { argument: &'static str, value: String, reason: ParseError },
+
+    /// A complex number parsed, but one of its parts is infinite or NaN, as
+    /// `"inf,0"` or `"1e400,0"` would be. The fields are as for `BadComplex`.
+    NotFinite { argument: &'static str, value: String },
+
+    /// An optional `--name value` flag was unrecognized or malformed.
+    BadFlag(String),
+
+    /// The wrong number of positional arguments was given.
+    WrongArgCount { expected: usize, got: usize },
+
+    /// The pool of rendering threads couldn't be started.
+    ThreadPool(rayon::ThreadPoolBuildError),
+
+    /// The upper-left corner isn't above and to the left of the lower-right
+    /// one, which would render the image mirrored or upside down. `part` is
+    /// `"real"` or `"imaginary"`, naming the coordinate that's out of order,
+    /// and `upper_left` and `lower_right` hold the two corners' values of it.
+    FlippedCorners { part: &'static str, upper_left: f64, lower_right: f64 },
+
+    /// Both corners have the same `value` for their `part`, either `"real"`
+    /// or `"imaginary"`, so the rectangle has no width or no height and every
+    /// pixel along that side would show the same point.
+    DegenerateCorners { part: &'static str, value: f64 },
+
+    /// The scene file named by `--config` couldn't be read or understood.
+    BadConfig { path: String, message: String },
+
+    /// The gradient file named by `--gradient` couldn't be read or understood.
+    BadGradient { path: String, message: String },
+
+    /// The tiles given to `stitch` couldn't be read, or don't fit together
+    /// into a grid; holds the reason.
+    BadTiles(String),
+
+    /// The images given to `compare` couldn't be read, or aren't the same
+    /// size and kind to compare; holds the reason.
+    BadComparison(String),
+
+    /// The images given to `compare` have `pixels` pixels that differ, by
+    /// up to `max` in some sample, which is more than `tolerance` allows.
+    ImagesDiffer { pixels: usize, max: u32, tolerance: u32 },
+
+    /// The mask named by `--mask` couldn't be read, or isn't the size of the
+    /// image; holds the reason.
+    BadMask(String),
+
+    /// An image given to `verify` couldn't be read, is the wrong size, or is
+    /// all one color; holds the reason.
+    FailedVerification(String),
+
+    /// The output filename's extension isn't a format we can write.
+    UnknownFormat(String),
+
+    /// Writing the image failed.
+    Io(io::Error)
+}
+
+impl fmt::Display for MandelError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        match self {
+            MandelError::BadDimensions(value) =>
+                write!(f, "error parsing image dimensions '{}': expected WIDTHxHEIGHT", value),
+            MandelError::TooManyPixels { bounds, supersample, max } => {
+                write!(f, "a {}x{} image", bounds.0, bounds.1)?;
+                if *supersample > 1 {
+                    write!(f, " supersampled {} times", supersample)?;
+                }
+                write!(f, " has more than {} pixels; pass --max-pixels to allow it", max)
+            }
+            MandelError::BadComplex { argument, value, reason } =>
+                write!(f, "error parsing {} '{}': {}", argument, value, reason),
+            MandelError::NotFinite { argument, value } =>
+                write!(f, "error parsing {} '{}': both parts must be finite numbers", argument, value),
+            MandelError::BadFlag(message) => write!(f, "{}", message),
+            MandelError::WrongArgCount { expected, got } =>
+                write!(f, "expected {} arguments, got {}", expected, got),
+            MandelError::FlippedCorners { part, upper_left, lower_right } => {
+                let relation = if *part == "real" { "less" } else { "greater" };
+                write!(f, "the upper-left corner's {} part {} must be {} than the lower-right corner's {}; \
+                           pass --allow-flipped to render a flipped image anyway",
+                       part, upper_left, relation, lower_right)
+            }
+            MandelError::DegenerateCorners { part, value } => {
+                let side = if *part == "real" { "width" } else { "height" };
+                write!(f, "the corners are degenerate: both have {} part {}, so the view has no {}",
+                       part, value, side)
+            }
+            MandelError::BadConfig { path, message } =>
+                write!(f, "error reading config '{}': {}", path, message),
+            MandelError::BadGradient { path, message } =>
+                write!(f, "error reading gradient '{}': {}", path, message),
+            MandelError::BadTiles(message) => write!(f, "can't stitch tiles: {}", message),
+            MandelError::BadComparison(message) => write!(f, "can't compare images: {}", message),
+            MandelError::ImagesDiffer { pixels, max, tolerance } =>
+                write!(f, "{} pixels differ, by up to {}, more than the tolerance of {}",
+                       pixels, max, tolerance),
+            MandelError::BadMask(message) => write!(f, "can't use mask: {}", message),
+            MandelError::FailedVerification(message) => write!(f, "verification failed: {}", message),
+            MandelError::ThreadPool(err) => write!(f, "error starting render threads: {}", err),
+            MandelError::UnknownFormat(filename) =>
+                write!(f, "don't know how to write '{}': use a .png or .ppm extension", filename),
+            MandelError::Io(err) => write!(f, "error writing image: {}", err)
+        }
+    }
+}
+
+/// Why a complex number like `"1.5,-0.25"` couldn't be parsed: which part
+/// was at fault, and the text of that part.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    /// There's no comma to split the real part from the imaginary part at.
+    MissingComma,
+
+    /// The `part`, either `"real"` or `"imaginary"`, isn't a number; `text`
+    /// holds it.
+    BadPart { part: &'static str, text: String },
+
+    /// The `part` is a number, but an infinite or NaN one.
+    NotFinite { part: &'static str, text: String }
+}
+
+impl fmt::Display for ParseError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        match self {
+            ParseError::MissingComma => write!(f, "expected RE,IM"),
+            ParseError::BadPart { part, text } => write!(f, "the {} part '{}' isn't a number", part, text),
+            ParseError::NotFinite { part, text } => write!(f, "the {} part '{}' isn't finite", part, text)
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl std::error::Error for MandelError {
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+
+        match self {
+            MandelError::ThreadPool(err) => Some(err),
+            MandelError::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for MandelError {
+
+    fn from(err: io::Error) -> MandelError {
+        MandelError::Io(err)
+    }
+}
+
+#[test]
+fn test_display() {
+
+    assert_eq!(MandelError::BadDimensions("10by10".to_string()).to_string(),
+               "error parsing image dimensions '10by10': expected WIDTHxHEIGHT");
+    assert_eq!(MandelError::TooManyPixels { bounds: (100000, 100000), supersample: 1, max: 256000000 }
+                   .to_string(),
+               "a 100000x100000 image has more than 256000000 pixels; pass --max-pixels to allow it");
+    assert_eq!(MandelError::TooManyPixels { bounds: (1000, 1000), supersample: 4, max: 100 }.to_string(),
+               "a 1000x1000 image supersampled 4 times has more than 100 pixels; \
+                pass --max-pixels to allow it");
+    assert_eq!(MandelError::BadComplex { argument: "upper-left corner", value: "1".to_string(),
+                                         reason: ParseError::MissingComma }.to_string(),
+               "error parsing upper-left corner '1': expected RE,IM");
+    let reason = ParseError::BadPart { part: "real", text: "1.2.3".to_string() };
+    assert_eq!(MandelError::BadComplex { argument: "center", value: "1.2.3,0.5".to_string(), reason }
+                   .to_string(),
+               "error parsing center '1.2.3,0.5': the real part '1.2.3' isn't a number");
+    assert_eq!(ParseError::NotFinite { part: "imaginary", text: "inf".to_string() }.to_string(),
+               "the imaginary part 'inf' isn't finite");
+    assert_eq!(MandelError::NotFinite { argument: "center", value: "inf,0".to_string() }.to_string(),
+               "error parsing center 'inf,0': both parts must be finite numbers");
+    assert_eq!(MandelError::WrongArgCount { expected: 4, got: 2 }.to_string(),
+               "expected 4 arguments, got 2");
+    assert_eq!(MandelError::FlippedCorners { part: "imaginary", upper_left: -1.0, lower_right: 1.5 }
+                   .to_string(),
+               "the upper-left corner's imaginary part -1 must be greater than the lower-right corner's \
+                1.5; pass --allow-flipped to render a flipped image anyway");
+    assert_eq!(MandelError::DegenerateCorners { part: "real", value: 0.5 }.to_string(),
+               "the corners are degenerate: both have real part 0.5, so the view has no width");
+    assert_eq!(MandelError::BadConfig { path: "scene.toml".to_string(), message: "bad limit".to_string() }
+                   .to_string(),
+               "error reading config 'scene.toml': bad limit");
+    assert_eq!(MandelError::BadGradient { path: "sunset.txt".to_string(), message: "no stops".to_string() }
+                   .to_string(),
+               "error reading gradient 'sunset.txt': no stops");
+    assert_eq!(MandelError::BadTiles("'b.png' is 4x4, but 'a.png' is 4x2".to_string()).to_string(),
+               "can't stitch tiles: 'b.png' is 4x4, but 'a.png' is 4x2");
+    assert_eq!(MandelError::BadComparison("'b.png' is 4x4, but 'a.png' is 4x2".to_string()).to_string(),
+               "can't compare images: 'b.png' is 4x4, but 'a.png' is 4x2");
+    assert_eq!(MandelError::ImagesDiffer { pixels: 3, max: 9, tolerance: 2 }.to_string(),
+               "3 pixels differ, by up to 9, more than the tolerance of 2");
+    assert_eq!(MandelError::BadMask("'m.png' is 4x4, but the image is 8x8".to_string()).to_string(),
+               "can't use mask: 'm.png' is 4x4, but the image is 8x8");
+    assert_eq!(MandelError::FailedVerification("every pixel of 'a.png' is the same color".to_string())
+                   .to_string(),
+               "verification failed: every pixel of 'a.png' is the same color");
+    assert_eq!(MandelError::UnknownFormat("out.gif".to_string()).to_string(),
+               "don't know how to write 'out.gif': use a .png or .ppm extension");
+
+    let io = MandelError::from(io::Error::new(io::ErrorKind::NotFound, "no such directory"));
+    assert_eq!(io.to_string(), "error writing image: no such directory");
+}
@@ -0,0 +1,1302 @@
+//! The escape-time iteration at the heart of every fractal.
+
+use num::{Complex, Float, Zero};
+use serde::{Deserialize, Serialize};
+use wide::f64x4;
+
+use crate::double_double::DoubleDouble;
+
+/// Try to determine if `c` is in the Mandelbrot set, using at most `limit' iterations to decide.
+///
+/// More generally, iterate `z = z * z + c` starting from `z0`: the Mandelbrot
+/// set starts every point at the origin, while Julia sets fix `c` and start
+/// from the point instead.
+///
+/// If `c` is not a member, return `Some(i)`, where `i` is the number of
+/// iterations it took for `c` to leave the circle of radius `escape_radius`
+/// centered on the origin. Any radius of at least 2 gives the same set,
+/// since no orbit that leaves the circle of radius 2 ever comes back; larger
+/// radii just take a few more iterations to reach. If `c` seems to be a
+/// member (more precisely, if we reached the iteration limit without being
+/// able to prove that `c` is not a member), return `None`.
+///
+/// Orbits starting at the origin skip the loop entirely if `c` lies in the
+/// main cardioid or the period-2 bulb, which never escape.
+///
+/// The arithmetic is done in whichever floating-point type the arguments
+/// use: `f32` is faster, but can't resolve points as close together as
+/// `f64`, so it's only good for shallow zooms.
+pub fn escape_time<T : Float>(z0 : Complex<T>, c : Complex<T>, limit:usize, escape_radius : T) -> Option<usize> {
+
+    Fractal::Mandelbrot.escape_time(z0, c, limit, escape_radius)
+}
+
+/// Like `escape_time` for the Mandelbrot set with an escape radius of 2, but
+/// for four values of `c` at once, each in its own lane of a SIMD vector.
+///
+/// Every lane takes the same steps, in the same order, as `escape_time`
+/// would, so the results match it exactly. A lane stops counting once its
+/// point escapes, and the loop ends when every lane has escaped or the limit
+/// is reached. Points in the cardioid or bulb never start counting, but there
+/// is no check for cycles, so other members of the set always take the full
+/// `limit` iterations.
+pub fn escape_time_simd(points : [Complex<f64>; 4], limit : usize) -> [Option<usize>; 4] {
+
+    let cr = f64x4::new(points.map(|c| c.re));
+    let ci = f64x4::new(points.map(|c| c.im));
+    let radius_sqr = f64x4::splat(4.0);
+
+    let mut zr = f64x4::ZERO;
+    let mut zi = f64x4::ZERO;
+    let mut counts = [None; 4];
+
+    // Bit `lane` is set while that lane is still counting.
+    let mut active = 0;
+    for (lane, &c) in points.iter().enumerate() {
+        if !in_cardioid_or_bulb(c) {
+            active |= 1 << lane;
+        }
+    }
+
+    for i in 0..limit {
+
+        if active == 0 {
+            break;
+        }
+
+        let escaped = (zr * zr + zi * zi).simd_gt(radius_sqr).to_bitmask() & active;
+        for (lane, count) in counts.iter_mut().enumerate() {
+            if escaped & (1 << lane) != 0 {
+                *count = Some(i);
+            }
+        }
+        active &= !escaped;
+
+        // The same operations as `z * z + c` on `Complex<f64>`.
+        let re = zr * zr - zi * zi + cr;
+        let im = zr * zi + zi * zr + ci;
+        zr = re;
+        zi = im;
+    }
+
+    counts
+}
+
+/// Like `escape_time`, for the Mandelbrot set and its Julia sets, but in
+/// double-double arithmetic, which resolves points about 2^53 times closer
+/// together than `f64` does.
+///
+/// Only the test against `escape_radius` is made in `f64`, since it needn't
+/// be exact. None of `escape_time`'s shortcuts are taken: deep views lie
+/// along the boundary, where telling a point from the cardioid or a cycle
+/// would need the same precision again, so every member of the set takes
+/// the full `limit` iterations.
+pub fn escape_time_double_double(z0 : Complex<DoubleDouble>, c : Complex<DoubleDouble>, limit : usize,
+                                 escape_radius : f64) -> Option<usize> {
+
+    let radius_sqr = escape_radius * escape_radius;
+    let (mut re, mut im) = (z0.re, z0.im);
+
+    for i in 0..limit {
+
+        let (re_sqr, im_sqr) = (re * re, im * im);
+        if (re_sqr + im_sqr).to_f64() > radius_sqr {
+            return Some(i);
+        }
+
+        im = re * im * 2.0 + c.im;
+        re = re_sqr - im_sqr + c.re;
+    }
+
+    None
+}
+
+/// Return the orbit of `c` under `z = z * z + c`: the successive values of
+/// `z`, starting from the origin, for as long as they stay within the circle
+/// of radius 2, and at most `limit` of them.
+///
+/// Each value is one that `escape_time` checked and found still inside the
+/// circle, so an escaping point's orbit is exactly as long as its escape
+/// count, and a member's orbit runs to the full `limit`. This is the same
+/// iteration with none of `escape_time`'s shortcuts, for looking at orbits
+/// rather than rendering them.
+pub fn orbit(c : Complex<f64>, limit : usize) -> impl Iterator<Item = Complex<f64>> {
+
+    std::iter::successors(Some(Complex::new(0.0, 0.0)), move |&z| Some(z * z + c))
+        .take(limit)
+        .take_while(|z| z.norm_sqr() <= 4.0)
+}
+
+/// Return the orbit of the center of the rectangle from `upper_left` to
+/// `lower_right`, as `orbit` does, collected: the reference orbit that
+/// perturbation rendering measures every other pixel's orbit against.
+pub fn reference_orbit(upper_left : Complex<f64>,
+                       lower_right : Complex<f64>,
+                       limit : usize) -> Vec<Complex<f64>> {
+
+    orbit((upper_left + lower_right) / 2.0, limit).collect()
+}
+
+/// The escape radius the free function `escape_time_smooth` uses. The
+/// fractional part of the smooth count is only accurate once `|z|` is large
+/// compared to `c`.
+pub const SMOOTH_RADIUS : f64 = 65536.0;
+
+/// The bailout radius smooth coloring uses unless told otherwise, instead
+/// of the faster radius 2. It's far enough out for the fraction to be within a
+/// hair of `SMOOTH_RADIUS`'s, in less than half the steps from there.
+pub const SMOOTH_BAILOUT : f64 = 256.0;
+
+/// Like `escape_time`, but return a fractional escape count that varies
+/// continuously across the plane instead of jumping at each whole iteration.
+///
+/// If the orbit of `z0` leaves the circle of radius `SMOOTH_RADIUS` on
+/// iteration `i` at `z`, return `i + 1 - log2(ln|z| / ln SMOOTH_RADIUS)`,
+/// which lies between `i` and `i + 1`. This is the usual normalized count
+/// `i + 1 - ln(ln|z|)/ln(2)`, shifted by the constant `log2(ln SMOOTH_RADIUS)`
+/// so that it stays in step with the integer count. Members return `None`.
+pub fn escape_time_smooth<T : Float>(z0 : Complex<T>, c : Complex<T>, limit : usize) -> Option<f64> {
+
+    Fractal::Mandelbrot.escape_time_smooth(z0, c, limit, float(SMOOTH_RADIUS))
+}
+
+/// Estimate the distance from `c` to the nearest point of the Mandelbrot set,
+/// or return `None` if `c` seems to be a member.
+///
+/// Alongside the orbit `z`, this follows its derivative with respect to `c`,
+/// `dz = 2 * z * dz + 1`. Once the orbit leaves the circle of radius
+/// `SMOOTH_RADIUS`, the distance is about `2 * |z| * ln|z| / |dz|`. Unlike
+/// escape counts, the estimate shrinks steadily towards filaments of the
+/// set far thinner than a pixel, so they can be drawn however thin they are.
+pub fn distance_estimate(c : Complex<f64>, limit : usize) -> Option<f64> {
+
+    if in_cardioid_or_bulb(c) {
+        return None;
+    }
+
+    estimate_distance(Complex::new(0.0, 0.0), Complex::new(0.0, 0.0), c, Complex::new(1.0, 0.0), limit)
+}
+
+/// Follow the orbit of `z0` under `z * z + c` along with its derivative,
+/// which starts at `dz0` and gains `dc` each step, and turn them into a
+/// distance estimate as `distance_estimate` describes.
+fn estimate_distance(z0 : Complex<f64>, dz0 : Complex<f64>, c : Complex<f64>, dc : Complex<f64>,
+                     limit : usize) -> Option<f64> {
+
+    let mut z = z0;
+    let mut dz = dz0;
+
+    for _ in 0..limit {
+
+        if z.norm_sqr() > SMOOTH_RADIUS * SMOOTH_RADIUS {
+            let modulus = z.norm();
+            return Some(2.0 * modulus * modulus.ln() / dz.norm());
+        }
+
+        (z, dz) = step_with_derivative(z, dz, c, dc);
+    }
+
+    None
+}
+
+/// Take one step of the orbit `z * z + c`, and of its derivative `dz`, which
+/// gains `dc` each step: 1 when differentiating by `c`, 0 when by `z0`.
+fn step_with_derivative(z : Complex<f64>, dz : Complex<f64>, c : Complex<f64>, dc : Complex<f64>)
+    -> (Complex<f64>, Complex<f64>) {
+
+    (z * z + c, z * dz * 2.0 + dc)
+}
+
+/// The escape-time fractals we can draw, each defined by the step that takes
+/// `z` to the next point of its orbit.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fractal {
+    /// `z * z + c`.
+    Mandelbrot,
+    /// `(|Re z| + i|Im z|)^2 + c`.
+    BurningShip,
+    /// `conj(z)^2 + c`, also called the Mandelbar set.
+    Tricorn,
+    /// `z^d + c`, for the given power `d` of at least 3: the Multibrot sets.
+    /// A power of 2 is `Mandelbrot`, which has faster ways to compute it.
+    Multibrot(u32),
+    /// `z^d + c`, for a fractional power `d` greater than 1, or a negative
+    /// one, taken on the principal branch. `0^d` counts as 0 even when `d`
+    /// is negative, so that orbits starting at the origin have somewhere to go.
+    FractionalMultibrot(f64)
+}
+
+impl std::str::FromStr for Fractal {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Fractal, String> {
+
+        match s {
+            "mandelbrot" => Ok(Fractal::Mandelbrot),
+            "burningship" => Ok(Fractal::BurningShip),
+            "tricorn" => Ok(Fractal::Tricorn),
+            _ => Err(format!("unknown fractal '{}'", s))
+        }
+    }
+}
+
+impl Fractal {
+
+    /// Return the point after `z` in an orbit with constant `c`.
+    pub fn step<T : Float>(self, z : Complex<T>, c : Complex<T>) -> Complex<T> {
+
+        match self {
+            Fractal::Mandelbrot => z * z + c,
+            Fractal::BurningShip => {
+                let folded = Complex { re: z.re.abs(), im: z.im.abs() };
+                folded * folded + c
+            }
+            Fractal::Tricorn => z.conj() * z.conj() + c,
+            Fractal::Multibrot(power) => z.powu(power) + c,
+            Fractal::FractionalMultibrot(_) if z.is_zero() => c,
+            Fractal::FractionalMultibrot(power) => z.powf(float(power)) + c
+        }
+    }
+
+    /// The power `z` is raised to in each step.
+    pub fn power(self) -> f64 {
+
+        match self {
+            Fractal::Multibrot(power) => power as f64,
+            Fractal::FractionalMultibrot(power) => power,
+            _ => 2.0
+        }
+    }
+
+    /// Like the free function `escape_time`, but for this fractal. Only the
+    /// Mandelbrot set has the cardioid and bulb shortcut.
+    pub fn escape_time<T : Float>(self, z0 : Complex<T>, c : Complex<T>, limit : usize, escape_radius : T)
+        -> Option<usize> {
+
+        if self.has_cardioid_at(z0, c) {
+            return None;
+        }
+
+        match follow_orbit(self, z0, c, limit, escape_radius * escape_radius) {
+            Fate::Escaped { iterations, .. } => Some(iterations),
+            Fate::Bounded { .. } => None // no escape time (assumed infinite)
+        }
+    }
+
+    /// Like `escape_time`, but counting the orbit as escaping at the first
+    /// step that takes `|z|` to more than `factor` times what it was, rather
+    /// than at the first point outside a circle: return the number of steps
+    /// taken, that one included, or `None` if no step in `limit` grows that
+    /// much. Steps from the origin, which any growth at all outstrips, don't
+    /// count. Neither the cardioid shortcut nor the watch for cycles applies,
+    /// since an orbit that stays bounded can still grow quickly for a step.
+    pub fn escape_time_growth<T : Float>(self, z0 : Complex<T>, c : Complex<T>, limit : usize, factor : T)
+        -> Option<usize> {
+
+        let factor_sqr = factor * factor;
+        let mut z = z0;
+
+        for i in 0..limit {
+
+            let next = self.step(z, c);
+            let (before, after) = (z.norm_sqr(), next.norm_sqr());
+            // An orbit that overflows has outgrown any factor, though the
+            // ratio of its infinities would be NaN.
+            if !after.is_finite() || (!before.is_zero() && after > factor_sqr * before) {
+                return Some(i + 1);
+            }
+            z = next;
+        }
+
+        None
+    }
+
+    /// Like the free function `escape_time_smooth`, but for this fractal,
+    /// escaping at `escape_radius` instead of `SMOOTH_RADIUS`: the larger it
+    /// is, the closer the fraction comes to exact. Far from the origin, each
+    /// step raises `|z|` to the fractal's power, so the fraction is taken in
+    /// logarithms to that base. Powers of 1 or less don't grow `|z|` that
+    /// way, and get no fraction.
+    pub fn escape_time_smooth<T : Float>(self, z0 : Complex<T>, c : Complex<T>, limit : usize,
+                                         escape_radius : T) -> Option<f64> {
+
+        if self.has_cardioid_at(z0, c) {
+            return None;
+        }
+
+        match follow_orbit(self, z0, c, limit, escape_radius * escape_radius) {
+            Fate::Escaped { iterations, z } => {
+                let log_ratio = float::<f64>(z.norm()).ln() / float::<f64>(escape_radius).ln();
+                let power = self.power();
+                let fraction = if power == 2.0 {
+                    log_ratio.log2()
+                } else if power > 1.0 {
+                    log_ratio.ln() / power.ln()
+                } else {
+                    0.0
+                };
+                Some(iterations as f64 + 1.0 - fraction)
+            }
+            Fate::Bounded { .. } => None
+        }
+    }
+
+    /// Like `escape_time`, but also return the closest the orbit came to
+    /// `trap`, measured after each step.
+    ///
+    /// The distance is wanted for points in the set too, so this always
+    /// follows the orbit to the limit or until it escapes, without taking the
+    /// shortcuts `escape_time` does. The count is the same as `escape_time`'s.
+    pub fn escape_time_trapped<T : Float>(self, z0 : Complex<T>, c : Complex<T>, limit : usize,
+                                          escape_radius : T, trap : Trap) -> (Option<usize>, f64) {
+
+        let radius_sqr = escape_radius * escape_radius;
+        let mut z = z0;
+        let mut nearest = f64::INFINITY;
+
+        for i in 0..limit {
+
+            if z.norm_sqr() > radius_sqr {
+                return (Some(i), nearest);
+            }
+
+            z = self.step(z, c);
+            nearest = nearest.min(trap.distance(Complex { re: float(z.re), im: float(z.im) }));
+        }
+
+        (None, nearest)
+    }
+
+    /// Like `escape_time`, but also return the last point of the orbit: where
+    /// it escaped, or where it was after `limit` steps if it never did.
+    ///
+    /// Points in the set need their final `z` too, so like
+    /// `escape_time_trapped` this takes no shortcuts.
+    pub fn escape_time_final<T : Float>(self, z0 : Complex<T>, c : Complex<T>, limit : usize,
+                                        escape_radius : T) -> (Option<usize>, Complex<f64>) {
+
+        let radius_sqr = escape_radius * escape_radius;
+        let mut z = z0;
+
+        for i in 0..limit {
+
+            if z.norm_sqr() > radius_sqr {
+                return (Some(i), Complex { re: float(z.re), im: float(z.im) });
+            }
+
+            z = self.step(z, c);
+        }
+
+        (None, Complex { re: float(z.re), im: float(z.im) })
+    }
+
+    /// Like `escape_time`, but for points that never escape, also return the
+    /// period of the cycle their orbit is drawn into: the `limit` steps let
+    /// it settle, and then `find_period` looks for a cycle of up to
+    /// `max_period` steps. Escaping points, and those whose orbits haven't
+    /// settled closely enough by then, have no period.
+    pub fn escape_time_periodic<T : Float>(self, z0 : Complex<T>, c : Complex<T>, limit : usize,
+                                           escape_radius : T, max_period : usize)
+        -> (Option<usize>, Option<usize>) {
+
+        match self.escape_time_final(z0, c, limit, escape_radius) {
+            (None, z) => {
+                let c = Complex { re: float(c.re), im: float(c.im) };
+                (None, self.find_period(z, c, max_period, PERIOD_EPSILON))
+            }
+            (count, _) => (count, None)
+        }
+    }
+
+    /// Return the smallest number of steps, up to `max_period`, after which
+    /// the orbit of `z` returns to within `epsilon` of `z`, or `None` if it
+    /// doesn't. Starting from a point an orbit has already settled at, this
+    /// is the period of the attracting cycle it settled into: 1 in the main
+    /// cardioid, 2 in the bulb to its left, and so on.
+    pub fn find_period(self, z : Complex<f64>, c : Complex<f64>, max_period : usize, epsilon : f64)
+        -> Option<usize> {
+
+        let mut w = z;
+        for period in 1..=max_period {
+            w = self.step(w, c);
+            if (w - z).norm_sqr() < epsilon * epsilon {
+                return Some(period);
+            }
+        }
+
+        None
+    }
+
+    /// Return true if the orbit of `z0` is known to stay bounded by
+    /// `in_cardioid_or_bulb`.
+    fn has_cardioid_at<T : Float>(self, z0 : Complex<T>, c : Complex<T>) -> bool {
+
+        self == Fractal::Mandelbrot && z0 == Complex::new(T::zero(), T::zero()) && in_cardioid_or_bulb(c)
+    }
+}
+
+/// A shape to measure orbits against for orbit-trap coloring.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trap {
+    /// A single point.
+    Point(Complex<f64>),
+    /// The real and imaginary axes, measuring to whichever is nearer.
+    Cross
+}
+
+impl std::str::FromStr for Trap {
+
+    type Err = String;
+
+    /// Parse `point`, a trap at the origin, or `cross`.
+    fn from_str(s: &str) -> Result<Trap, String> {
+
+        match s {
+            "point" => Ok(Trap::Point(Complex { re: 0.0, im: 0.0 })),
+            "cross" => Ok(Trap::Cross),
+            _ => Err(format!("unknown orbit trap '{}'", s))
+        }
+    }
+}
+
+impl Trap {
+
+    /// Return the distance from `z` to the nearest part of this trap.
+    pub fn distance(self, z : Complex<f64>) -> f64 {
+
+        match self {
+            Trap::Point(point) => (z - point).norm(),
+            Trap::Cross => z.re.abs().min(z.im.abs())
+        }
+    }
+}
+
+/// How `follow_orbit` left an orbit.
+#[derive(Debug, PartialEq)]
+enum Fate<T> {
+    /// The orbit left the escape circle at `z`, after `iterations` steps.
+    Escaped { iterations: usize, z: Complex<T> },
+
+    /// The orbit was still inside the circle when we stopped, after
+    /// `iterations` steps: either the limit was reached or it was caught in
+    /// a cycle.
+    Bounded { iterations: usize }
+}
+
+/// Orbits closer than this (squared) to an earlier point are taken to be
+/// repeating. It is far below the spacing of any orbit that escapes, so
+/// escape counts are unaffected.
+const PERIOD_EPSILON_SQR : f64 = 1e-24;
+
+/// How close, by default, an orbit must come back to where it settled for
+/// `Fractal::escape_time_periodic` to count it as having gone once around
+/// its cycle. Orbits near the edge of a bulb settle slowly, so this is far
+/// looser than `follow_orbit`'s test.
+pub const PERIOD_EPSILON : f64 = 1e-6;
+
+/// The longest cycle `--interior period` looks for unless told otherwise.
+pub const DEFAULT_MAX_PERIOD : usize = 32;
+
+/// How much a step must grow `|z|` by for `--escape growth` to count the
+/// orbit as escaping, unless told otherwise.
+pub const DEFAULT_GROWTH_FACTOR : f64 = 2.0;
+
+/// Iterate `fractal`'s step from `z0` until `|z|^2` exceeds `radius_sqr` or
+/// `limit` iterations have passed.
+///
+/// Interior orbits usually settle into a cycle long before the limit, so
+/// this also watches for a repeated `z`, using Brent's method: a reference
+/// point is saved at steps 1, 2, 4, 8, and so on, and each new `z` is
+/// compared against it. Any cycle whose length is at most the current
+/// saving interval is noticed within two intervals of the orbit settling,
+/// and the orbit is then known never to escape.
+fn follow_orbit<T : Float>(fractal : Fractal, z0 : Complex<T>, c : Complex<T>, limit : usize, radius_sqr : T)
+    -> Fate<T> {
+
+    let epsilon_sqr : T = float(PERIOD_EPSILON_SQR);
+
+    let mut z = z0;
+    let mut reference = z0;
+    let mut interval = 1;
+    let mut since_reference = 0;
+
+    for i in 0..limit {
+
+        if z.norm_sqr() > radius_sqr {
+            return Fate::Escaped { iterations: i, z };
+        }
+
+        z = fractal.step(z, c);
+
+        if (z - reference).norm_sqr() < epsilon_sqr {
+            return Fate::Bounded { iterations: i + 1 };
+        }
+
+        since_reference += 1;
+        if since_reference == interval {
+            reference = z;
+            since_reference = 0;
+            interval *= 2;
+        }
+    }
+
+    Fate::Bounded { iterations: limit }
+}
+
+/// Return true if `c` lies in the Mandelbrot set's main cardioid or its
+/// period-2 bulb, the disk of radius 1/4 around -1.
+///
+/// Together these cover most of the set's area, and every point inside them
+/// iterates the full limit without escaping, so testing for them first saves
+/// a great deal of time on views that include them.
+pub fn in_cardioid_or_bulb<T : Float>(c : Complex<T>) -> bool {
+
+    let x = c.re - float(0.25);
+    let y2 = c.im * c.im;
+    let q = x * x + y2;
+    let one = T::one();
+
+    q * (q + x) <= y2 / float(4.0) || (c.re + one) * (c.re + one) + y2 <= one / float(16.0)
+}
+
+/// Convert `x` to the floating-point type `T`, which can't fail between
+/// `f32` and `f64`: values out of `f32`'s range become infinities.
+fn float<T : Float>(x : impl Float) -> T {
+
+    T::from(x).unwrap()
+}
+
+/// The floating-point type to do each point's arithmetic in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Precision {
+    /// `f32`: faster, but only good for shallow zooms.
+    Single,
+    /// `f64`: slower, but able to zoom about a billion times deeper.
+    Double,
+    /// Double-double, with `escape_time_double_double`: much slower again,
+    /// and only for whole escape counts of the Mandelbrot set and its Julia
+    /// sets. The other kinds of counts, and other fractals, fall back to
+    /// `f64`. `render` also works out each pixel's point in double-double.
+    DoubleDouble
+}
+
+impl std::str::FromStr for Precision {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Precision, String> {
+
+        match s {
+            "f32" => Ok(Precision::Single),
+            "f64" => Ok(Precision::Double),
+            "dd" => Ok(Precision::DoubleDouble),
+            _ => Err(format!("unknown precision '{}'", s))
+        }
+    }
+}
+
+/// How to decide that an orbit has escaped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Escape {
+    /// When it leaves the circle of the bailout radius, as
+    /// `Fractal::escape_time` does.
+    Radius,
+    /// When one step grows `|z|` by more than some factor, as
+    /// `Fractal::escape_time_growth` does.
+    Growth
+}
+
+impl std::str::FromStr for Escape {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Escape, String> {
+
+        match s {
+            "radius" => Ok(Escape::Radius),
+            "growth" => Ok(Escape::Growth),
+            _ => Err(format!("unknown escape test '{}'", s))
+        }
+    }
+}
+
+/// The iteration to run for each point of an image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Iteration {
+    /// Maximum number of iterations to spend on each point.
+    pub limit: usize,
+
+    /// The constant `c` for a Julia set, or `None` to draw the fractal
+    /// itself, with each point as its own `c`.
+    pub julia: Option<Complex<f64>>,
+
+    /// Whether to compute fractional escape counts with `escape_time_smooth`.
+    pub smooth: bool,
+
+    /// The radius `escape_time` treats as escaping; at least 2.
+    pub bailout: f64,
+
+    /// The factor by which one step must grow `|z|` for `escape_time` to
+    /// count the orbit as escaping, with `escape_time_growth`, instead of
+    /// leaving the circle of radius `bailout`; or `None` to use the circle.
+    pub growth: Option<f64>,
+
+    /// Which fractal's step to iterate.
+    pub fractal: Fractal,
+
+    /// A trap to measure each orbit's closest approach to, instead of
+    /// counting its escape time.
+    pub trap: Option<Trap>,
+
+    /// The floating-point type to iterate in.
+    pub precision: Precision,
+
+    /// Whether to estimate each point's distance from the set with
+    /// `distance_estimate`, instead of counting its escape time. Only the
+    /// Mandelbrot set and its Julia sets have distance estimates, and they
+    /// are always computed in `f64`.
+    pub distance: bool,
+
+    /// Whether to keep the last point of each orbit from
+    /// `escape_time_final` along with its count, for coloring the points
+    /// that never escape, or the escaping ones by the angle they escape at.
+    pub interior: bool,
+
+    /// The longest cycle to look for, with `escape_time_periodic`, in the
+    /// orbits of points that never escape, so they can be colored by its
+    /// period; or 0 not to look.
+    pub max_period: usize,
+
+    /// The width and height of the square blocks `render` evaluates pixels
+    /// in, one block after another, or 0 to evaluate them row by row. Only
+    /// the order changes, never the counts.
+    pub tile_size: usize
+}
+
+impl Default for Iteration {
+
+    /// The classic Mandelbrot set: 255 iterations, escaping at radius 2.
+    fn default() -> Iteration {
+        Iteration {
+            limit: 255,
+            julia: None,
+            smooth: false,
+            bailout: 2.0,
+            growth: None,
+            fractal: Fractal::Mandelbrot,
+            trap: None,
+            precision: Precision::Double,
+            distance: false,
+            interior: false,
+            max_period: 0,
+            tile_size: 0
+        }
+    }
+}
+
+impl Iteration {
+
+    /// Run `escape_time` for the pixel at `point`, or `escape_time_growth`
+    /// if there's a `growth` factor.
+    pub fn escape_time(&self, point : Complex<f64>) -> Option<usize> {
+
+        match (self.precision, self.growth) {
+            (Precision::DoubleDouble, None) if self.fractal == Fractal::Mandelbrot => {
+                self.escape_time_double_double(Complex { re: point.re.into(), im: point.im.into() })
+            }
+            (Precision::Single, None) => {
+                let (z0, c) = self.orbit::<f32>(point);
+                self.fractal.escape_time(z0, c, self.limit, float(self.bailout))
+            }
+            (Precision::Double | Precision::DoubleDouble, None) => {
+                let (z0, c) = self.orbit::<f64>(point);
+                self.fractal.escape_time(z0, c, self.limit, self.bailout)
+            }
+            (Precision::Single, Some(factor)) => {
+                let (z0, c) = self.orbit::<f32>(point);
+                self.fractal.escape_time_growth(z0, c, self.limit, float(factor))
+            }
+            (Precision::Double | Precision::DoubleDouble, Some(factor)) => {
+                let (z0, c) = self.orbit::<f64>(point);
+                self.fractal.escape_time_growth(z0, c, self.limit, factor)
+            }
+        }
+    }
+
+    /// Run `escape_time_double_double` for the pixel at `point`, given in
+    /// double-double so it can lie between two `f64`s. `fractal` and
+    /// `growth` are ignored.
+    pub fn escape_time_double_double(&self, point : Complex<DoubleDouble>) -> Option<usize> {
+
+        match self.julia {
+            None => escape_time_double_double(Complex::default(), point, self.limit, self.bailout),
+            Some(c) => escape_time_double_double(point, Complex { re: c.re.into(), im: c.im.into() },
+                                                 self.limit, self.bailout)
+        }
+    }
+
+    /// Run `escape_time_trapped` for the pixel at `point`.
+    pub fn escape_time_trapped(&self, point : Complex<f64>, trap : Trap) -> (Option<usize>, f64) {
+
+        match self.precision {
+            Precision::Single => {
+                let (z0, c) = self.orbit::<f32>(point);
+                self.fractal.escape_time_trapped(z0, c, self.limit, float(self.bailout), trap)
+            }
+            Precision::Double | Precision::DoubleDouble => {
+                let (z0, c) = self.orbit::<f64>(point);
+                self.fractal.escape_time_trapped(z0, c, self.limit, self.bailout, trap)
+            }
+        }
+    }
+
+    /// Run `escape_time_final` for the pixel at `point`.
+    pub fn escape_time_final(&self, point : Complex<f64>) -> (Option<usize>, Complex<f64>) {
+
+        match self.precision {
+            Precision::Single => {
+                let (z0, c) = self.orbit::<f32>(point);
+                self.fractal.escape_time_final(z0, c, self.limit, float(self.bailout))
+            }
+            Precision::Double | Precision::DoubleDouble => {
+                let (z0, c) = self.orbit::<f64>(point);
+                self.fractal.escape_time_final(z0, c, self.limit, self.bailout)
+            }
+        }
+    }
+
+    /// Run `escape_time_periodic` for the pixel at `point`, looking for
+    /// cycles of up to `max_period` steps.
+    pub fn escape_time_periodic(&self, point : Complex<f64>) -> (Option<usize>, Option<usize>) {
+
+        match self.precision {
+            Precision::Single => {
+                let (z0, c) = self.orbit::<f32>(point);
+                self.fractal.escape_time_periodic(z0, c, self.limit, float(self.bailout), self.max_period)
+            }
+            Precision::Double | Precision::DoubleDouble => {
+                let (z0, c) = self.orbit::<f64>(point);
+                self.fractal.escape_time_periodic(z0, c, self.limit, self.bailout, self.max_period)
+            }
+        }
+    }
+
+    /// Return true if every point's orbit is the mirror image, across the real
+    /// axis, of its own mirror image's orbit, so the two get the same count.
+    /// The Burning Ship's fold breaks the symmetry, as do a Julia constant
+    /// or a trap point off the real axis. Fractional powers are symmetric in
+    /// exact arithmetic, but their branch cut doesn't promise bit-exact mirrors.
+    pub fn is_symmetric(&self) -> bool {
+
+        !matches!(self.fractal, Fractal::BurningShip | Fractal::FractionalMultibrot(_))
+            && self.julia.is_none_or(|c| c.im == 0.0)
+            && !matches!(self.trap, Some(Trap::Point(point)) if point.im != 0.0)
+    }
+
+    /// Return true if `escape_time_simd` computes exactly what `escape_time`
+    /// does for these settings: the plain Mandelbrot set, at radius 2, in
+    /// `f64`.
+    pub fn vectorizes(&self) -> bool {
+
+        self.fractal == Fractal::Mandelbrot && self.julia.is_none() && self.bailout == 2.0
+            && self.growth.is_none() && self.precision == Precision::Double
+    }
+
+    /// Return true if these settings ask for whole escape counts, with
+    /// `escape_time`, that `escape_time_double_double` computes: those of
+    /// the Mandelbrot set or its Julia sets, in double-double, at a radius.
+    pub fn iterates_double_double(&self) -> bool {
+
+        self.precision == Precision::DoubleDouble && self.fractal == Fractal::Mandelbrot
+            && self.growth.is_none() && !self.smooth && self.trap.is_none() && !self.distance
+            && !self.interior && self.max_period == 0
+    }
+
+    /// Run `distance_estimate` for the pixel at `point`, or its equivalent
+    /// for a Julia set, which differentiates by the starting point instead.
+    pub fn distance_estimate(&self, point : Complex<f64>) -> Option<f64> {
+
+        let zero = Complex::new(0.0, 0.0);
+        let one = Complex::new(1.0, 0.0);
+
+        match self.julia {
+            None => distance_estimate(point, self.limit),
+            Some(c) => estimate_distance(point, one, c, zero, self.limit)
+        }
+    }
+
+    /// Run `escape_time_smooth` for the pixel at `point`, escaping at
+    /// `bailout` as given. The fractions are only smooth once `|z|` is large
+    /// next to `c`, so near the default radius of 2 they come out rough and
+    /// banded; smooth renders want `SMOOTH_BAILOUT` or more.
+    pub fn escape_time_smooth(&self, point : Complex<f64>) -> Option<f64> {
+
+        match self.precision {
+            Precision::Single => {
+                let (z0, c) = self.orbit::<f32>(point);
+                self.fractal.escape_time_smooth(z0, c, self.limit, float(self.bailout))
+            }
+            Precision::Double | Precision::DoubleDouble => {
+                let (z0, c) = self.orbit::<f64>(point);
+                self.fractal.escape_time_smooth(z0, c, self.limit, self.bailout)
+            }
+        }
+    }
+
+    /// Return the starting point and constant of the orbit for the pixel at
+    /// `point`, converted to `T`.
+    fn orbit<T : Float>(&self, point : Complex<f64>) -> (Complex<T>, Complex<T>) {
+
+        let convert = |z : Complex<f64>| Complex { re: float(z.re), im: float(z.im) };
+
+        match self.julia {
+            None => (Complex::new(T::zero(), T::zero()), convert(point)),
+            Some(c) => (convert(point), convert(c))
+        }
+    }
+}
+
+#[test]
+fn test_julia_escape_time() {
+
+    let origin = Complex { re: 0.0, im: 0.0 };
+
+    // The dendrite-like Julia set for c = -0.8 + 0.156i is disconnected, so
+    // even the origin eventually escapes; points farther out leave quickly.
+    let c = Complex { re: -0.8, im: 0.156 };
+    let julia = Iteration { limit: 1000, julia: Some(c), ..Iteration::default() };
+    assert!(julia.escape_time(origin).is_some());
+    assert_eq!(julia.escape_time(Complex { re: 1.5, im: 0.0 }), Some(4));
+    assert_eq!(escape_time(Complex { re: 1.5, im: 0.0 }, c, 1000, 2.0), Some(4));
+
+    // The basilica, c = -1, traps the origin in the cycle 0, -1, 0, ...
+    let basilica = Iteration { limit: 1000, julia: Some(Complex { re: -1.0, im: 0.0 }), ..Iteration::default() };
+    assert_eq!(basilica.escape_time(origin), None);
+    assert_eq!(basilica.escape_time(Complex { re: 2.0, im: 0.0 }), Some(1));
+
+    // Without a Julia constant, the point is used as `c`.
+    let mandelbrot = Iteration { limit: 1000, ..Iteration::default() };
+    assert_eq!(mandelbrot.escape_time(Complex { re: -1.0, im: 0.0 }), None);
+    assert_eq!(mandelbrot.escape_time(c), escape_time(origin, c, 1000, 2.0));
+}
+
+#[test]
+fn test_escape_time_smooth() {
+
+    let origin = Complex { re: 0.0, im: 0.0 };
+
+    for c in [Complex { re: 0.4, im: 0.3 }, Complex { re: 0.26, im: 0.0 },
+              Complex { re: -1.5, im: 0.01 }, Complex { re: 2.0, im: 2.0 }] {
+
+        // Count whole iterations against the same raised radius.
+        let mut z = origin;
+        let mut count = 0;
+        while z.norm() <= SMOOTH_RADIUS {
+            z = z * z + c;
+            count += 1;
+        }
+
+        let smooth = escape_time_smooth(origin, c, 1000).unwrap();
+        assert!(count as f64 <= smooth && smooth < count as f64 + 1.0,
+                "{} not in [{}, {})", smooth, count, count + 1);
+    }
+
+    assert_eq!(escape_time_smooth(origin, Complex { re: -1.0, im: 0.0 }, 1000), None);
+
+    // An iteration escapes at its bailout, however near, so the fraction
+    // follows the radius it's given.
+    let c = Complex { re: 0.4, im: 0.3 };
+    let at = |bailout| Iteration { smooth: true, bailout, ..Iteration::default() }.escape_time_smooth(c);
+    assert_eq!(at(SMOOTH_BAILOUT), Fractal::Mandelbrot.escape_time_smooth(origin, c, 255, SMOOTH_BAILOUT));
+    assert_eq!(at(4.0), Fractal::Mandelbrot.escape_time_smooth(origin, c, 255, 4.0));
+    assert!(at(4.0) != at(SMOOTH_BAILOUT));
+    assert_eq!(at(SMOOTH_RADIUS), escape_time_smooth(origin, c, 255));
+    assert!(at(SMOOTH_RADIUS) != at(SMOOTH_BAILOUT));
+}
+
+#[test]
+fn test_in_cardioid_or_bulb() {
+
+    assert!(in_cardioid_or_bulb(Complex { re: 0.0, im: 0.0 }));
+    assert!(in_cardioid_or_bulb(Complex { re: -0.5, im: 0.5 }));
+    assert!(in_cardioid_or_bulb(Complex { re: -1.0, im: 0.2 }));
+    assert!(!in_cardioid_or_bulb(Complex { re: 0.3, im: 0.0 }));
+    assert!(!in_cardioid_or_bulb(Complex { re: -1.3, im: 0.0 }));
+
+    // In the set, but in a smaller bulb the test doesn't cover.
+    assert!(!in_cardioid_or_bulb(Complex { re: -0.12, im: 0.75 }));
+
+    let origin = Complex { re: 0.0, im: 0.0 };
+    assert_eq!(escape_time(origin, Complex { re: -0.1, im: 0.1 }, usize::MAX, 2.0), None);
+}
+
+#[test]
+fn test_cardioid_check_preserves_escape_times() {
+
+    let naive = |c: Complex<f64>, limit: usize| {
+        let mut z = Complex { re: 0.0, im: 0.0 };
+        (0..limit).find(|_| {
+            let escaped = z.norm_sqr() > 4.0;
+            z = z * z + c;
+            escaped
+        })
+    };
+
+    let origin = Complex { re: 0.0, im: 0.0 };
+    for y in 0..60 {
+        for x in 0..80 {
+            let c = Complex { re: -2.5 + x as f64 * 0.045, im: 1.2 - y as f64 * 0.04 };
+            assert_eq!(escape_time(origin, c, 300, 2.0), naive(c, 300), "at {}", c);
+        }
+    }
+
+    // Boundary points along the seahorse valley, where orbits linger longest
+    // before escaping and are most easily mistaken for cycles.
+    for y in 0..40 {
+        for x in 0..40 {
+            let c = Complex { re: -0.76 + x as f64 * 0.0005, im: 0.08 + y as f64 * 0.0005 };
+            assert_eq!(escape_time(origin, c, 2000, 2.0), naive(c, 2000), "at {}", c);
+        }
+    }
+}
+
+#[test]
+fn test_periodicity_detection_stops_early() {
+
+    let origin = Complex { re: 0.0, im: 0.0 };
+    let limit = 100_000;
+
+    // Centers of the period-3 and period-4 bulbs, which the cardioid and
+    // bulb test doesn't catch, and a point inside the cardioid.
+    for c in [Complex { re: -0.1226, im: 0.7449 }, Complex { re: -1.3107, im: 0.0 },
+              Complex { re: 0.1, im: 0.3 }] {
+        match follow_orbit(Fractal::Mandelbrot, origin, c, limit, 4.0) {
+            Fate::Bounded { iterations } => assert!(iterations < limit / 10,
+                                                    "{} took {} iterations", c, iterations),
+            fate => panic!("{} should be bounded, got {:?}", c, fate)
+        }
+    }
+
+    // Escaping points run exactly as long as before.
+    assert!(matches!(follow_orbit(Fractal::Mandelbrot, origin, Complex { re: -0.75, im: 0.01 }, limit, 4.0),
+                     Fate::Escaped { iterations: 315, .. }));
+}
+
+#[test]
+fn test_bailout_lengthens_escape() {
+
+    let origin = Complex { re: 0.0, im: 0.0 };
+    let c = Complex { re: -0.75, im: 0.01 };
+
+    let near = escape_time(origin, c, 1000, 2.0).unwrap();
+    let far = escape_time(origin, c, 1000, 1000.0).unwrap();
+    assert!(far > near, "{} should exceed {}", far, near);
+
+    let wide = Iteration { bailout: 1000.0, limit: 1000, ..Iteration::default() };
+    assert_eq!(wide.escape_time(c), Some(far));
+}
+
+#[test]
+fn test_fractal_steps() {
+
+    let z = Complex { re: -1.0, im: 2.0 };
+    let c = Complex { re: 0.5, im: 0.25 };
+    assert_eq!(Fractal::Mandelbrot.step(z, c), z * z + c);
+    assert_eq!(Fractal::BurningShip.step(z, c), Complex { re: -2.5, im: 4.25 });
+    assert_eq!(Fractal::Tricorn.step(z, c), Complex { re: -2.5, im: 4.25 });
+    assert_eq!(Fractal::Tricorn.step(Complex { re: 1.0, im: 2.0 }, c), Complex { re: -2.5, im: -3.75 });
+}
+
+#[test]
+fn test_fractal_membership() {
+
+    let origin = Complex { re: 0.0, im: 0.0 };
+    let member = |fractal: Fractal, re, im| fractal.escape_time(origin, Complex { re, im }, 1000, 2.0);
+
+    assert_eq!(member(Fractal::Mandelbrot, -0.5, 0.5), None);
+    assert_eq!(member(Fractal::Mandelbrot, 1.0, 1.0), Some(2));
+
+    assert_eq!(member(Fractal::BurningShip, -0.2, -0.6), None);
+    assert_eq!(member(Fractal::BurningShip, -0.5, 0.5), Some(4));
+    assert_eq!(member(Fractal::BurningShip, 1.0, 1.0), Some(2));
+
+    assert_eq!(member(Fractal::Tricorn, 0.2, 0.2), None);
+    assert_eq!(member(Fractal::Tricorn, -0.2, -0.6), Some(4));
+    assert_eq!(member(Fractal::Tricorn, 1.0, 1.0), Some(3));
+
+    assert_eq!("burningship".parse(), Ok(Fractal::BurningShip));
+    assert!("julia".parse::<Fractal>().is_err());
+}
+
+#[test]
+fn test_orbit_trap_distance() {
+
+    let origin = Complex { re: 0.0, im: 0.0 };
+    let point : Trap = "point".parse().unwrap();
+
+    // The orbit of -1 is 0, -1, 0, -1, ..., landing exactly on the origin.
+    let (count, distance) = Fractal::Mandelbrot.escape_time_trapped(origin, Complex { re: -1.0, im: 0.0 },
+                                                                    100, 2.0, point);
+    assert_eq!(count, None);
+    assert!(distance < 1e-12, "{} should be zero", distance);
+
+    // The first step from the origin lands on c, here on the imaginary axis.
+    let (_, distance) = Fractal::Mandelbrot.escape_time_trapped(origin, Complex { re: 0.0, im: 0.5 },
+                                                                100, 2.0, Trap::Cross);
+    assert!(distance < 1e-12, "{} should be zero", distance);
+
+    // Escaping points count the same as without a trap, and points that
+    // steer clear of the trap are some way from it.
+    let iteration = Iteration { limit: 1000, ..Iteration::default() };
+    for c in [Complex { re: 0.3, im: 0.6 }, Complex { re: -0.75, im: 0.01 }, Complex { re: 0.25, im: 0.0 }] {
+        assert_eq!(iteration.escape_time_trapped(c, point).0, iteration.escape_time(c));
+    }
+    // 1 + i goes to 1 + 3i and escapes; the first step is the nearest.
+    let (count, distance) = iteration.escape_time_trapped(Complex { re: 1.0, im: 1.0 }, point);
+    assert_eq!(count, Some(2));
+    assert_eq!(distance, 2.0f64.sqrt());
+
+    assert_eq!("cross".parse(), Ok(Trap::Cross));
+    assert!("circle".parse::<Trap>().is_err());
+}
+
+#[test]
+fn test_escape_time_in_either_precision() {
+
+    // Points well away from the boundary escape at the same count whatever
+    // the precision.
+    for (re, im) in [(0.3, 0.6), (-1.5, 0.2), (1.0, 1.0), (-0.1, 0.9), (-2.0, 0.1)] {
+        let single = escape_time(Complex::new(0.0f32, 0.0), Complex::new(re as f32, im as f32), 1000, 2.0);
+        let double = escape_time(Complex::new(0.0f64, 0.0), Complex::new(re, im), 1000, 2.0);
+        let c = Complex { re: re.into(), im: im.into() };
+        let double_double = escape_time_double_double(Complex::default(), c, 1000, 2.0);
+        assert!(single.is_some());
+        assert_eq!(single, double, "{},{}", re, im);
+        assert_eq!(double_double, double, "{},{}", re, im);
+
+        let single = escape_time_smooth(Complex::new(0.0f32, 0.0), Complex::new(re as f32, im as f32), 1000);
+        let double = escape_time_smooth(Complex::new(0.0f64, 0.0), Complex::new(re, im), 1000);
+        assert!((single.unwrap() - double.unwrap()).abs() < 1e-3);
+    }
+
+    assert!(in_cardioid_or_bulb(Complex::new(-0.1f32, 0.1)));
+    assert!(!in_cardioid_or_bulb(Complex::new(0.3f32, 0.6)));
+    assert_eq!(Fractal::BurningShip.step(Complex::new(-1.0f32, -2.0), Complex::new(0.5, 0.5)),
+               Complex::new(-2.5, 4.5));
+
+    let single = Iteration { limit: 1000, precision: Precision::Single, ..Iteration::default() };
+    let double = Iteration { limit: 1000, ..Iteration::default() };
+    let point = Complex { re: -0.75, im: 0.01 };
+    assert_eq!(single.escape_time(point), double.escape_time(point));
+    let julia = Iteration { julia: Some(Complex { re: -0.8, im: 0.156 }), ..single };
+    assert_eq!(julia.escape_time(Complex { re: 1.5, im: 0.0 }), Some(4));
+    let julia = Iteration { precision: Precision::DoubleDouble, ..julia };
+    assert_eq!(julia.escape_time(Complex { re: 1.5, im: 0.0 }), Some(4));
+    let in_set = Iteration { precision: Precision::DoubleDouble, ..double };
+    assert_eq!(in_set.escape_time(Complex { re: -0.1, im: 0.1 }), None);
+
+    assert_eq!("f32".parse(), Ok(Precision::Single));
+    assert_eq!("f64".parse(), Ok(Precision::Double));
+    assert_eq!("dd".parse(), Ok(Precision::DoubleDouble));
+    assert!("f16".parse::<Precision>().is_err());
+}
+
+#[test]
+fn test_distance_estimate() {
+
+    // Two steps from the origin by hand, for c = 1 + i: z goes to c, then to
+    // c^2 + c = 1 + 3i; dz goes to 1, then to 2c + 1 = 3 + 2i.
+    let c = Complex { re: 1.0, im: 1.0 };
+    let zero = Complex::new(0.0, 0.0);
+    let one = Complex::new(1.0, 0.0);
+    let (z, dz) = step_with_derivative(zero, zero, c, one);
+    assert_eq!((z, dz), (c, one));
+    let (z, dz) = step_with_derivative(z, dz, c, one);
+    assert_eq!((z, dz), (Complex::new(1.0, 3.0), Complex::new(3.0, 2.0)));
+
+    // The set's rightmost point is 1/4, so the distance from 1 is 3/4; the
+    // estimate is right to within a factor of four.
+    let estimate = distance_estimate(one, 1000).unwrap();
+    assert!(0.75 / 4.0 < estimate && estimate < 0.75 * 4.0, "{}", estimate);
+
+    // Nearer points get smaller estimates.
+    let near = distance_estimate(Complex::new(0.26, 0.0), 1000).unwrap();
+    assert!(near < estimate);
+    assert_eq!(distance_estimate(Complex::new(-0.1, 0.1), 1000), None);
+    assert_eq!(distance_estimate(Complex::new(-1.0, 0.0), 1000), None);
+
+    let julia = Iteration { julia: Some(zero), ..Iteration::default() };
+    let estimate = julia.distance_estimate(Complex::new(2.0, 0.0)).unwrap();
+    assert!(0.25 < estimate && estimate < 4.0, "{}", estimate);
+}
+
+#[test]
+fn test_escape_time_simd_matches_scalar() {
+
+    let origin = Complex::new(0.0, 0.0);
+
+    // A grid over the whole set, including the cardioid, the bulb and the
+    // slow-escaping points between them.
+    let points : Vec<Complex<f64>> = (0..40)
+        .flat_map(|y| (0..60).map(move |x| Complex::new(-2.2 + x as f64 * 0.05, -1.2 + y as f64 * 0.06)))
+        .chain([Complex::new(-0.75, 0.01), Complex::new(0.26, 0.0), Complex::new(-1.25, 0.0)])
+        .collect();
+
+    for chunk in points.chunks_exact(4) {
+        let lanes = [chunk[0], chunk[1], chunk[2], chunk[3]];
+        let scalar = lanes.map(|c| escape_time(origin, c, 500, 2.0));
+        assert_eq!(escape_time_simd(lanes, 500), scalar, "{:?}", lanes);
+    }
+
+    assert!(Iteration::default().vectorizes());
+    assert!(!Iteration { bailout: 16.0, ..Iteration::default() }.vectorizes());
+    assert!(!Iteration { julia: Some(origin), ..Iteration::default() }.vectorizes());
+}
+
+#[test]
+fn test_orbit() {
+
+    let c = Complex { re: 1.0, im: 0.0 };
+    let points : Vec<_> = orbit(c, 255).collect();
+    assert_eq!(points, [Complex::new(0.0, 0.0), Complex::new(1.0, 0.0), Complex::new(2.0, 0.0)]);
+    assert_eq!(escape_time(Complex::new(0.0, 0.0), c, 255, 2.0), Some(points.len()));
+
+    let c = Complex { re: 0.0, im: 1.0 };
+    let points : Vec<_> = orbit(c, 5).collect();
+    assert_eq!(points, [Complex::new(0.0, 0.0), Complex::new(0.0, 1.0), Complex::new(-1.0, 1.0),
+                        Complex::new(0.0, -1.0), Complex::new(-1.0, 1.0)]);
+
+    // Escaping points' orbits are as long as their counts; members' run to
+    // the limit.
+    for (re, im) in [(-0.75, 0.1), (0.3, 0.5), (-2.0, 0.0), (-0.1, 0.9), (0.25, 0.0), (-1.4, 0.02)] {
+        let c = Complex { re, im };
+        let length = orbit(c, 1000).count();
+        let expected = escape_time(Complex::new(0.0, 0.0), c, 1000, 2.0).unwrap_or(1000);
+        assert_eq!(length, expected, "orbit of {} has the wrong length", c);
+    }
+}
+
+#[test]
+fn test_multibrot() {
+
+    let origin = Complex { re: 0.0, im: 0.0 };
+
+    // Raising to the power 2 steps exactly as squaring does.
+    for c in [Complex { re: -0.75, im: 0.1 }, Complex { re: 0.3, im: -0.5 }, Complex { re: -1.9, im: 0.2 }] {
+        let (mut z, mut w) = (origin, origin);
+        for _ in 0..100 {
+            if z.norm_sqr() > 4.0 {
+                break;
+            }
+            z = Fractal::Mandelbrot.step(z, c);
+            w = Fractal::Multibrot(2).step(w, c);
+            assert_eq!(z, w);
+        }
+        assert_eq!(Fractal::Multibrot(2).escape_time(origin, c, 1000, 2.0),
+                   Fractal::Mandelbrot.escape_time(origin, c, 1000, 2.0));
+    }
+
+    // This point escapes the Mandelbrot set, but is inside the cubic
+    // Multibrot set, which is fatter along the imaginary axis.
+    let c = Complex { re: 0.4, im: 0.4 };
+    assert_eq!(escape_time(origin, c, 1000, 2.0), Some(9));
+    assert_eq!(Fractal::Multibrot(3).escape_time(origin, c, 1000, 2.0), None);
+    assert_eq!(Fractal::Multibrot(3).escape_time(origin, Complex { re: 0.6, im: 0.0 }, 1000, 2.0), Some(4));
+    assert_eq!(Fractal::Multibrot(3).step(Complex { re: 0.0, im: 1.0 }, c), Complex { re: 0.4, im: -0.6 });
+
+    // The smooth count lies within a step of the whole count at the same radius.
+    let c = Complex { re: 0.9, im: 0.1 };
+    let whole = Fractal::Multibrot(4).escape_time(origin, c, 1000, SMOOTH_RADIUS).unwrap() as f64;
+    let smooth = Fractal::Multibrot(4).escape_time_smooth(origin, c, 1000, SMOOTH_RADIUS).unwrap();
+    assert!((whole..whole + 1.0).contains(&smooth), "{} is far from {}", smooth, whole);
+}
+
+#[test]
+fn test_fractional_multibrot() {
+
+    let origin = Complex { re: 0.0, im: 0.0 };
+    let grid : Vec<Complex<f64>> = (0..60).flat_map(|y| (0..80).map(move |x| {
+        Complex { re: -2.0 + x as f64 * 0.03, im: 1.2 - y as f64 * 0.04 }
+    })).collect();
+
+    // A power of 2.0 takes the general path, but rounding aside it's squaring,
+    // so nearly every point gets the same count as it does in the Mandelbrot set.
+    let same = grid.iter().filter(|&&c| {
+        Fractal::FractionalMultibrot(2.0).escape_time(origin, c, 200, 2.0)
+            == Fractal::Mandelbrot.escape_time(origin, c, 200, 2.0)
+    }).count();
+    assert!(same * 100 >= grid.len() * 98, "only {} of {} counts agree", same, grid.len());
+
+    // Fractional and negative powers give finite smooth counts, the same ones
+    // every time, with some points escaping and some not.
+    for power in [2.5, -2.0] {
+        let fractal = Fractal::FractionalMultibrot(power);
+        let render = || grid.iter().map(|&c| fractal.escape_time_smooth(origin, c, 200, SMOOTH_RADIUS)).collect::<Vec<_>>();
+        let counts = render();
+        assert!(counts.iter().flatten().all(|count| count.is_finite()));
+        assert!(counts.iter().any(|count| count.is_none()) && counts.iter().any(|count| count.is_some()));
+        assert_eq!(counts, render());
+    }
+
+    // Zero to a negative power counts as zero, rather than infinity.
+    let c = Complex { re: 0.5, im: 0.25 };
+    assert_eq!(Fractal::FractionalMultibrot(-2.0).step(origin, c), c);
+    assert_eq!(Fractal::FractionalMultibrot(-2.0).power(), -2.0);
+}
+
+#[test]
+fn test_escape_time_periodic() {
+
+    use crate::{Iteration, DEFAULT_MAX_PERIOD};
+
+    let iteration = Iteration { max_period: DEFAULT_MAX_PERIOD, ..Iteration::default() };
+    let period = |re, im| iteration.escape_time_periodic(Complex { re, im });
+
+    // The main cardioid settles on a fixed point, and the bulb to its left
+    // on a cycle between two; the bulb at the top of the cardioid has three.
+    assert_eq!(period(0.0, 0.0), (None, Some(1)));
+    assert_eq!(period(-0.1, 0.1), (None, Some(1)));
+    assert_eq!(period(-1.0, 0.0), (None, Some(2)));
+    assert_eq!(period(-1.1, 0.05), (None, Some(2)));
+    assert_eq!(period(-0.12, 0.74), (None, Some(3)));
+    assert_eq!(Iteration { max_period: 2, ..iteration }.escape_time_periodic(Complex { re: -0.12, im: 0.74 }),
+               (None, None));
+    assert_eq!(period(1.0, 0.0), (iteration.escape_time(Complex { re: 1.0, im: 0.0 }), None));
+    assert!(period(1.0, 0.0).0.is_some());
+
+    assert_eq!(Fractal::Mandelbrot.find_period(Complex { re: 0.0, im: 0.0 }, Complex { re: -1.0, im: 0.0 },
+                                               1, PERIOD_EPSILON),
+               None);
+}
+
+#[test]
+fn test_escape_time_growth() {
+
+    use crate::Iteration;
+
+    let radius = Iteration::default();
+    let growth = Iteration { growth: Some(1.5), ..radius };
+    let counts = |re, im| {
+        let point = Complex { re, im };
+        (radius.escape_time(point), growth.escape_time(point))
+    };
+
+    // From 1, |z| goes 1, 2, 5: the second step doubles it while still
+    // inside the circle, so growth notices a step sooner.
+    assert_eq!(counts(1.0, 0.0), (Some(3), Some(2)));
+    // From -2.1, |z| goes 2.1, 2.31, 3.24, 8.1: the orbit starts outside the
+    // circle, but takes three steps to grow by half in one.
+    assert_eq!(counts(-2.1, 0.0), (Some(1), Some(4)));
+    // An orbit creeping up to its fixed point never grows that fast.
+    assert_eq!(counts(0.2, 0.0), (None, None));
+    assert_eq!(Iteration { precision: Precision::Single, ..growth }.escape_time(Complex { re: 1.0, im: 0.0 }),
+               Some(2));
+
+    // An orbit that overflows counts as escaping, rather than comparing NaNs.
+    assert_eq!(Fractal::Mandelbrot.escape_time_growth(Complex::new(0.0, 0.0), Complex::new(1e100, 0.0),
+                                                      10, 1e300),
+               Some(2));
+    assert!(!growth.vectorizes());
+}
@@ -0,0 +1,44 @@
+//! Render the Mandelbrot set, and its Julia-set relatives, to images.
+//!
+//! The pieces can be used separately: `escape_time` decides whether a single
+//! point belongs to the set, `pixel_to_point` maps image pixels onto the
+//! complex plane, `render` computes the escape counts of a whole image in
+//! parallel, `map_colors` turns those counts into pixels, and `write_image`
+//! saves the result as a PNG. `RenderBuilder` gathers the usual settings
+//! and does the rendering and coloring in one call.
+
+mod ascii;
+mod color;
+mod config;
+mod double_double;
+mod error;
+mod escape;
+mod metadata;
+mod output;
+mod parse;
+mod progress;
+mod random;
+mod render;
+mod resample;
+mod stats;
+mod view;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use ascii::*;
+pub use color::*;
+pub use config::*;
+pub use double_double::*;
+pub use error::*;
+pub use escape::*;
+pub use metadata::*;
+pub use output::*;
+pub use parse::*;
+pub use progress::*;
+pub use random::*;
+pub use render::*;
+pub use resample::*;
+pub use stats::*;
+pub use view::*;
+#[cfg(feature = "wasm")]
+pub use wasm::*;
@@ -0,0 +1,169 @@
+//! Leveled log messages on stderr, for reporting what a render is doing
+//! without mixing it into the images or the text it prints.
+//!
+//! The `error!`, `warn!`, `info!` and `debug!` macros each format a message
+//! and pass it to `log` at their level, which writes it only if
+//! `set_max_level` lets that level through. Nothing is ever written to
+//! stdout, so an image sent there is the same at every level.
+
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How much a log message matters, from the most to the least.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Something failed, and the work stopped.
+    Error = 1,
+    /// Something was wrong, but the work went on around it.
+    Warn,
+    /// How the work is going: the stages of a render and how long each took.
+    Info,
+    /// Finer detail, like each tile of a render.
+    Debug,
+    /// Everything.
+    Trace
+}
+
+impl Level {
+
+    /// Every level, from the one that matters most to the one that matters
+    /// least.
+    pub const ALL : [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+
+    /// The level written before each message at it, as in `warn: ...`.
+    fn name(self) -> &'static str {
+
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace"
+        }
+    }
+}
+
+impl FromStr for Level {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Level, String> {
+
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(format!("unknown log level '{}'", s))
+        }
+    }
+}
+
+/// The level messages are written at unless `set_max_level` says otherwise.
+pub const DEFAULT_LOG_LEVEL : Level = Level::Warn;
+
+/// The most detailed level written, as a `Level`'s number, or 0 for none.
+static MAX_LEVEL : AtomicUsize = AtomicUsize::new(DEFAULT_LOG_LEVEL as usize);
+
+/// Where messages go instead of stderr, if anywhere.
+static WRITER : Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+
+/// Write messages at `level` and every level that matters more, or none at
+/// all for `None`.
+pub fn set_max_level(level : Option<Level>) {
+
+    MAX_LEVEL.store(level.map_or(0, |level| level as usize), Ordering::Relaxed);
+}
+
+/// Return true if messages at `level` are written.
+pub fn log_enabled(level : Level) -> bool {
+
+    level as usize <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Send messages to `writer` instead of stderr, or back to stderr for
+/// `None`, returning wherever they went before.
+#[cfg(test)]
+pub fn set_log_writer(writer : Option<Box<dyn Write + Send>>) -> Option<Box<dyn Write + Send>> {
+
+    std::mem::replace(&mut *WRITER.lock().unwrap_or_else(|poisoned| poisoned.into_inner()), writer)
+}
+
+/// Write the message `args` at `level`, on a line of its own after the
+/// level's name, if that level is written. The `error!` to `debug!` macros
+/// call this. A message that can't be written is dropped.
+pub fn log(level : Level, args : fmt::Arguments) {
+
+    if !log_enabled(level) {
+        return;
+    }
+
+    let mut writer = WRITER.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let _ = match writer.as_mut() {
+        Some(writer) => writeln!(writer, "{}: {}", level.name(), args),
+        None => writeln!(std::io::stderr(), "{}: {}", level.name(), args)
+    };
+}
+
+/// Return the level that `spec`, written as for the `RUST_LOG` environment
+/// variable, sets for this crate: a comma-separated list of levels or `off`,
+/// each either alone or after `mandelbrot=`. The last one naming the crate
+/// wins, or failing that the last one alone; other crates' are ignored.
+/// Return `Some(None)` to write nothing, or `None` if `spec` sets no level.
+pub fn level_from_spec(spec : &str) -> Result<Option<Option<Level>>, String> {
+
+    let parse = |text : &str| match text.trim() {
+        off if off.eq_ignore_ascii_case("off") => Ok(None),
+        level => level.parse().map(Some)
+    };
+
+    let (mut general, mut ours) = (None, None);
+    for directive in spec.split(',').map(str::trim).filter(|directive| !directive.is_empty()) {
+        match directive.split_once('=') {
+            None => general = Some(parse(directive)?),
+            Some((module, level)) if module.trim() == "mandelbrot" => ours = Some(parse(level)?),
+            Some(_) => {}
+        }
+    }
+
+    Ok(ours.or(general))
+}
+
+/// Log a message at `Level::Error`, formatted as by `format!`.
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Error, format_args!($($arg)*)) }
+}
+
+/// Log a message at `Level::Warn`, formatted as by `format!`.
+macro_rules! warn {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Warn, format_args!($($arg)*)) }
+}
+
+/// Log a message at `Level::Info`, formatted as by `format!`.
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Info, format_args!($($arg)*)) }
+}
+
+/// Log a message at `Level::Debug`, formatted as by `format!`.
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Debug, format_args!($($arg)*)) }
+}
+
+#[test]
+fn test_level_from_spec() {
+
+    assert_eq!(level_from_spec("debug"), Ok(Some(Some(Level::Debug))));
+    assert_eq!(level_from_spec("INFO"), Ok(Some(Some(Level::Info))));
+    assert_eq!(level_from_spec("off"), Ok(Some(None)));
+    assert_eq!(level_from_spec(""), Ok(None));
+    assert_eq!(level_from_spec("rayon=trace"), Ok(None));
+    assert_eq!(level_from_spec("trace,mandelbrot=warn,info"), Ok(Some(Some(Level::Warn))));
+    assert_eq!(level_from_spec("error, rayon=debug"), Ok(Some(Some(Level::Error))));
+    assert!(level_from_spec("loud").is_err());
+    assert!(level_from_spec("mandelbrot=loud").is_err());
+    assert!(Level::Error < Level::Warn && Level::Debug < Level::Trace);
+}
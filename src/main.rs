@@ -1,210 +1,2344 @@
-use image::png::PNGEncoder;
-use image::ColorType;
+#[macro_use]
+mod logging;
+
+use mandelbrot::{apply_gamma, ascii_preview, auto_limit, average, big_endian_bytes, check_corners,
+                 check_nondegenerate, check_pixel_count, compare_pngs, crop_view, downsample_with,
+                 estimate_area, f32_resolves, fill_masked_out, find_boundary_point, find_preset,
+                 gamma_table, invert, is_complete_png, load_config, load_gradient, map_colors,
+                 map_gray16, map_rgba_transparent, mirror_pixels, numbered_filename, palette_strip,
+                 parse_color, parse_dimensions, parse_pair, parse_point, pixel_step, pyramid_level,
+                 read_mask, rect_from_center, rect_from_center_width, reference_orbit,
+                 render_antialiased_edges, render_jittered, render_mariani_silver, render_masked,
+                 render_scanline, render_tile, render_with_checkpoints, render_with_progress,
+                 stitch_pngs, thread_pool, tile_grid, upscale_bilinear, verify_png, write_image,
+                 write_image_retrying, write_metadata, write_orbit, write_profile, write_pyramid,
+                 zoom_frame, BitDepth, ColorScheme, Coloring, Config, Counts, Crop, Downscale, Escape,
+                 Fractal, Gradient, ImageFormat, Interior, Iteration, MandelError, Mirror, Palette,
+                 Precision, Preset, Progress, Raster, RenderMeta, RenderStats, Rng, Trap,
+                 DEFAULT_GROWTH_FACTOR, DEFAULT_MAX_PERIOD, DEFAULT_MAX_PIXELS, PYRAMID_TILE_SIZE,
+                 SMOOTH_BAILOUT};
+use clap::builder::ValueParser;
+use clap::error::ErrorKind;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use logging::{level_from_spec, set_max_level, Level, DEFAULT_LOG_LEVEL};
 use num::Complex;
-use std::str::FromStr;
-use std::fs::File;
+use rayon::ThreadPool;
 use std::env;
+use std::error::Error;
+use std::path::Path;
+use std::time::{Duration, Instant};
+#[cfg(feature = "exr")]
+use mandelbrot::write_exr;
 
 fn main() {
 
     let args : Vec<String> = env::args().collect();
+    init_logging(&args);
+
+    if let Err(err) = run(&args) {
 
-    if args.len() != 5 {
-        eprintln!("Usage: {} FILE PIXELS UPPERLEFT LOWERRIGHT", args[0]);
-        eprintln!("Example: {} mandelbrot.png 1024x768 -1.20,0.35 -1,0.2", args[0]);
+        error!("{}", err);
+        if let MandelError::WrongArgCount { .. } | MandelError::BadFlag(_) = err {
+            usage(&args[0]);
+        }
         std::process::exit(1);
     }
+}
 
-    let bounds = parse_pair::<usize>(&args[2], 'x').expect("error parsing image dimensions");
-    let upper_left = parse_complex(&args[3]).expect("error parsing upper-left corner point");
-    let lower_right = parse_complex(&args[4]).expect("error parsing lower-right corner point");
-    
-    let mut pixels = vec![0; bounds.0 * bounds.1];
+/// Set how much to log, as `log_level` works it out from the `RUST_LOG`
+/// environment variable and the `-v` flags in `args`, but at least
+/// `Level::Info` with `--stats`, whose report is logged there. A variable
+/// that's set but doesn't parse is warned of, and left out.
+fn init_logging(args: &[String]) {
 
-    //render(&mut pixels, bounds, upper_left, lower_right);
-    threaded_version(&mut pixels, bounds, upper_left, lower_right);
+    // The level has to be set before anything is logged, so the flags are
+    // counted ahead of parsing the rest.
+    let program = args.first().map_or("mandelbrot", String::as_str);
+    // A value that doesn't parse stops clap short, before it fills in the
+    // defaults, so neither the count nor the switch need be there.
+    let matches = command(program).ignore_errors(true).try_get_matches_from(args).ok();
+    let (verbosity, stats) = matches.map_or((0, false), |matches| {
+        (matches.get_one::<u8>("verbose").copied().unwrap_or(0),
+         matches.get_one::<bool>("stats").copied().unwrap_or(false))
+    });
+    let least = stats.then_some(Level::Info);
 
-    write_image(&args[1], &pixels, bounds).expect("error writing PNG file");
+    match log_level(env::var("RUST_LOG").ok().as_deref(), verbosity) {
+        Ok(level) => set_max_level(level.max(least)),
+        Err(message) => {
+            set_max_level(log_level(None, verbosity).unwrap_or(Some(DEFAULT_LOG_LEVEL)).max(least));
+            warn!("ignoring RUST_LOG: {}", message);
+        }
+    }
 }
 
-fn threaded_version(pixels: &mut Vec<u8>, bounds : (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>) {
+/// Warn that writing an image failed with `err`, and will be tried again
+/// after `wait`; `write_image_retrying` calls this before each retry.
+fn warn_of_retry(err: &MandelError, wait: Duration) {
 
-    let threads = 8;
-    let rows_per_band = bounds.1 / threads + 1;
+    warn!("{}; retrying in {:?}", err, wait);
+}
 
-    {
-        let bands: Vec<&mut [u8]> = pixels.chunks_mut(rows_per_band * bounds.0).collect();
+/// Return the level to log at, with `spec` the value of `RUST_LOG`, if it's
+/// set, and `verbosity` the number of `-v` flags, each asking for a level
+/// of detail more than `DEFAULT_LOG_LEVEL`. Whichever asks for more wins.
+fn log_level(spec: Option<&str>, verbosity: u8) -> Result<Option<Level>, String> {
 
-        crossbeam::scope(|spawner| {
+    let from_spec = spec.map(level_from_spec).transpose()?.flatten().unwrap_or(Some(DEFAULT_LOG_LEVEL));
+    if verbosity == 0 {
+        return Ok(from_spec);
+    }
+    let default = Level::ALL.iter().position(|&level| level == DEFAULT_LOG_LEVEL).unwrap_or(0);
+    let steps = default + verbosity as usize;
+    let from_flags = Level::ALL[steps.min(Level::ALL.len() - 1)];
 
-            for (i, band) in bands.into_iter().enumerate() {
+    Ok(from_spec.max(Some(from_flags)))
+}
 
-                let top = rows_per_band * i;
-                let height:usize = band.len() / bounds.0;
-                let band_bounds = (bounds.0, height);
+fn run(args: &[String]) -> Result<(), MandelError> {
 
-                let band_upper_left = pixel_to_point(bounds, (0, top), upper_left, lower_right);
+    let started = Instant::now();
 
-                let band_lower_right = pixel_to_point(bounds, (bounds.0, top + height), upper_left, lower_right);
+    // Stitching and comparing work on images that have already been
+    // rendered, so they take none of the rendering flags.
+    match args.get(1).map(String::as_str) {
+        Some("stitch") => return stitch(&args[2..]),
+        Some("compare") => return compare(&args[2..]),
+        Some("verify") => return verify(&args[2..]),
+        _ => {}
+    }
 
-                spawner.spawn(move |_| {
-                    render(band, band_bounds, band_upper_left, band_lower_right);
-                });
+    // A scene file supplies defaults for the flags and arguments that follow,
+    // so read it first.
+    let (config, defaults) = match config_path(args)? {
+        Some(path) => {
+            let config = load_config(path)?;
+            let defaults = config_flags(&config, path)?;
+            (config, defaults)
+        }
+        None => (Config::default(), Flags::default())
+    };
+    let (args, mut flags) = parse_flags(args, defaults)?;
 
-            }
+    // Estimating the area renders nothing, so it needs no file or view.
+    if let Some(samples) = flags.area {
+        if args.len() > 1 {
+            return Err(MandelError::BadFlag("--area takes no file, pixels or corners".to_string()));
+        }
+        let estimate = estimate_area(samples, flags.limit, &mut Rng::new(flags.seed));
+        println!("area: {:.4} ± {:.4} ({} samples, limit {})",
+                 estimate.area, estimate.standard_error, samples, flags.limit);
+        return Ok(());
+    }
+
+    // A palette strip has nothing to do with the view either.
+    if let Some(palette) = flags.show_palette {
+        if args.len() != 2 {
+            return Err(MandelError::BadFlag("--show-palette takes a file and nothing else".to_string()));
+        }
+        let pixels = palette_strip(PALETTE_STRIP_BOUNDS, flags.limit, palette);
+        return write_image(&args[1], &pixels, PALETTE_STRIP_BOUNDS, BitDepth::Eight);
+    }
+
+    // The corners come either from the arguments, from --preset, or from
+    // --center, plus either --zoom or --scale.
+    let centered = flags.center.is_some() || flags.zoom.is_some() || flags.scale.is_some();
+    let expected = if centered || flags.preset.is_some() { 2 } else { 4 };
+    let args = fill_positionals(args, &config, |name| env::var(name).ok(), expected)?;
+
+    // Catch an unwritable extension before spending time on the render.
+    let format = ImageFormat::from_filename(&args[1])?;
+    if flags.transparent_interior && format != ImageFormat::Png {
+        return Err(MandelError::BadFlag("--transparent-interior needs a PNG to write the alpha channel to"
+                                        .to_string()));
+    }
+    if flags.metadata && args[1] == "-" {
+        return Err(MandelError::BadFlag("--metadata needs an image file to write beside".to_string()));
+    }
+    if flags.checkpoint.is_some() && args[1] == "-" {
+        return Err(MandelError::BadFlag("--checkpoint needs an image file to write to".to_string()));
+    }
+    if flags.resume && (flags.frames.is_none() || format != ImageFormat::Png || args[1] == "-") {
+        return Err(MandelError::BadFlag("--resume only works with --frames written as PNGs".to_string()));
+    }
+    if format == ImageFormat::Exr
+        && (flags.supersample > 1 || flags.samples > 1 || flags.antialias_edges || flags.checkpoint.is_some()
+            || flags.bit_depth == BitDepth::Sixteen || flags.stats || flags.metadata
+            || flags.preview_scale > 1 || flags.mirror != Mirror::None || flags.pyramid.is_some()) {
+        return Err(MandelError::BadFlag("EXR output can't be used with --supersample, --samples, \
+                                         --antialias-edges-only, --checkpoint, --output-bit-depth, \
+                                         --stats, --metadata, --preview-scale, --mirror or --pyramid"
+                                        .to_string()));
+    }
+
+    let (bounds, upper_left, lower_right) = resolve_view(&args, &flags)?;
+    // Auto-centering keeps the view's width, but moves it onto the boundary.
+    let (upper_left, lower_right) = if flags.auto_center {
+        let iteration = Iteration { limit: flags.limit, julia: flags.julia, bailout: flags.bailout,
+                                    fractal: flags.fractal, precision: flags.precision,
+                                    ..Iteration::default() };
+        let center = find_boundary_point(upper_left, lower_right, iteration).ok_or_else(|| {
+            MandelError::BadFlag("--auto-center found no boundary of the set in the view".to_string())
+        })?;
+        rect_from_center_width(center, lower_right.re - upper_left.re, bounds)
+    } else {
+        (upper_left, lower_right)
+    };
+    // A crop renders its pixels of the view as an image of their own.
+    let (bounds, upper_left, lower_right) = match flags.crop {
+        None => (bounds, upper_left, lower_right),
+        Some(crop) => {
+            let (upper_left, lower_right) = crop_view(bounds, crop, upper_left, lower_right).ok_or_else(|| {
+                MandelError::BadFlag(format!("--crop {}x{} from ({}, {}) extends past the {}x{} image",
+                                             crop.size.0, crop.size.1, crop.origin.0, crop.origin.1,
+                                             bounds.0, bounds.1))
+            })?;
+            (crop.size, upper_left, lower_right)
+        }
+    };
+    check_nondegenerate(upper_left, lower_right)?;
+    check_pixel_count(bounds, flags.supersample, flags.max_pixels)?;
+
+    check_conflicts(&flags)?;
 
-        }).unwrap();
+    match check_corners(upper_left, lower_right) {
+        Err(err) if !flags.allow_flipped => return Err(err),
+        Err(_) => warn!("the corners are flipped, so the image will come out mirrored or upside down"),
+        Ok(()) => {}
     }
+    if flags.auto_iterations {
+        flags.limit = auto_limit(lower_right.re - upper_left.re);
+    }
+
+    if flags.dry_run {
+        print!("{}", dry_run_report(bounds, upper_left, lower_right, &flags));
+        return Ok(());
+    }
+    if let Some(path) = &flags.dump_orbit {
+        write_orbit(path, &reference_orbit(upper_left, lower_right, flags.limit))?;
+    }
+    if flags.ascii {
+        let iteration = Iteration { limit: flags.limit, julia: flags.julia, bailout: flags.bailout,
+                                    growth: growth(&flags), fractal: flags.fractal,
+                                    precision: flags.precision, ..Iteration::default() };
+        print!("{}", ascii_preview(ASCII_BOUNDS, upper_left, lower_right, iteration));
+        return Ok(());
+    }
+    if let Some(y) = flags.scanline {
+        if y >= bounds.1 {
+            let message = format!("--scanline {} is outside the image's {} rows", y, bounds.1);
+            return Err(MandelError::BadFlag(message));
+        }
+        let iteration = Iteration { limit: flags.limit, julia: flags.julia, bailout: flags.bailout,
+                                    growth: growth(&flags), fractal: flags.fractal,
+                                    precision: flags.precision, ..Iteration::default() };
+        println!("{}", scanline_text(&render_scanline(bounds, y, upper_left, lower_right, iteration)));
+        return Ok(());
+    }
+
+    info!("parsed arguments in {:?}", started.elapsed());
+    let pool = thread_pool(flags.threads)?;
+
+    if let Some(dir) = &flags.pyramid {
+        return render_pyramid(&args[1], dir, bounds, upper_left, lower_right, &flags, &pool);
+    }
+
+    let (frames, end) = match (flags.frames, flags.target, flags.target_scale) {
+        (None, None, None) => {
+            return render_to_file(&args[1], bounds, upper_left, lower_right, &flags, &pool);
+        }
+        (Some(frames), Some(target), Some(target_scale)) => {
+            (frames, rect_from_center(target, target_scale, bounds))
+        }
+        _ => return Err(MandelError::BadFlag(
+            "--frames, --target and --target-scale must be used together".to_string()))
+    };
+
+    for frame in 0..frames {
+        let (upper_left, lower_right) = zoom_frame((upper_left, lower_right), end, frame, frames);
+        if flags.auto_iterations {
+            flags.limit = auto_limit(lower_right.re - upper_left.re);
+        }
+        let filename = numbered_filename(&args[1], frame + 1);
+        if flags.resume && is_complete_png(&filename, bounds) {
+            info!("{}: already rendered; skipping", filename);
+            continue;
+        }
+        render_to_file(&filename, bounds, upper_left, lower_right, &flags, &pool)?;
+    }
+
+    Ok(())
 }
 
-/// Parse the string `s` as a coordinate pair, like `"400x600"` or `"1.0,0.5"`.
-///
-/// Specifically, `s` should have the form <left><sep><right>, where <sep> is
-/// the character given by the `separator` argument, and <left> and <right> are
-/// both strings that can be parsed by `T::from_str`. `separator` must be an
-/// ASCII character.
+/// The size of an image in pixels, and its upper-left and lower-right corners.
+type View = ((usize, usize), Complex<f64>, Complex<f64>);
+
+/// Return the view that the positional `args`, which must already be the
+/// right number, and `flags` call for.
+fn resolve_view(args: &[String], flags: &Flags) -> Result<View, MandelError> {
+
+    let bounds = parse_dimensions(&args[2])?;
+    let (upper_left, lower_right) = match (flags.center, flags.zoom, flags.scale) {
+        (None, None, None) => match flags.preset {
+            Some(preset) => (preset.upper_left, preset.lower_right),
+            None => (parse_point(&args[3], "upper-left corner")?,
+                     parse_point(&args[4], "lower-right corner")?)
+        },
+        _ if flags.preset.is_some() =>
+            return Err(MandelError::BadFlag("--preset can't be used with --center".to_string())),
+        (Some(center), Some(width), None) => rect_from_center_width(center, width, bounds),
+        (Some(center), None, Some(scale)) => rect_from_center(center, scale, bounds),
+        (_, Some(_), Some(_)) =>
+            return Err(MandelError::BadFlag("--zoom and --scale can't be used together".to_string())),
+        _ => return Err(MandelError::BadFlag("--center needs one of --zoom or --scale".to_string()))
+    };
+
+    Ok((bounds, upper_left, lower_right))
+}
+
+/// Return the value of the `--config` flag in `args`, if there is one.
+fn config_path(args: &[String]) -> Result<Option<&str>, MandelError> {
+
+    match args.iter().position(|arg| arg == "--config") {
+        None => Ok(None),
+        Some(i) => args.get(i + 1)
+            .map(|path| Some(path.as_str()))
+            .ok_or_else(|| MandelError::BadFlag("--config requires a value".to_string()))
+    }
+}
+
+/// Return the flags `config`, read from `path`, sets, with the usual defaults
+/// for the rest, for the command-line flags to override.
+fn config_flags(config: &Config, path: &str) -> Result<Flags, MandelError> {
+
+    let mut flags = Flags::default();
+    let bad = |message: String| MandelError::BadConfig { path: path.to_string(), message };
+
+    if let Some(limit) = config.limit {
+        if limit == 0 {
+            return Err(bad("limit must be a positive integer".to_string()));
+        }
+        flags.limit = limit;
+    }
+    if let Some(palette) = &config.palette {
+        flags.palette = palette.parse().map_err(bad)?;
+    }
+    if let Some(fractal) = &config.fractal {
+        flags.fractal = fractal.parse().map_err(bad)?;
+    }
+
+    Ok(flags)
+}
+
+/// The environment variables that stand in for the pixels and corners, in
+/// the order of the positional arguments after the file.
+const POSITIONAL_VARS: [&str; 3] = ["MANDEL_BOUNDS", "MANDEL_UL", "MANDEL_LR"];
+
+/// Complete the positional arguments `args` from `config`: any of the file,
+/// pixels and corners missing from the end of the command line are taken
+/// from the scene instead, or failing that, for all but the file, from the
+/// environment variables in `POSITIONAL_VARS`, as looked up by `var`. Return
+/// an error unless that makes `expected` arguments, following the program
+/// name.
 ///
-/// If `s` has the proper form, return `Some<(x, y)>`. If it doesn't parse
-/// correctly, return `None`.
-fn parse_pair<T: FromStr>(s: &str, separator:char) -> Option<(T, T)> {
+/// The values are parsed later, along with the command line's own, so they
+/// are written just as the arguments would be.
+fn fill_positionals<F>(mut args: Vec<String>, config: &Config, var: F, expected: usize)
+    -> Result<Vec<String>, MandelError>
+    where F: Fn(&str) -> Option<String>
+{
+    let given = args.len().saturating_sub(1);
+    let from_config = [&config.filename, &config.pixels, &config.upper_left, &config.lower_right];
+
+    for (i, value) in from_config.iter().enumerate().take(expected).skip(given) {
+        let from_env = || i.checked_sub(1).and_then(|var_index| var(POSITIONAL_VARS[var_index]));
+        match (*value).clone().or_else(from_env) {
+            Some(value) => args.push(value),
+            None => break
+        }
+    }
+
+    if args.len() != expected + 1 {
+        return Err(MandelError::WrongArgCount { expected, got: given });
+    }
+
+    Ok(args)
+}
+
+/// Describe the render `run` would do of the rectangle from `upper_left` to
+/// `lower_right` as an image of `bounds` pixels, as `flags` describe, for
+/// `--dry-run`: the corners, the step between pixels, and about how much
+/// memory the escape counts and the finished image need.
+fn dry_run_report(bounds: (usize, usize),
+                  upper_left: Complex<f64>,
+                  lower_right: Complex<f64>,
+                  flags: &Flags) -> String {
+
+    // Supersampling renders, and colors, a larger image before shrinking it.
+    let pixels = bounds.0 * bounds.1;
+    let render_pixels = pixels * flags.supersample * flags.supersample;
+    let count_size = if flags.trap.is_some() { size_of::<f64>() } else { size_of::<Option<f64>>() };
+    let channels = if flags.bit_depth == BitDepth::Sixteen { 1 } else { 3 };
+    let image = pixels * channels * flags.bit_depth.bytes();
+    let step = pixel_step(bounds, upper_left, lower_right);
+
+    format!("upper left:  {}\nlower right: {}\npixel step:  {} across, {} down\n\
+             memory:      {} bytes of counts, {} bytes of image\n",
+            upper_left, lower_right, step.re, step.im, render_pixels * count_size, image)
+}
+
+/// With `--border-trace`, the size below which rectangles have every pixel
+/// evaluated rather than being split further.
+const TRACE_MIN_SIZE: usize = 8;
+
+/// With `--antialias-edges-only`, how far apart neighbouring pixels' escape
+/// counts must be for them to be supersampled.
+const EDGE_THRESHOLD: f64 = 1.0;
 
-    match s.find(separator) {
+/// The size of the strip `--show-palette` draws, in pixels.
+const PALETTE_STRIP_BOUNDS: (usize, usize) = (512, 64);
 
-        None => None,
-        Some(index) => {
+/// The size of the preview `--ascii` prints, in characters: about a 4:3
+/// view, since each character is about twice as tall as it is wide.
+const ASCII_BOUNDS: (usize, usize) = (80, 40);
 
-            match (T::from_str(&s[..index]), T::from_str(&s[index+1..])) {
-                (Ok(l), Ok(r)) => Some((l, r)),
-                _ => None
+/// The iteration limit `--area` counts points in the set by unless `--limit`
+/// is given. Points that escape after more iterations than the limit are
+/// counted in, so the render default of 255 overestimates the area by a
+/// good deal more than the sampling error of a large estimate.
+const AREA_LIMIT: usize = 10000;
+
+/// Format one row's escape counts as `--scanline` prints them: separated by
+/// commas, with `-` for the points in the set.
+fn scanline_text(counts: &[Option<usize>]) -> String {
+
+    let counts: Vec<String> = counts.iter()
+        .map(|count| count.map_or("-".to_string(), |count| count.to_string()))
+        .collect();
+    counts.join(",")
+}
+
+/// Return how `flags` say to evaluate the points of an image of `bounds`
+/// pixels from `upper_left` to `lower_right` that will be written to
+/// `filename`.
+fn iteration_for(filename: &str,
+                 bounds: (usize, usize),
+                 upper_left: Complex<f64>,
+                 lower_right: Complex<f64>,
+                 flags: &Flags) -> Iteration {
+
+    // Deep zooms fall back to f64 rather than render blocks of identical pixels.
+    let precision = match flags.precision {
+        Precision::Single if !f32_resolves(bounds, upper_left, lower_right) => {
+            warn!("{}: this view is too deep for f32; using f64", filename);
+            Precision::Double
+        }
+        precision => precision
+    };
+
+    // Rainbow coloring is made to blend, so it always uses smooth counts.
+    let smooth = flags.smooth || flags.color == Coloring::Rainbow;
+    Iteration { limit: flags.limit, julia: flags.julia, smooth,
+                bailout: flags.bailout, growth: growth(flags), fractal: flags.fractal, trap: flags.trap,
+                precision, distance: flags.color == Coloring::Distance,
+                interior: flags.interior != Interior::Black || flags.color == Coloring::ExternalAngle,
+                max_period: if flags.interior == Interior::Period { flags.max_period } else { 0 },
+                tile_size: flags.tile_size }
+}
+
+/// Return the factor `flags` say a step must grow `|z|` by to escape, if
+/// they say to test for growth rather than the bailout radius.
+fn growth(flags: &Flags) -> Option<f64> {
+
+    (flags.escape == Escape::Growth).then_some(flags.growth_factor)
+}
+
+/// Return how `flags` say to color escape counts.
+fn color_scheme(flags: &Flags) -> ColorScheme {
+
+    ColorScheme { palette: flags.palette, gradient: flags.gradient.clone(),
+                  coloring: flags.color, invert: flags.invert, color_scale: flags.color_scale,
+                  palette_offset: flags.palette_offset, interior: flags.interior, contour: flags.contour,
+                  dither: None }
+}
+
+/// Render the rectangle from `upper_left` to `lower_right` as an image of
+/// `bounds` pixels, as `flags` describe, using the threads of `pool`, and
+/// write it to `filename`.
+fn render_to_file(filename: &str,
+                  bounds: (usize, usize),
+                  upper_left: Complex<f64>,
+                  lower_right: Complex<f64>,
+                  flags: &Flags,
+                  pool: &ThreadPool) -> Result<(), MandelError> {
+
+    let started = Instant::now();
+
+    // A preview renders a smaller image of the same rectangle, then
+    // stretches it back out to the requested size.
+    let requested = bounds;
+    let bounds = ((bounds.0 / flags.preview_scale).max(1), (bounds.1 / flags.preview_scale).max(1));
+
+    // Supersampling renders a proportionally larger image of the same
+    // rectangle, then averages it back down to the requested size.
+    let factor = flags.supersample;
+    let render_bounds = (bounds.0 * factor, bounds.1 * factor);
+    let iteration = iteration_for(filename, render_bounds, upper_left, lower_right, flags);
+    debug!("{}: rendering {}x{} pixels from {} to {}, at a limit of {}",
+           filename, render_bounds.0, render_bounds.1, upper_left, lower_right, iteration.limit);
+
+    let samples = flags.samples;
+    let mask = flags.mask.as_ref().map(|path| read_mask(path, render_bounds)).transpose()?;
+    let progress = if flags.quiet {
+        Progress::hidden(render_bounds.1 * samples)
+    } else {
+        Progress::new(render_bounds.1 * samples)
+    };
+
+    // Counts are colored and shrunk back down by supersampling's factor,
+    // at either depth; they may cover only the first rows of the render.
+    let scheme = ColorScheme { dither: flags.dither.then_some(render_bounds.0), ..color_scheme(flags) };
+    let color = |counts: &Counts| match flags.transparent_interior {
+        true => map_rgba_transparent(counts, iteration.limit, &scheme),
+        false => map_colors(counts, iteration.limit, &scheme)
+    };
+    let gray = |counts: &Counts| {
+        let mut samples = map_gray16(counts, iteration.limit);
+        if flags.invert {
+            invert(&mut samples);
+        }
+        samples
+    };
+    let rendered = |counts: &Counts| (render_bounds.0, counts.len() / render_bounds.0);
+    // Gamma correction applies to the finished 8-bit bytes, after averaging,
+    // but not to alpha, which says how much of a pixel is covered.
+    let gamma = gamma_table(flags.gamma);
+    let correct = |pixels: &mut [u8]| match flags.transparent_interior {
+        true => pixels.chunks_mut(4).for_each(|pixel| apply_gamma(&mut pixel[..3], &gamma)),
+        false => apply_gamma(pixels, &gamma)
+    };
+    let colors = |counts: &Counts| downsample_with(&color(counts), rendered(counts), factor, flags.downscale);
+    let grays = |counts: &Counts| downsample_with(&gray(counts), rendered(counts), factor, flags.downscale);
+
+    // A checkpoint writes the rows rendered so far, leaving the rest black.
+    let write_checkpoint = |counts: &Counts| {
+        let mut pixels = match flags.bit_depth {
+            BitDepth::Eight => {
+                let mut pixels = colors(counts);
+                correct(&mut pixels);
+                pixels
+            }
+            BitDepth::Sixteen => big_endian_bytes(&grays(counts))
+        };
+        let rows = rendered(counts).1 / factor;
+        debug!("{}: writing a checkpoint of {} of {} rows", filename, rows, bounds.1);
+        pixels.resize(pixels.len() / rows * bounds.1, 0);
+        write_image_retrying(filename, &pixels, bounds, flags.bit_depth, flags.retries, warn_of_retry)
+    };
+
+    // A single sample goes through the ordinary grid; more are each jittered
+    // afresh, and averaged once colored.
+    let mut rng = Rng::new(flags.seed);
+    let mut stats = RenderStats::default();
+    let mut profile: Vec<RenderStats> = Vec::new();
+    let mut elapsed = Duration::ZERO;
+    let mut render_sample = || {
+        let start = Instant::now();
+        let counts = pool.install(|| {
+            if let Some(rows) = flags.checkpoint {
+                render_with_checkpoints(render_bounds, upper_left, lower_right, iteration, rows * factor,
+                                        &progress, write_checkpoint)
+            } else if flags.border_trace {
+                Ok(render_mariani_silver(render_bounds, upper_left, lower_right, iteration, TRACE_MIN_SIZE,
+                                         &progress).0)
+            } else if let Some(mask) = &mask {
+                Ok(render_masked(render_bounds, upper_left, lower_right, iteration, mask, &progress))
+            } else if samples == 1 {
+                Ok(render_with_progress(render_bounds, upper_left, lower_right, iteration, &progress))
+            } else {
+                Ok(render_jittered(render_bounds, upper_left, lower_right, iteration, &mut rng, &progress))
             }
+        })?;
+        elapsed += start.elapsed();
+        if flags.stats {
+            // `check_conflicts` rules out the kinds of counts without these.
+            stats += RenderStats::of(&counts, iteration.limit).unwrap_or_default();
+        }
+        if flags.profile.is_some() {
+            let rows = RenderStats::of_rows(&counts, render_bounds.0, iteration.limit).unwrap_or_default();
+            profile.resize(rows.len(), RenderStats::default());
+            profile.iter_mut().zip(rows).for_each(|(total, row)| *total += row);
         }
+        Ok(counts)
+    };
 
+    // An EXR file holds the raw values of the single sample `run` allows.
+    #[cfg(feature = "exr")]
+    if ImageFormat::from_filename(filename)? == ImageFormat::Exr {
+        let counts = render_sample();
+        progress.finish();
+        let counts = counts?;
+        if let Some(path) = &flags.profile {
+            write_profile(path, &profile)?;
+        }
+        return write_exr(filename, &counts.values(), bounds);
     }
+
+    // Rendering and coloring alternate sample by sample, so coloring's time
+    // is what's left after rendering's; edge antialiasing counts both as
+    // rendering.
+    let allocating = started.elapsed();
+    let coloring_started = Instant::now();
+
+    // A checkpoint that can't be written stops the render, and `failure`
+    // holds why.
+    let mut failure = None;
+    let mut pixels = if flags.antialias_edges {
+        // `check_conflicts` leaves a single sample at the image's own size.
+        let start = Instant::now();
+        let pixels = pool.install(|| match flags.bit_depth {
+            BitDepth::Eight => render_antialiased_edges(bounds, upper_left, lower_right, iteration,
+                                                        EDGE_THRESHOLD, &progress, color),
+            BitDepth::Sixteen => big_endian_bytes(&render_antialiased_edges(bounds, upper_left, lower_right,
+                                                                            iteration, EDGE_THRESHOLD,
+                                                                            &progress, gray))
+        });
+        elapsed = start.elapsed();
+        pixels
+    } else {
+        let renders = (0..samples).map_while(|_| render_sample().map_err(|err| failure = Some(err)).ok());
+        match flags.bit_depth {
+            BitDepth::Eight => average(renders.map(|counts| colors(&counts))),
+            BitDepth::Sixteen => big_endian_bytes(&average(renders.map(|counts| grays(&counts))))
+        }
+    };
+    progress.finish();
+    if let Some(err) = failure {
+        return Err(err);
+    }
+    if flags.bit_depth == BitDepth::Eight {
+        correct(&mut pixels);
+    }
+    // `check_conflicts` leaves the mask the size of the 8-bit image.
+    if let Some(mask) = &mask {
+        fill_masked_out(&mut pixels, mask, flags.mask_fill);
+    }
+    // `check_conflicts` leaves only 8-bit samples to stretch.
+    if bounds != requested {
+        pixels = upscale_bilinear(&pixels, bounds, requested);
+    }
+    let bounds = requested;
+    mirror_pixels(&mut pixels, bounds, flags.mirror);
+    let coloring = coloring_started.elapsed().saturating_sub(elapsed);
+
+    if flags.stats {
+        info!("{}: {} iterations, {:.2}% of pixels in the set, rendered in {:.3}s",
+              filename, stats.iterations, 100.0 * stats.in_set_fraction(), elapsed.as_secs_f64());
+    }
+    if let Some(path) = &flags.profile {
+        write_profile(path, &profile)?;
+    }
+
+    let encoding_started = Instant::now();
+    write_image_retrying(filename, &pixels, bounds, flags.bit_depth, flags.retries, warn_of_retry)?;
+    info!("{}: allocated in {:?}, rendered in {:?}, colored in {:?}, encoded in {:?}",
+          filename, allocating, elapsed, coloring, encoding_started.elapsed());
+
+    if flags.metadata {
+        let meta = RenderMeta { bounds, upper_left, lower_right, limit: flags.limit,
+                                palette: flags.palette, fractal: flags.fractal,
+                                version: env!("CARGO_PKG_VERSION").to_string(),
+                                duration: elapsed.as_secs_f64() };
+        write_metadata(filename, &meta)?;
+    }
+
+    Ok(())
 }
 
-fn parse_complex(s : &str) -> Option<Complex<f64>> {
+/// Render the rectangle from `upper_left` to `lower_right` as an image of
+/// `bounds` pixels, as `flags` describe, using the threads of `pool`, one
+/// tile of a deep-zoom pyramid at a time, with `render_tile`. Write the whole
+/// image to `filename`, and the top `flags.levels` levels of the pyramid, or
+/// all of them, to the directory `dir`.
+fn render_pyramid(filename: &str,
+                  dir: &str,
+                  bounds: (usize, usize),
+                  upper_left: Complex<f64>,
+                  lower_right: Complex<f64>,
+                  flags: &Flags,
+                  pool: &ThreadPool) -> Result<(), MandelError> {
 
-    match parse_pair(s, ',') {
+    let iteration = iteration_for(filename, bounds, upper_left, lower_right, flags);
+    let scheme = color_scheme(flags);
+    let gamma = gamma_table(flags.gamma);
+    let progress = if flags.quiet { Progress::hidden(bounds.1) } else { Progress::new(bounds.1) };
 
-        None => None,
-        Some((re, im)) => Some(Complex{re, im})
+    // Each band of tiles is rendered a tile at a time, then laid out row by
+    // row into the whole image.
+    let grid = tile_grid(bounds, PYRAMID_TILE_SIZE);
+    let mut pixels = Vec::new();
+    for row in 0..grid.1 {
+        let top = row * PYRAMID_TILE_SIZE;
+        let height = PYRAMID_TILE_SIZE.min(bounds.1 - top);
+        let tiles: Vec<Vec<u8>> = (0..grid.0).map(|column| {
+            let left = column * PYRAMID_TILE_SIZE;
+            let size = (PYRAMID_TILE_SIZE.min(bounds.0 - left), height);
+            debug!("{}: rendering the {}x{} tile at ({}, {})", filename, size.0, size.1, left, top);
+            let counts = pool.install(|| render_tile(bounds, (left, top), size, upper_left, lower_right,
+                                                     iteration));
+            // Every tile but the last in a row or column is a multiple of
+            // the dither pattern's size, so the pattern lines up across them.
+            let scheme = ColorScheme { dither: flags.dither.then_some(size.0), ..scheme.clone() };
+            let mut colors = map_colors(&counts, iteration.limit, &scheme);
+            apply_gamma(&mut colors, &gamma);
+            colors
+        }).collect();
+        for y in 0..height {
+            for tile in &tiles {
+                let row_bytes = tile.len() / height;
+                pixels.extend_from_slice(&tile[y * row_bytes..(y + 1) * row_bytes]);
+            }
+            progress.row_done();
+        }
     }
+    progress.finish();
+
+    write_image_retrying(filename, &pixels, bounds, BitDepth::Eight, flags.retries, warn_of_retry)?;
+    let image = Raster { pixels, bounds, depth: BitDepth::Eight };
+    let levels = flags.levels.unwrap_or(pyramid_level(bounds) + 1);
+    debug!("{}: writing {} pyramid levels to {}", filename, levels, dir);
+    write_pyramid(Path::new(dir), &image, levels, PYRAMID_TILE_SIZE)?;
+    Ok(())
 }
 
+/// Join the tiles named by `args`, after the output filename and the grid's
+/// size, into one image.
+fn stitch(args: &[String]) -> Result<(), MandelError> {
 
-fn lerp(a : f64, b : f64, t : f64) -> f64 {
+    if args.len() < 3 {
+        return Err(MandelError::BadFlag("stitch takes a FILE, a GRID and the TILEs to join".to_string()));
+    }
+    let grid = parse_pair(&args[1], 'x').filter(|&(columns, rows): &(usize, usize)| columns > 0 && rows > 0)
+        .ok_or_else(|| {
+            MandelError::BadFlag(format!("error parsing tile grid '{}': expected COLUMNSxROWS", args[1]))
+        })?;
 
-    a * (1.0 - t) + b * t
+    ImageFormat::from_filename(&args[0])?;
+    let stitched = stitch_pngs(&args[2..], grid)?;
+    write_image(&args[0], &stitched.pixels, stitched.bounds, stitched.depth)
 }
 
+/// Compare the two images named by `args`, reporting how many pixels differ
+/// and by how much, and fail if any sample differs by more than the
+/// `--tolerance` that may follow them.
+fn compare(args: &[String]) -> Result<(), MandelError> {
 
-/// Given the row and column of a pixel in the output image, return the
-/// corresponding point on the complex plane.
-///
-/// `bounds` is a pair giving the width and height of the image in pixels.
-/// `pixel` is a (column, row) pair indicating a particular pixel in that image.
-/// The `upper_left` and `lower_right` parameters are points on the complex
-/// plane designating the area our image covers.
-fn pixel_to_point(bounds : (usize, usize),
-                  pixel : (usize, usize),
-                  upper_left : Complex<f64>,
-                  lower_right : Complex<f64>) -> Complex<f64> {
+    let tolerance = match args {
+        [_, _] => 0,
+        [_, _, flag, value] if flag == "--tolerance" => value.parse().map_err(|_| {
+            MandelError::BadFlag("--tolerance must be a non-negative integer".to_string())
+        })?,
+        _ => return Err(MandelError::BadFlag("compare takes two images, and optionally --tolerance N"
+                                             .to_string()))
+    };
 
-    Complex{
-        re:lerp(upper_left.re, lower_right.re, pixel.0 as f64 / bounds.0 as f64),
-        im:lerp(upper_left.im, lower_right.im, pixel.1 as f64 / bounds.1 as f64)
+    let diff = compare_pngs(&args[0], &args[1])?;
+    println!("{} pixels differ, by up to {}", diff.pixels, diff.max);
+    if diff.max > tolerance {
+        return Err(MandelError::ImagesDiffer { pixels: diff.pixels, max: diff.max, tolerance });
     }
+    Ok(())
+}
+
+/// Check that the image named by `args` decodes, has the dimensions that
+/// follow it, and isn't all one color, reporting that it passed or failing.
+fn verify(args: &[String]) -> Result<(), MandelError> {
+
+    let [filename, pixels] = args else {
+        return Err(MandelError::BadFlag("verify takes an image and its PIXELS".to_string()));
+    };
+
+    verify_png(filename, parse_dimensions(pixels)?)?;
+    println!("{}: ok", filename);
+    Ok(())
 }
 
-/// Try to determine if `c` is in the Mandelbrot set, using at most `limit' iterations to decide.
+/// The ways to run the program, each after its name, for the usage line.
+const USAGE: [&str; 11] = [
+    "FILE PIXELS UPPERLEFT LOWERRIGHT [OPTIONS]",
+    "--output FILE --pixels PIXELS --upper-left RE,IM --lower-right RE,IM",
+    "FILE PIXELS --center RE,IM --zoom WIDTH [OPTIONS]",
+    "FILE PIXELS --center RE,IM --scale HEIGHT [OPTIONS]",
+    "FILE PIXELS --preset NAME [OPTIONS]",
+    "[FILE [PIXELS ...]] --config SCENE [OPTIONS]",
+    "--area SAMPLES [--limit N] [--seed N]",
+    "FILE --show-palette NAME [--limit N]",
+    "stitch FILE GRID TILE...",
+    "compare FIRST SECOND [--tolerance N]",
+    "verify FILE PIXELS"
+];
+
+/// What the help says after the options, beyond an example.
+const NOTES: &str = "\
+FILE may be a .png or .ppm image, or - to write a PNG to standard output.
+With --center, the view is WIDTH wide on the real axis or HEIGHT high on the
+imaginary axis, with the other side following from the aspect ratio of PIXELS.
+
+A SCENE is a TOML file that may set filename, pixels, upper_left and
+lower_right, standing in for any arguments left off the end of the command line,
+and limit, palette and fractal, which options override.
+
+Missing pixels and corners are also taken from the MANDEL_BOUNDS, MANDEL_UL and
+MANDEL_LR environment variables, after the command line and any SCENE.
+
+Warnings and errors are logged to standard error. RUST_LOG may set a level
+instead, as error, warn, info, debug, trace or off, alone or after mandelbrot=;
+each -v logs one level more than warn, and the more detailed of the two wins.
+
+stitch joins a GRID of PNG TILEs, all the same size, into FILE: 4x2 means four
+across and two down, given left to right along each row from the top.
+
+compare reports how many pixels of two PNGs of the same size differ, and by how
+much, and fails if any sample differs by more than N (0).
+
+Presets, each with a limit to suit it, which --limit overrides: full, seahorse,
+elephant and triple-spiral.";
+
+/// The positional arguments, in order, each with the flag that can give it
+/// by name instead, what that flag's value is called, and the help for both.
+const POSITIONALS: [(&str, &str, &str, &str); 4] = [
+    ("FILE", "output", "FILE", "the image to write"),
+    ("PIXELS", "pixels", "PIXELS", "the image's width and height, as WIDTHxHEIGHT"),
+    ("UPPERLEFT", "upper-left", "RE,IM", "the point on the complex plane at the upper-left corner"),
+    ("LOWERRIGHT", "lower-right", "RE,IM", "the point on the complex plane at the lower-right corner")
+];
+
+/// The options, in the order the help lists them: each one's name, what its
+/// value is called, or `None` for a switch that takes none, and its help.
+const OPTIONS: [(&str, Option<&str>, &str); 65] = [
+    ("preset", Some("NAME"), "render the preset view NAME instead of corners"),
+    ("center", Some("RE,IM"), "the middle of the view, instead of the corners"),
+    ("zoom", Some("WIDTH"), "with --center, the view's width on the real axis"),
+    ("scale", Some("HEIGHT"),
+     "with --center, the height of the view on the\n\
+      imaginary axis"),
+    ("config", Some("SCENE"), "take defaults for arguments and options from SCENE"),
+    ("limit", Some("N"),
+     "iterations per point before assuming it's in the\n\
+      set (255)"),
+    ("auto-iterations", None,
+     "choose the limit from the view's width, more the\n\
+      deeper it zooms; with --frames, afresh for each\n\
+      frame"),
+    ("palette", Some("NAME"), "grayscale (default), fire or ocean"),
+    ("gradient", Some("FILE"),
+     "color with the stops in FILE instead of a palette:\n\
+      one POSITION R G B per line, positions running\n\
+      from 0 to 1"),
+    ("color", Some("NAME"),
+     "how escape times choose colors: linear (default);\n\
+      histogram, to spread the palette evenly over the\n\
+      pixels of the image; distance, to shade by\n\
+      distance from the set, which shows its thinnest\n\
+      filaments; log, to shade by the logarithm of the\n\
+      escape time; rainbow, to cycle through the hues\n\
+      with the smooth escape time; normalize, to\n\
+      stretch the image's escape times over the whole\n\
+      palette; or external-angle, to take the hue from\n\
+      the angle at which each point escapes, tracing\n\
+      the set's external rays"),
+    ("color-scale", Some("D"),
+     "with --color rainbow, degrees of hue per iteration\n\
+      (10)"),
+    ("palette-offset", Some("K"),
+     "rotate escaping points' colors K of the palette's\n\
+      256 steps along it, wrapping around, to cycle them\n\
+      (0)"),
+    ("gamma", Some("G"),
+     "brighten the midtones by raising each 8-bit value,\n\
+      as a fraction of white, to the power 1/G (1)"),
+    ("interior", Some("NAME"),
+     "how to color points in the set, by where their\n\
+      orbits end up: black (default), angle or\n\
+      magnitude, or by the period of the cycle they\n\
+      settle into: period"),
+    ("max-period", Some("N"), "the longest cycle --interior period looks for (32)"),
+    ("contour", Some("N"),
+     "draw black lines over the points whose escape\n\
+      counts are multiples of N"),
+    ("julia", Some("RE,IM"), "render the Julia set for this constant"),
+    ("smooth", None, "color by fractional escape time, without banding"),
+    ("trap", Some("NAME"),
+     "color by how close each orbit comes to a point at\n\
+      the origin, or a cross along the axes: point or\n\
+      cross"),
+    ("invert", None, "invert the shades, so points in the set are white"),
+    ("dither", None,
+     "dither linear and log shades with a 4x4 Bayer\n\
+      pattern, rather than rounding them down, against\n\
+      banding"),
+    ("transparent-interior", None,
+     "leave points in the set transparent, in a PNG with\n\
+      alpha"),
+    ("threads", Some("N"), "rendering threads; 0 means one per core (0)"),
+    ("tile-size", Some("N"),
+     "render in N x N blocks of pixels, which can suit\n\
+      the cache better; 0 means row by row (0)"),
+    ("quiet", None, "don't show progress"),
+    ("area", Some("SAMPLES"),
+     "estimate the area of the set from SAMPLES random\n\
+      points, instead of rendering (--limit 10000)"),
+    ("dry-run", None,
+     "print the corners, pixel step and memory needed,\n\
+      and stop"),
+    ("ascii", None,
+     "print an 80x40 character preview of the view, and\n\
+      stop"),
+    ("show-palette", Some("NAME"),
+     "write a 512x64 strip of the palette NAME, from a\n\
+      count of 0 on the left to the limit on the right,\n\
+      to FILE, and stop"),
+    ("scanline", Some("Y"),
+     "print the escape counts of row Y, from 0 at the\n\
+      top, with commas between them and - for points in\n\
+      the set, and stop"),
+    ("dump-orbit", Some("PATH"),
+     "write the orbit of the view's center to PATH as\n\
+      CSV"),
+    ("metadata", None,
+     "write how each image was made to FILE.json beside\n\
+      it"),
+    ("stats", None,
+     "log the iterations, pixels in the set and time\n\
+      taken, at the info level"),
+    ("profile", Some("PATH"),
+     "write each row's iterations and pixels in the set\n\
+      to PATH as CSV, summed over --samples"),
+    ("verbose", None,
+     "log the time spent parsing the arguments, and then\n\
+      allocating, rendering, coloring and encoding each\n\
+      image; given twice, as -vv, log each tile and\n\
+      checkpoint too"),
+    ("allow-flipped", None,
+     "accept corners that mirror or turn the image\n\
+      upside down"),
+    ("checkpoint", Some("N"),
+     "write the image so far every N rows, leaving the\n\
+      rest black"),
+    ("mask", Some("PATH"),
+     "only render the pixels that are set in the PNG at\n\
+      PATH, which must be the image's size"),
+    ("mask-fill", Some("R,G,B"),
+     "the color of the pixels the mask leaves out\n\
+      (0,0,0)"),
+    ("retry", Some("N"),
+     "try writing each image up to N more times if\n\
+      writing fails, waiting a little longer each time\n\
+      (0)"),
+    ("border-trace", None,
+     "fill rectangles whose borders share an escape\n\
+      count, rather than evaluate every pixel: faster,\n\
+      but thin filaments may be lost"),
+    ("supersample", Some("N"), "average N x N samples for each pixel (1)"),
+    ("downscale", Some("NAME"),
+     "how to shrink supersamples back down: box averages\n\
+      each pixel's own, lanczos blends its neighbours'\n\
+      in too, for crisper edges (box)"),
+    ("antialias-edges-only", None,
+     "average 3 x 3 samples only for the pixels whose\n\
+      neighbours' escape times differ from theirs: much\n\
+      faster than --supersample 3, and about as smooth\n\
+      along the boundary"),
+    ("preview-scale", Some("S"),
+     "render S times smaller along each side, for speed,\n\
+      and stretch the result back out to PIXELS,\n\
+      blurring it (1)"),
+    ("auto-center", None,
+     "move the view, keeping its width, to center on the\n\
+      point of a coarse scan of it that escapes slowest\n\
+      beside the set"),
+    ("crop", Some("X,Y,W,H"),
+     "render only the W x H pixels from column X and row\n\
+      Y of the view, as an image of their own"),
+    ("pyramid", Some("DIR"),
+     "render a tile at a time, and cut the image into a\n\
+      deep-zoom pyramid of 256x256 tiles in DIR, as\n\
+      DIR/LEVEL/X_Y.png, each level half the size of the\n\
+      one above"),
+    ("levels", Some("L"), "with --pyramid, write only the L largest levels"),
+    ("mirror", Some("NAME"),
+     "reflect the finished image's top half onto its\n\
+      bottom (horizontal) or its left half onto its\n\
+      right (vertical), symmetric or not (none)"),
+    ("max-pixels", Some("N"),
+     "refuse to render more than N pixels, counting\n\
+      supersampling (256000000)"),
+    ("samples", Some("S"),
+     "average S randomly placed samples for each pixel\n\
+      (1)"),
+    ("seed", Some("N"),
+     "seed for placing --samples, for reproducible\n\
+      images (0)"),
+    ("bailout", Some("R"),
+     "escape radius, at least 2 (2, or 256 with --smooth\n\
+      or --color rainbow, whose fractions grow rough\n\
+      at small radii)"),
+    ("escape", Some("TEST"),
+     "when an orbit counts as escaping: on leaving the\n\
+      --bailout circle, radius (default), or on a step\n\
+      growing |z| more than --growth-factor times, growth"),
+    ("growth-factor", Some("F"), "with --escape growth, a factor above 1 (2)"),
+    ("frames", Some("N"),
+     "render N frames zooming from the view to a target,\n\
+      written as FILE with _0001, _0002, ... before the\n\
+      extension"),
+    ("resume", None,
+     "with --frames, skip the frames already written\n\
+      whole"),
+    ("target", Some("RE,IM"), "with --frames, the center of the last frame"),
+    ("target-scale", Some("S"), "with --frames, the height of the last frame"),
+    ("fractal", Some("NAME"), "mandelbrot (default), burningship or tricorn"),
+    ("power", Some("D"),
+     "iterate z^D + c instead of z^2 + c, for D greater\n\
+      than 1 or negative; D may be fractional, like 2.5"),
+    ("output-bit-depth", Some("N"), "8 (default), or 16 for finer grayscale gradients"),
+    ("precision", Some("TYPE"),
+     "f64 (default), or the faster f32 for shallow\n\
+      zooms; views too deep for f32 use f64 anyway;\n\
+      or the much slower dd, double-double, for views\n\
+      too deep for f64, with whole escape counts of the\n\
+      Mandelbrot set or its Julia sets"),
+    ("help", None, "print this help, and stop")
+];
+
+/// Return the parser for the command line of `program` when it renders,
+/// which also makes the help `usage` prints.
 ///
-/// If `c` is not a member, return `Some(i)`, where `i` is the number of
-/// iterations it took for `c` to leave the circle of radius 2 centered
-/// on the origin. If `c` seems to be a member (more precisely, if we
-/// reached the iteration limit without being able to prove that `c` is
-/// not a member), return `None`.
-fn escape_time(c : Complex<f64>, limit:usize) -> Option<usize> {
+/// Each option's value is parsed as `value_parser` says, and may start with
+/// a hyphen, as negative numbers and corners do. The positional arguments
+/// are kept as text. An option given twice takes the last value.
+fn command(program: &str) -> Command {
 
-    let mut z:Complex<f64> = Complex{ re: 0.0, im: 0.0 };
+    let usage : Vec<String> = USAGE.iter().map(|line| format!("{} {}", program, line)).collect();
+    let example = format!("Example: {} mandelbrot.png 1024x768 -1.20,0.35 -1,0.2 --limit 1000", program);
+    let exr = if cfg!(feature = "exr") {
+        "\nA .exr FILE holds each pixel's raw escape time as a float, -1 in the set."
+    } else {
+        ""
+    };
+    let notes = NOTES.replacen('\n', &format!("{}\n", exr), 1);
 
-    for i in 0..limit {
+    let mut command = Command::new("mandelbrot")
+        .bin_name(program)
+        .override_usage(usage.join("\n       "))
+        .after_help(format!("{}\n\n{}", example, notes))
+        .disable_help_flag(true)
+        .args_override_self(true);
 
-        if z.norm_sqr() > 4.0 {
-            return Some(i);
+    // The positional arguments and the flags that stand in for them can't be
+    // mixed; given by name, each needs the ones before it, or it would fill
+    // the wrong place.
+    let names = POSITIONALS.map(|(positional, ..)| positional);
+    for (i, &(positional, name, value, help)) in POSITIONALS.iter().enumerate() {
+        command = command.arg(Arg::new(positional).help(help).allow_hyphen_values(i >= 2));
+        let mut flag = Arg::new(name).long(name).value_name(value).allow_hyphen_values(true)
+            .help(format!("{}, given by name rather than by position", positional))
+            .conflicts_with_all(names);
+        if i > 0 {
+            flag = flag.requires(POSITIONALS[i - 1].1);
         }
+        command = command.arg(flag);
+    }
+    // More arguments than there's room for are counted, not refused, so the
+    // error can say how many were expected.
+    command = command.arg(Arg::new("more").action(ArgAction::Append).allow_hyphen_values(true).hide(true));
+
+    for (name, value, help) in OPTIONS {
+        let arg = Arg::new(name).long(name).help(help);
+        command = command.arg(match (name, value) {
+            ("help", _) => arg.action(ArgAction::Help),
+            ("verbose", _) => arg.short('v').action(ArgAction::Count),
+            (_, Some(value)) => arg.value_name(value).allow_hyphen_values(true)
+                .value_parser(value_parser(name)),
+            (_, None) => arg.action(ArgAction::SetTrue)
+        });
+    }
+
+    command
+}
+
+/// Return the parser for the value of the option `name`, which turns its text
+/// into the type `parse_flags` reads it back as, or into a message saying
+/// why it can't. Options whose values are paths keep them as text.
+fn value_parser(name: &'static str) -> ValueParser {
 
-        z = z * z + c;
+    let integer = move |least: usize, kind: &'static str| move |text: &str| match text.parse::<usize>() {
+        Ok(n) if n >= least => Ok(n),
+        _ => Err(format!("--{} must be a {} integer", name, kind))
+    };
+    let point = |argument: &'static str| move |text: &str| parse_point::<f64>(text, argument);
+
+    match name {
+        "limit" | "contour" | "preview-scale" | "levels" | "area" | "checkpoint" | "supersample"
+            | "samples" | "max-period" | "max-pixels" | "frames" => ValueParser::new(integer(1, "positive")),
+        "palette-offset" | "scanline" | "retry" | "threads" | "tile-size" =>
+            ValueParser::new(integer(0, "non-negative")),
+        "color-scale" | "gamma" | "zoom" | "scale" | "target-scale" =>
+            ValueParser::new(move |text: &str| parse_extent(text, &format!("--{}", name))),
+        "center" => ValueParser::new(point("--center point")),
+        "target" => ValueParser::new(point("--target point")),
+        "julia" => ValueParser::new(point("--julia constant")),
+        "preset" => ValueParser::new(find_preset),
+        "palette" | "show-palette" => value_parser!(Palette).into(),
+        "color" => value_parser!(Coloring).into(),
+        "interior" => value_parser!(Interior).into(),
+        "fractal" => value_parser!(Fractal).into(),
+        "power" => ValueParser::new(parse_power),
+        "output-bit-depth" => value_parser!(BitDepth).into(),
+        "precision" => value_parser!(Precision).into(),
+        "trap" => value_parser!(Trap).into(),
+        "crop" => value_parser!(Crop).into(),
+        "mirror" => value_parser!(Mirror).into(),
+        "escape" => value_parser!(Escape).into(),
+        "downscale" => value_parser!(Downscale).into(),
+        "bailout" => ValueParser::new(|text: &str| match text.parse::<f64>() {
+            Ok(bailout) if bailout >= 2.0 => Ok(bailout),
+            _ => Err("--bailout must be a number no less than 2")
+        }),
+        "growth-factor" => ValueParser::new(|text: &str| match text.parse::<f64>() {
+            Ok(factor) if factor.is_finite() && factor > 1.0 => Ok(factor),
+            _ => Err("--growth-factor must be a number greater than 1")
+        }),
+        "mask-fill" => ValueParser::new(|text: &str| {
+            parse_color(text).ok_or("--mask-fill must be R,G,B, each from 0 to 255")
+        }),
+        "seed" => ValueParser::new(|text: &str| {
+            text.parse::<u64>().map_err(|_| "--seed must be a non-negative integer")
+        }),
+        _ => value_parser!(String)
     }
+}
+
+/// Parse the value of `--power`: a whole power of 2 is the Mandelbrot set
+/// itself, and any other power above 1, or below 0, a multibrot.
+fn parse_power(text: &str) -> Result<Fractal, &'static str> {
 
-    None // no escape time (assumed infinite)
+    match (text.parse::<u32>(), text.parse::<f64>()) {
+        (Ok(2), _) => Ok(Fractal::Mandelbrot),
+        (Ok(power), _) if power > 2 => Ok(Fractal::Multibrot(power)),
+        (Err(_), Ok(power)) if power.is_finite() && !(0.0..=1.0).contains(&power) =>
+            Ok(Fractal::FractionalMultibrot(power)),
+        _ => Err("--power must be a number greater than 1, or a negative one")
+    }
 }
 
-fn render(pixels : &mut [u8],
-        bounds : (usize, usize),
-        upper_left : Complex<f64>,
-        lower_right : Complex<f64>) {
+fn usage(program: &str) {
 
-    assert!(pixels.len() == bounds.0 * bounds.1);
+    eprint!("{}", command(program).render_help());
+}
 
-    for y in 0..bounds.1 {
+/// Optional `--name value` settings accepted anywhere on the command line.
+#[derive(Debug, PartialEq)]
+struct Flags {
+    /// Maximum number of iterations `escape_time` may spend on each point.
+    limit: usize,
 
-        for x in 0..bounds.0 {
+    /// Whether to choose `limit` from the width of the view instead.
+    auto_iterations: bool,
 
-            let point = pixel_to_point(bounds, (x, y), upper_left, lower_right);
+    /// Colors used for escaping points.
+    palette: Palette,
+
+    /// A gradient to color with instead of `palette`.
+    gradient: Option<Gradient>,
+
+    /// How escape counts choose colors from `palette`.
+    color: Coloring,
+
+    /// With rainbow coloring, the degrees of hue each iteration moves on.
+    color_scale: f64,
+
+    /// How far to rotate escaping points' colors along the palette.
+    palette_offset: usize,
+
+    /// The gamma to correct the finished 8-bit image by.
+    gamma: f64,
+
+    /// How to color the points in the set.
+    interior: Interior,
+
+    /// The spacing in escape counts of the contour lines to draw, if any.
+    contour: Option<usize>,
+
+    /// The constant `c` to render the Julia set of, if any.
+    julia: Option<Complex<f64>>,
+
+    /// Whether to color by fractional escape time rather than whole counts.
+    smooth: bool,
+
+    /// The orbit trap to color by, instead of escape time.
+    trap: Option<Trap>,
+
+    /// Whether to invert the image's shades, making points in the set white.
+    invert: bool,
+
+    /// Number of rendering threads, or zero for one per logical core.
+    threads: usize,
+
+    /// The side of the blocks of pixels to render at a time, or zero to
+    /// render row by row.
+    tile_size: usize,
+
+    /// Whether to suppress the progress display.
+    quiet: bool,
+
+    /// Whether to report the work each render took when it's done.
+    stats: bool,
+
+    /// The file to write each row's iterations and pixels in the set to, as
+    /// CSV.
+    profile: Option<String>,
+
+    /// Whether to write an alpha channel, leaving points in the set fully
+    /// transparent.
+    transparent_interior: bool,
+
+    /// Whether to dither linear and log shades to bytes, rather than round
+    /// them down.
+    dither: bool,
+
+    /// How many more times to try writing an image when writing it fails.
+    retries: usize,
+
+    /// A PNG the size of the image whose set pixels are the only ones to
+    /// render.
+    mask: Option<String>,
+
+    /// The color to paint the pixels `mask` leaves clear.
+    mask_fill: [u8; 3],
+
+    /// The longest cycle `--interior period` looks for.
+    max_period: usize,
+
+    /// Whether to describe the render instead of doing it.
+    dry_run: bool,
+
+    /// Whether to print a preview of the view as text instead of rendering it.
+    ascii: bool,
+
+    /// A palette to draw a strip of instead of rendering anything.
+    show_palette: Option<Palette>,
+
+    /// A row to print the escape counts of instead of rendering the image.
+    scanline: Option<usize>,
+
+    /// Whether to supersample only the pixels on edges between escape counts.
+    antialias_edges: bool,
+
+    /// How many times smaller along each side to render the image, before
+    /// stretching it back out to size.
+    preview_scale: usize,
+
+    /// Which half of the finished image to reflect onto the other.
+    mirror: Mirror,
+
+    /// The pixels of the view to render, alone, instead of the whole image.
+    crop: Option<Crop>,
+
+    /// Whether to move the view's center to a point on the set's boundary.
+    auto_center: bool,
+
+    /// The directory to write a deep-zoom pyramid of the image's tiles to.
+    pyramid: Option<String>,
+
+    /// How many of the pyramid's levels to write, from the full-size one
+    /// down, or `None` for all of them.
+    levels: Option<usize>,
+
+    /// The file to write the orbit of the view's center to, as CSV.
+    dump_orbit: Option<String>,
+
+    /// The number of random points to estimate the set's area from, instead
+    /// of rendering.
+    area: Option<usize>,
+
+    /// Whether to write a JSON file describing each image beside it.
+    metadata: bool,
+
+    /// Whether to render corners given the wrong way round, flipping the image.
+    allow_flipped: bool,
+
+    /// The number of rows after which to write the image so far, again and
+    /// again, while rendering.
+    checkpoint: Option<usize>,
+
+    /// Whether to fill the insides of rectangles with uniform borders rather
+    /// than evaluate them.
+    border_trace: bool,
+
+    /// Samples per pixel along each axis; 1 disables supersampling.
+    supersample: usize,
+
+    /// How to shrink supersampled pixels back down.
+    downscale: Downscale,
+
+    /// The most pixels to render, counting supersampling.
+    max_pixels: usize,
+
+    /// Randomly placed samples to average for each pixel; 1 places a single
+    /// sample at the corner of each pixel, as usual.
+    samples: usize,
+
+    /// The seed for placing `samples`.
+    seed: u64,
+
+    /// Radius of the circle an orbit must leave to count as escaping.
+    bailout: f64,
+
+    /// How to decide that an orbit has escaped.
+    escape: Escape,
+
+    /// How much one step must grow `|z|` by, with `Escape::Growth`.
+    growth_factor: f64,
+
+    /// Which fractal to iterate.
+    fractal: Fractal,
+
+    /// The floating-point type to iterate in.
+    precision: Precision,
+
+    /// Bits per channel of the image written.
+    bit_depth: BitDepth,
+
+    /// A named view to render, instead of giving its corners.
+    preset: Option<&'static Preset>,
+
+    /// The point to center the view on, instead of giving its corners.
+    center: Option<Complex<f64>>,
+
+    /// With `center`, the width of the view along the real axis.
+    zoom: Option<f64>,
+
+    /// With `center`, the height of the view along the imaginary axis.
+    scale: Option<f64>,
+
+    /// The number of frames to render zooming from the view into `target`.
+    frames: Option<usize>,
 
-            pixels[y * bounds.0 + x] = 
-                match escape_time(point, 255) {
-                    None => 0,
-                    Some(count) => 255 - count as u8
-                };
+    /// With `frames`, whether to keep the frames already written whole, and
+    /// only render the rest.
+    resume: bool,
+
+    /// With `frames`, the center of the last frame.
+    target: Option<Complex<f64>>,
+
+    /// With `frames`, the height of the last frame.
+    target_scale: Option<f64>
+}
+
+impl Default for Flags {
+
+    fn default() -> Flags {
+        Flags { limit: 255, auto_iterations: false, palette: Palette::Grayscale, gradient: None,
+                color: Coloring::Linear, color_scale: 10.0, palette_offset: 0, gamma: 1.0,
+                interior: Interior::Black, contour: None, julia: None, smooth: false, trap: None,
+                invert: false, threads: 0, tile_size: 0, quiet: false, stats: false, profile: None,
+                transparent_interior: false, dither: false, retries: 0, mask: None,
+                mask_fill: [0, 0, 0], max_period: DEFAULT_MAX_PERIOD, dry_run: false, ascii: false,
+                show_palette: None, scanline: None, antialias_edges: false, preview_scale: 1,
+                mirror: Mirror::None, crop: None, auto_center: false, pyramid: None, levels: None,
+                dump_orbit: None, area: None, metadata: false, allow_flipped: false, checkpoint: None,
+                border_trace: false, supersample: 1, downscale: Downscale::Box,
+                max_pixels: DEFAULT_MAX_PIXELS, samples: 1, seed: 0, bailout: 2.0,
+                escape: Escape::Radius, growth_factor: DEFAULT_GROWTH_FACTOR,
+                fractal: Fractal::Mandelbrot, precision: Precision::Double, bit_depth: BitDepth::Eight,
+                preset: None, center: None, zoom: None, scale: None, frames: None, resume: false,
+                target: None, target_scale: None }
+    }
+}
+
+/// Separate the `--name value` flags in `args`, the program name first, from
+/// the positional arguments, as `command` recognizes them.
+///
+/// Flags that aren't given keep their values from `defaults`, except that
+/// `--preset` supplies the limit unless `--limit` is also given. Return the
+/// program name and the positional arguments, in order, whether given in
+/// place or by name, along with the parsed flags. If a flag is
+/// unrecognized, is missing its value, or its value doesn't parse, return an
+/// error saying so. Given `--help`, print the help and exit.
+fn parse_flags(args: &[String], defaults: Flags) -> Result<(Vec<String>, Flags), MandelError> {
+
+    let program = args.first().map_or("mandelbrot", String::as_str);
+    let matches = command(program).try_get_matches_from(args).map_err(|err| {
+        if err.kind() == ErrorKind::DisplayHelp {
+            err.exit();
+        }
+        // A value that doesn't parse is reported in its parser's own words,
+        // and the usage clap follows other messages with is left for `main`
+        // to print.
+        if let (ErrorKind::ValueValidation, Some(source)) = (err.kind(), err.source()) {
+            return MandelError::BadFlag(source.to_string());
+        }
+        let message = err.to_string();
+        let first = message.lines().next().unwrap_or_default();
+        MandelError::BadFlag(first.trim_start_matches("error: ").to_string())
+    })?;
+
+    let mut positional = vec![program.to_string()];
+    for (name, flag, ..) in POSITIONALS {
+        match matches.get_one::<String>(name).or(matches.get_one::<String>(flag)) {
+            Some(arg) => positional.push(arg.clone()),
+            None => break
         }
     }
+    positional.extend(matches.get_many::<String>("more").into_iter().flatten().cloned());
+    // Corners may start with a hyphen, but never two.
+    if let Some(arg) = positional.iter().skip(1).find(|arg| arg.starts_with("--")) {
+        return Err(MandelError::BadFlag(format!("unexpected argument '{}' found", arg)));
+    }
+
+    let value = |name: &str| matches.get_one::<String>(name).map(String::as_str);
+    let given = |name: &str| matches.get_flag(name);
+
+    let mut flags = defaults;
+    let limit_given = matches.contains_id("limit");
+
+    flags.limit = parsed(&matches, "limit").unwrap_or(flags.limit);
+    flags.auto_iterations |= given("auto-iterations");
+    flags.palette = parsed(&matches, "palette").unwrap_or(flags.palette);
+    if let Some(path) = value("gradient") {
+        flags.gradient = Some(load_gradient(path)?);
+    }
+    flags.color = parsed(&matches, "color").unwrap_or(flags.color);
+    flags.color_scale = parsed(&matches, "color-scale").unwrap_or(flags.color_scale);
+    flags.gamma = parsed(&matches, "gamma").unwrap_or(flags.gamma);
+    flags.interior = parsed(&matches, "interior").unwrap_or(flags.interior);
+    flags.contour = parsed(&matches, "contour").or(flags.contour);
+    flags.palette_offset = parsed(&matches, "palette-offset").unwrap_or(flags.palette_offset);
+    flags.fractal = parsed(&matches, "fractal").unwrap_or(flags.fractal);
+    let power = parsed(&matches, "power").unwrap_or(Fractal::Mandelbrot);
+    flags.bit_depth = parsed(&matches, "output-bit-depth").unwrap_or(flags.bit_depth);
+    flags.precision = parsed(&matches, "precision").unwrap_or(flags.precision);
+    flags.preset = parsed(&matches, "preset").or(flags.preset);
+    flags.center = parsed(&matches, "center").or(flags.center);
+    flags.zoom = parsed(&matches, "zoom").or(flags.zoom);
+    flags.scale = parsed(&matches, "scale").or(flags.scale);
+    flags.frames = parsed(&matches, "frames").or(flags.frames);
+    flags.resume |= given("resume");
+    flags.target = parsed(&matches, "target").or(flags.target);
+    flags.target_scale = parsed(&matches, "target-scale").or(flags.target_scale);
+    flags.julia = parsed(&matches, "julia").or(flags.julia);
+    flags.smooth |= given("smooth");
+    flags.trap = parsed(&matches, "trap").or(flags.trap);
+    flags.invert |= given("invert");
+    flags.quiet |= given("quiet");
+    flags.stats |= given("stats");
+    flags.profile = value("profile").map(str::to_string).or(flags.profile);
+    flags.transparent_interior |= given("transparent-interior");
+    flags.dither |= given("dither");
+    flags.dry_run |= given("dry-run");
+    flags.ascii |= given("ascii");
+    flags.show_palette = parsed(&matches, "show-palette").or(flags.show_palette);
+    flags.scanline = parsed(&matches, "scanline").or(flags.scanline);
+    flags.antialias_edges |= given("antialias-edges-only");
+    flags.preview_scale = parsed(&matches, "preview-scale").unwrap_or(flags.preview_scale);
+    flags.auto_center |= given("auto-center");
+    flags.pyramid = value("pyramid").map(str::to_string).or(flags.pyramid);
+    flags.levels = parsed(&matches, "levels").or(flags.levels);
+    flags.crop = parsed(&matches, "crop").or(flags.crop);
+    flags.mirror = parsed(&matches, "mirror").unwrap_or(flags.mirror);
+    flags.dump_orbit = value("dump-orbit").map(str::to_string).or(flags.dump_orbit);
+    flags.area = parsed(&matches, "area").or(flags.area);
+    flags.metadata |= given("metadata");
+    flags.allow_flipped |= given("allow-flipped");
+    flags.border_trace |= given("border-trace");
+    flags.checkpoint = parsed(&matches, "checkpoint").or(flags.checkpoint);
+    flags.bailout = parsed(&matches, "bailout").unwrap_or(flags.bailout);
+    flags.escape = parsed(&matches, "escape").unwrap_or(flags.escape);
+    flags.growth_factor = parsed(&matches, "growth-factor").unwrap_or(flags.growth_factor);
+    flags.supersample = parsed(&matches, "supersample").unwrap_or(flags.supersample);
+    flags.downscale = parsed(&matches, "downscale").unwrap_or(flags.downscale);
+    flags.samples = parsed(&matches, "samples").unwrap_or(flags.samples);
+    flags.mask = value("mask").map(str::to_string).or(flags.mask);
+    flags.mask_fill = parsed(&matches, "mask-fill").unwrap_or(flags.mask_fill);
+    flags.max_period = parsed(&matches, "max-period").unwrap_or(flags.max_period);
+    flags.retries = parsed(&matches, "retry").unwrap_or(flags.retries);
+    flags.seed = parsed(&matches, "seed").unwrap_or(flags.seed);
+    flags.max_pixels = parsed(&matches, "max-pixels").unwrap_or(flags.max_pixels);
+    flags.threads = parsed(&matches, "threads").unwrap_or(flags.threads);
+    flags.tile_size = parsed(&matches, "tile-size").unwrap_or(flags.tile_size);
+
+    if let (Some(preset), false) = (flags.preset, limit_given) {
+        flags.limit = preset.limit;
+    }
+    if flags.area.is_some() && !limit_given {
+        flags.limit = AREA_LIMIT;
+    }
+    // Smooth coloring wants orbits followed far out for accurate fractions,
+    // but everything else is quicker with the radius-2 test, so only smooth
+    // coloring raises it, and the counts beside it escape at the same radius.
+    if !matches.contains_id("bailout") && (flags.smooth || flags.color == Coloring::Rainbow) {
+        flags.bailout = SMOOTH_BAILOUT;
+    }
+    if flags.auto_iterations && limit_given {
+        return Err(MandelError::BadFlag("--auto-iterations can't be used with --limit".to_string()));
+    }
+    if power != Fractal::Mandelbrot {
+        if flags.fractal != Fractal::Mandelbrot {
+            return Err(MandelError::BadFlag("--power only applies to the mandelbrot fractal".to_string()));
+        }
+        flags.fractal = power;
+    }
+
+    Ok((positional, flags))
 }
 
-fn write_image(filename: &str, pixels: &[u8], bounds : (usize, usize)) -> Result<(), std::io::Error> {
+/// Return the value of the option `name` in `matches`, as `value_parser`
+/// parsed it, if it was given.
+fn parsed<T: Clone + Send + Sync + 'static>(matches: &ArgMatches, name: &str) -> Option<T> {
 
-    let output = File::create(filename)?;
+    matches.get_one::<T>(name).cloned()
+}
+
+/// Return an error for each combination of `flags` that can't be rendered
+/// together.
+fn check_conflicts(flags: &Flags) -> Result<(), MandelError> {
 
-    let encoder = PNGEncoder::new(output);
+    let conflict = |message: &str| Err(MandelError::BadFlag(message.to_string()));
 
-    encoder.encode(pixels, bounds.0 as u32, bounds.1 as u32, ColorType::Gray(8))?;
+    if flags.smooth && flags.color == Coloring::Histogram {
+        return conflict("--smooth can't be used with --color histogram");
+    }
+    if flags.trap.is_some()
+        && (flags.smooth || matches!(flags.color, Coloring::Histogram | Coloring::Rainbow)) {
+        return conflict("--trap can't be used with --smooth, --color histogram or --color rainbow");
+    }
+    if flags.gradient.is_some() && flags.color == Coloring::Rainbow {
+        return conflict("--gradient can't be used with --color rainbow");
+    }
+    if flags.gamma != 1.0 && flags.bit_depth == BitDepth::Sixteen {
+        return conflict("--gamma can't be used with --output-bit-depth 16");
+    }
+    if flags.palette_offset != 0
+        && (flags.color == Coloring::Rainbow || flags.bit_depth == BitDepth::Sixteen) {
+        return conflict("--palette-offset can't be used with --color rainbow or --output-bit-depth 16");
+    }
+    if flags.interior != Interior::Black
+        && (flags.smooth || flags.trap.is_some() || flags.border_trace || flags.bit_depth == BitDepth::Sixteen
+            || matches!(flags.color, Coloring::Distance | Coloring::Rainbow)) {
+        return conflict("--interior only works with whole escape counts, not --smooth, --trap, \
+                         --border-trace, --output-bit-depth 16, --color distance or --color rainbow");
+    }
+    if flags.max_period != DEFAULT_MAX_PERIOD && flags.interior != Interior::Period {
+        return conflict("--max-period needs --interior period");
+    }
+    if flags.growth_factor != DEFAULT_GROWTH_FACTOR && flags.escape != Escape::Growth {
+        return conflict("--growth-factor needs --escape growth");
+    }
+    if flags.escape == Escape::Growth
+        && (flags.smooth || flags.trap.is_some() || flags.border_trace || flags.bailout != 2.0
+            || flags.interior != Interior::Black
+            || matches!(flags.color, Coloring::Distance | Coloring::Rainbow | Coloring::ExternalAngle)) {
+        return conflict("--escape growth only works with whole escape counts, not --smooth, --trap, \
+                         --border-trace, --bailout, --interior, --color distance, --color rainbow or \
+                         --color external-angle");
+    }
+    if flags.precision == Precision::DoubleDouble
+        && (flags.fractal != Fractal::Mandelbrot || flags.escape != Escape::Radius || flags.smooth
+            || flags.trap.is_some() || flags.interior != Interior::Black
+            || matches!(flags.color, Coloring::Distance | Coloring::Rainbow | Coloring::ExternalAngle)
+            || flags.checkpoint.is_some() || flags.border_trace || flags.mask.is_some() || flags.samples > 1
+            || flags.antialias_edges || flags.pyramid.is_some()) {
+        return conflict("--precision dd only works with whole escape counts of the Mandelbrot set, \
+                         not --fractal, --power, --escape growth, --smooth, --trap, --interior, \
+                         --color distance, rainbow or external-angle, --checkpoint, --border-trace, \
+                         --mask, --samples, --antialias-edges-only or --pyramid");
+    }
+    if flags.downscale != Downscale::Box && flags.supersample == 1 {
+        return conflict("--downscale lanczos needs --supersample of 2 or more");
+    }
+    if flags.contour.is_some()
+        && (flags.smooth || flags.trap.is_some() || flags.bit_depth == BitDepth::Sixteen
+            || matches!(flags.color, Coloring::Distance | Coloring::Rainbow)) {
+        return conflict("--contour needs whole escape counts, so can't be used with --smooth, --trap, \
+                         --output-bit-depth 16, --color distance or --color rainbow");
+    }
+    if flags.color == Coloring::Distance
+        && (flags.smooth || flags.trap.is_some() || flags.fractal != Fractal::Mandelbrot) {
+        return conflict("--color distance can't be used with --smooth, --trap or other fractals");
+    }
+    if flags.color == Coloring::ExternalAngle
+        && (flags.smooth || flags.trap.is_some() || flags.border_trace || flags.antialias_edges
+            || flags.gradient.is_some() || flags.palette_offset != 0 || flags.contour.is_some()
+            || flags.interior != Interior::Black) {
+        return conflict("--color external-angle can't be used with --smooth, --trap, --border-trace, \
+                         --antialias-edges-only, --gradient, --palette-offset, --contour or --interior");
+    }
+    if flags.border_trace
+        && (flags.smooth || flags.trap.is_some() || flags.samples > 1
+            || matches!(flags.color, Coloring::Distance | Coloring::Rainbow)) {
+        return conflict("--border-trace only works with whole escape counts, not --smooth, --trap, \
+                         --samples, --color distance or --color rainbow");
+    }
+    if flags.preview_scale > 1 && (flags.checkpoint.is_some() || flags.bit_depth == BitDepth::Sixteen) {
+        return conflict("--preview-scale can't be used with --checkpoint or --output-bit-depth 16");
+    }
+    if flags.checkpoint.is_some() && (flags.samples > 1 || flags.border_trace) {
+        return conflict("--checkpoint can't be used with --samples or --border-trace");
+    }
+    if flags.dump_orbit.is_some() && (flags.julia.is_some() || flags.fractal != Fractal::Mandelbrot) {
+        return conflict("--dump-orbit only works for the mandelbrot fractal, without --julia");
+    }
+    if flags.antialias_edges
+        && (flags.supersample > 1 || flags.samples > 1 || flags.checkpoint.is_some() || flags.border_trace
+            || flags.stats || flags.trap.is_some()
+            || matches!(flags.color, Coloring::Histogram | Coloring::Normalize | Coloring::Distance)) {
+        return conflict("--antialias-edges-only can't be used with --supersample, --samples, --checkpoint, \
+                         --border-trace, --stats, --trap or --color histogram, normalize or distance");
+    }
+    if flags.stats && (flags.trap.is_some() || flags.color == Coloring::Distance) {
+        return conflict("--stats can't be used with --trap or --color distance");
+    }
+    if flags.profile.is_some()
+        && (flags.frames.is_some() || flags.antialias_edges || flags.pyramid.is_some() || flags.trap.is_some()
+            || flags.color == Coloring::Distance) {
+        return conflict("--profile can't be used with --frames, --antialias-edges-only, --pyramid, --trap \
+                         or --color distance");
+    }
+    if flags.bit_depth == BitDepth::Sixteen
+        && (flags.palette != Palette::Grayscale || flags.gradient.is_some()
+            || !matches!(flags.color, Coloring::Linear | Coloring::Distance)) {
+        return conflict("--output-bit-depth 16 only supports the grayscale palette, \
+                         with linear or distance coloring");
+    }
+    if flags.transparent_interior
+        && (flags.bit_depth == BitDepth::Sixteen || flags.antialias_edges || flags.pyramid.is_some()
+            || flags.ascii) {
+        return conflict("--transparent-interior can't be used with --output-bit-depth 16, \
+                         --antialias-edges-only, --pyramid or --ascii");
+    }
+    if flags.dither
+        && (flags.bit_depth == BitDepth::Sixteen || flags.trap.is_some() || flags.antialias_edges
+            || !matches!(flags.color, Coloring::Linear | Coloring::Log)) {
+        return conflict("--dither only works at 8 bits, with linear or log coloring, and without --trap \
+                         or --antialias-edges-only");
+    }
+    if flags.mask.is_some()
+        && (flags.supersample > 1 || flags.samples > 1 || flags.preview_scale > 1
+            || flags.checkpoint.is_some() || flags.border_trace || flags.antialias_edges
+            || flags.mirror != Mirror::None || flags.pyramid.is_some()
+            || flags.bit_depth == BitDepth::Sixteen) {
+        return conflict("--mask can't be used with --supersample, --samples, --preview-scale, \
+                         --checkpoint, --border-trace, --antialias-edges-only, --mirror, --pyramid or \
+                         --output-bit-depth 16");
+    }
+    if flags.levels.is_some() && flags.pyramid.is_none() {
+        return conflict("--levels only works with --pyramid");
+    }
+    if flags.pyramid.is_some()
+        && (flags.frames.is_some() || flags.supersample > 1 || flags.samples > 1 || flags.checkpoint.is_some()
+            || flags.antialias_edges || flags.border_trace || flags.preview_scale > 1
+            || flags.mirror != Mirror::None || flags.bit_depth == BitDepth::Sixteen || flags.stats
+            || flags.metadata || matches!(flags.color, Coloring::Histogram | Coloring::Normalize)) {
+        // Each tile is colored on its own, so coloring that looks at the
+        // whole image can't be used either.
+        return conflict("--pyramid can't be used with --frames, --supersample, --samples, --checkpoint, \
+                         --antialias-edges-only, --border-trace, --preview-scale, --mirror, \
+                         --output-bit-depth 16, --stats, --metadata or --color histogram or normalize");
+    }
 
     Ok(())
 }
 
+/// Parse the value of `flag` as a positive, finite length on the complex plane.
+fn parse_extent(value: &str, flag: &str) -> Result<f64, MandelError> {
+
+    match value.parse::<f64>() {
+        Ok(extent) if extent.is_finite() && extent > 0.0 => Ok(extent),
+        _ => Err(MandelError::BadFlag(format!("{} must be a positive number", flag)))
+    }
+}
+
+/// Return `args` as the owned strings `run` and `parse_flags` take.
+#[cfg(test)]
+fn args(args: &[&str]) -> Vec<String> {
+
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+/// The path of a file or directory a test writes in the temporary
+/// directory, which is removed when this is dropped, whether the test
+/// passed or not.
+#[cfg(test)]
+struct TempPath(String);
+
+#[cfg(test)]
+impl TempPath {
+
+    /// Return a path for `name` that no other test, or other run of the
+    /// tests at the same time, uses.
+    fn new(name: &str) -> TempPath {
+
+        let path = env::temp_dir().join(format!("mandelbrot-{}-{}", std::process::id(), name));
+        TempPath(path.to_str().unwrap().to_string())
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempPath {
+
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TempPath {
+
+    fn as_ref(&self) -> &Path {
+        Path::new(&self.0)
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempPath {
+
+    fn drop(&mut self) {
+
+        // A test that failed early may not have written anything here.
+        let path = Path::new(&self.0);
+        let _ = if path.is_dir() { std::fs::remove_dir_all(path) } else { std::fs::remove_file(path) };
+    }
+}
+
+#[test]
+fn test_parse_flags() {
+
+    let given = args(&["prog", "out.png", "--limit", "1000", "800x600", "-1,1", "1,-1", "--palette",
+                       "ocean", "--color", "histogram", "--color-scale", "2.5", "--palette-offset",
+                       "40", "--gamma", "2.2", "--interior", "angle", "--contour", "25", "--julia",
+                       "-0.8,0.156", "--threads", "3", "--tile-size", "16", "--trap", "cross",
+                       "--invert", "--quiet", "--stats", "--profile", "rows.csv",
+                       "--transparent-interior", "--dither", "--retry", "2", "--mask", "mask.png",
+                       "--mask-fill", "255,128,0", "--max-period", "12", "--verbose", "--dry-run",
+                       "--ascii", "--show-palette", "fire", "--scanline", "12",
+                       "--antialias-edges-only", "--preview-scale", "3", "--mirror", "vertical",
+                       "--crop", "1,2,3,4", "--auto-center", "--pyramid", "tiles", "--levels", "3",
+                       "--dump-orbit", "orbit.csv", "--area", "500", "--metadata", "--allow-flipped",
+                       "--checkpoint", "4", "--border-trace", "--supersample", "2", "--downscale",
+                       "lanczos", "--max-pixels", "5000000", "--samples", "8", "--seed", "99",
+                       "--bailout", "16", "--escape", "growth", "--growth-factor", "1.5", "--fractal",
+                       "tricorn", "--precision", "f32", "--output-bit-depth", "16", "--frames", "10",
+                       "--resume"]);
+    let (positional, flags) = parse_flags(&given, Flags::default()).unwrap();
+    assert_eq!(positional, ["prog", "out.png", "800x600", "-1,1", "1,-1"]);
+    assert_eq!(flags, Flags { limit: 1000, auto_iterations: false, palette: Palette::Ocean,
+                              gradient: None, color: Coloring::Histogram, color_scale: 2.5,
+                              palette_offset: 40, gamma: 2.2, interior: Interior::Angle,
+                              contour: Some(25), julia: Some(Complex { re: -0.8, im: 0.156 }),
+                              smooth: false, trap: Some(Trap::Cross), invert: true, threads: 3,
+                              tile_size: 16, quiet: true, stats: true,
+                              profile: Some("rows.csv".to_string()), transparent_interior: true,
+                              dither: true, retries: 2, mask: Some("mask.png".to_string()),
+                              mask_fill: [255, 128, 0], max_period: 12, dry_run: true, ascii: true,
+                              show_palette: Some(Palette::Fire), scanline: Some(12),
+                              antialias_edges: true, preview_scale: 3, mirror: Mirror::Vertical,
+                              crop: Some(Crop { origin: (1, 2), size: (3, 4) }), auto_center: true,
+                              pyramid: Some("tiles".to_string()), levels: Some(3),
+                              dump_orbit: Some("orbit.csv".to_string()), area: Some(500),
+                              metadata: true, allow_flipped: true, checkpoint: Some(4),
+                              border_trace: true, supersample: 2, downscale: Downscale::Lanczos,
+                              max_pixels: 5000000, samples: 8, seed: 99, bailout: 16.0,
+                              escape: Escape::Growth, growth_factor: 1.5, fractal: Fractal::Tricorn,
+                              precision: Precision::Single, bit_depth: BitDepth::Sixteen, preset: None,
+                              center: None, zoom: None, scale: None, frames: Some(10), resume: true,
+                              target: None, target_scale: None });
+
+    assert_eq!(parse_flags(&args(&["prog", "out.png"]), Flags::default()).unwrap().1, Flags::default());
+
+    let power = |given: &[&str]| {
+        parse_flags(&args(&[&["prog"], given].concat()), Flags::default()).unwrap().1.fractal
+    };
+    assert_eq!(power(&["--power", "3"]), Fractal::Multibrot(3));
+    assert_eq!(power(&["--power", "2", "--fractal", "mandelbrot"]), Fractal::Mandelbrot);
+    assert_eq!(power(&["--power", "2.5"]), Fractal::FractionalMultibrot(2.5));
+    assert_eq!(power(&["--power", "2.0"]), Fractal::FractionalMultibrot(2.0));
+    assert_eq!(power(&["--power", "-2"]), Fractal::FractionalMultibrot(-2.0));
+
+    assert!(parse_flags(&args(&["prog", "--auto-iterations"]), Flags::default()).unwrap().1.auto_iterations);
+
+    let limit = |given: &[&str]| {
+        parse_flags(&args(&[&["prog"], given].concat()), Flags::default()).unwrap().1.limit
+    };
+    assert_eq!(limit(&["--area", "100"]), AREA_LIMIT);
+    assert_eq!(limit(&["--area", "100", "--limit", "50"]), 50);
+    assert_eq!(limit(&[]), Flags::default().limit);
+
+    let bailout = |given: &[&str]| {
+        parse_flags(&args(&[&["prog"], given].concat()), Flags::default()).unwrap().1.bailout
+    };
+    assert_eq!(bailout(&["--smooth"]), SMOOTH_BAILOUT);
+    assert_eq!(bailout(&["--color", "rainbow"]), SMOOTH_BAILOUT);
+    assert_eq!(bailout(&["--smooth", "--bailout", "4"]), 4.0);
+    assert_eq!(bailout(&["--palette", "grayscale"]), 2.0);
+    assert_eq!(bailout(&["--color", "log"]), 2.0);
+
+    for bad in [&["--limit"][..], &["--limit", "lots"], &["--limit", "0"], &["--bogus", "1"],
+                &["--palette", "mauve"], &["--gradient", "/nonexistent/stops.txt"],
+                &["--color", "sqrt"], &["--color-scale", "0"], &["--color-scale", "NaN"],
+                &["--trap", "circle"], &["--julia", "0.3"], &["--threads", "-1"], &["--tile-size", "x"],
+                &["--supersample", "0"], &["--downscale", "bicubic"], &["--samples", "0"],
+                &["--max-pixels", "0"], &["--area", "0"], &["--checkpoint", "0"], &["--seed", "-1"],
+                &["--retry", "-1"], &["--mask-fill", "1,2"], &["--max-period", "0"],
+                &["--mask-fill", "1,2,256"], &["--bailout", "1.5"], &["--bailout", "NaN"],
+                &["--escape", "stall"], &["--growth-factor", "1"], &["--growth-factor", "inf"],
+                &["--fractal", "newton"], &["--precision", "f16"], &["--output-bit-depth", "12"],
+                &["--zoom", "0"], &["--center", "1"], &["--scale", "-1"], &["--scale", "inf"],
+                &["--frames", "0"], &["--target-scale", "0"], &["--power", "1"], &["--power", "x"],
+                &["--power", "0.5"], &["--power", "inf"], &["--palette-offset", "-1"],
+                &["--gamma", "0"], &["--gamma", "inf"], &["--interior", "white"],
+                &["--mirror", "diagonal"], &["--crop", "1,2,0,4"], &["--levels", "0"],
+                &["--contour", "0"], &["--auto-iterations", "--limit", "9"], &["--scanline", "-1"],
+                &["--preview-scale", "0"], &["--show-palette", "plaid"],
+                &["--fractal", "tricorn", "--power", "3"]] {
+        assert!(parse_flags(&args(&[&["prog"], bad].concat()), Flags::default()).is_err(),
+                "{:?} should be rejected", bad);
+    }
+}
+
+#[test]
+fn test_command() {
+
+    use clap::error::ErrorKind;
+
+    command("prog").debug_assert();
+
+    let matches = |args: &[&str]| command("prog").try_get_matches_from(args);
+    let value = |args: &[&str], name: &str| matches(args).unwrap().get_one::<String>(name).cloned();
+
+    // The original five arguments still work, corners with their hyphens,
+    // and so do the same values by name.
+    let given = ["prog", "out.png", "--limit", "9", "80x60", "-1,1", "--smooth", "1,-1"];
+    assert_eq!(value(&given, "UPPERLEFT").as_deref(), Some("-1,1"));
+    assert_eq!(value(&given, "LOWERRIGHT").as_deref(), Some("1,-1"));
+    assert_eq!(matches(&given).unwrap().get_one::<usize>("limit"), Some(&9));
+    assert!(matches(&given).unwrap().get_flag("smooth"));
+    let named = ["prog", "--output", "out.png", "--pixels", "80x60", "--upper-left", "-1,1",
+                 "--lower-right", "1,-1", "--limit", "9", "--smooth"];
+    assert_eq!(value(&named, "upper-left").as_deref(), Some("-1,1"));
+    assert_eq!(parse_flags(&args(&named), Flags::default()).unwrap(),
+               parse_flags(&args(&given), Flags::default()).unwrap());
+    assert_eq!(parse_flags(&args(&["prog", "-", "80x60"]), Flags::default()).unwrap().0,
+               ["prog", "-", "80x60"]);
+
+    // The last of a repeated option wins, and negative values are values.
+    let power = matches(&["prog", "--power", "3", "--power", "-1.5"]).unwrap();
+    assert_eq!(power.get_one::<Fractal>("power"), Some(&Fractal::FractionalMultibrot(-1.5)));
+
+    for (args, kind) in [(&["prog", "--bogus"][..], ErrorKind::UnknownArgument),
+                         (&["prog", "out.png", "--limit"], ErrorKind::InvalidValue),
+                         (&["prog", "out.png", "--limit", "0"], ErrorKind::ValueValidation),
+                         (&["prog", "out.png", "--pixels", "80x60"], ErrorKind::ArgumentConflict),
+                         (&["prog", "--output", "out.png", "--upper-left", "0,0"],
+                          ErrorKind::MissingRequiredArgument)] {
+        assert_eq!(matches(args).unwrap_err().kind(), kind, "{:?}", args);
+    }
+    assert!(matches!(parse_flags(&args(&["prog", "out.png", "--bogus"]), Flags::default()),
+                     Err(MandelError::BadFlag(message)) if message == "unexpected argument '--bogus' found"));
+    assert!(matches!(parse_flags(&args(&["prog", "a", "b", "c", "--bogus"]), Flags::default()),
+                     Err(MandelError::BadFlag(message)) if message == "unexpected argument '--bogus' found"));
+    // A value that doesn't parse is reported as its parser put it.
+    assert!(matches!(parse_flags(&args(&["prog", "--limit", "0"]), Flags::default()),
+                     Err(MandelError::BadFlag(message)) if message == "--limit must be a positive integer"));
+
+    // The help lists every option, and the ways to run the program.
+    let help = command("prog").render_help().to_string();
+    assert!(help.starts_with("Usage: prog FILE PIXELS UPPERLEFT LOWERRIGHT [OPTIONS]\n"));
+    for (name, ..) in OPTIONS {
+        assert!(help.contains(&format!("--{}", name)), "--{} isn't in the help", name);
+    }
+    assert!(help.contains("prog verify FILE PIXELS"));
+}
+
+#[test]
+fn test_run_errors() {
+
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10"])),
+                     Err(MandelError::WrongArgCount { expected: 4, got: 2 })));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--center", "0,0",
+                                 "--zoom", "2"])),
+                     Err(MandelError::WrongArgCount { expected: 2, got: 4 })));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "--center", "0,0"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "--center", "0,0", "--zoom", "1",
+                                 "--scale", "1"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--smooth",
+                                 "--color", "histogram"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--smooth",
+                                 "--trap", "point"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--palette", "fire",
+                                 "--output-bit-depth", "16"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--color", "rainbow",
+                                 "--trap", "point"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--color", "distance",
+                                 "--fractal", "tricorn"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--stats", "--trap",
+                                 "cross"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--dither", "--color",
+                                 "histogram"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.ppm", "10x10", "-1,1", "1,-1", "--transparent-interior"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--profile", "rows.csv",
+                                 "--pyramid", "tiles"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--border-trace",
+                                 "--smooth"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--downscale",
+                                 "lanczos"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--color", "external-angle",
+                                 "--smooth"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--max-period", "8",
+                                 "--interior", "angle"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--precision", "dd",
+                                 "--fractal", "tricorn"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--growth-factor", "3"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--escape", "growth",
+                                 "--smooth"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--crop", "5,5,6,5"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "3,3", "4,2", "--auto-center"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "--area", "100"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(run(&args(&["prog", "--area", "100", "--limit", "50"])).is_ok());
+    assert!(matches!(run(&args(&["prog", "stitch", "out.png", "2x2"])), Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "stitch", "out.png", "0x2", "a.png"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "stitch", "out.png", "2x1", "a.png"])),
+                     Err(MandelError::BadTiles(_))));
+    assert!(matches!(run(&args(&["prog", "compare", "a.png"])), Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "compare", "a.png", "b.png", "--tolerance", "-1"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "compare", "/nonexistent/a.png", "b.png"])),
+                     Err(MandelError::BadComparison(_))));
+    assert!(matches!(run(&args(&["prog", "verify", "a.png"])), Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "verify", "a.png", "10by10"])),
+                     Err(MandelError::BadDimensions(_))));
+    assert!(matches!(run(&args(&["prog", "verify", "/nonexistent/a.png", "10x10"])),
+                     Err(MandelError::FailedVerification(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "1,1", "-1,-1"])),
+                     Err(MandelError::FlippedCorners { part: "real", .. })));
+    assert!(matches!(run(&args(&["prog", "out.png", "10by10", "-1,1", "1,-1"])),
+                     Err(MandelError::BadDimensions(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "0x0", "-1,1", "1,-1"])),
+                     Err(MandelError::BadDimensions(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "100000x100000", "-1,1", "1,-1"])),
+                     Err(MandelError::TooManyPixels { .. })));
+    assert!(matches!(run(&args(&["prog", "out.png", "100x100", "-1,1", "1,-1", "--supersample", "4",
+                                 "--max-pixels", "10000"])),
+                     Err(MandelError::TooManyPixels { supersample: 4, .. })));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1"])),
+                     Err(MandelError::BadComplex { argument: "lower-right corner", .. })));
+    assert!(matches!(run(&args(&["prog", "out.gif", "10x10", "-1,1", "1,-1"])),
+                     Err(MandelError::UnknownFormat(_))));
+    assert!(matches!(run(&args(&["prog", "/nonexistent/dir/out.png", "10x10", "-1,1", "1,-1",
+                                     "--quiet", "--supersample", "2", "--bailout", "16",
+                                     "--fractal", "tricorn"])),
+                     Err(MandelError::Io(_))));
+}
+
+#[test]
+fn test_config_matches_command_line() {
+
+    let config = Config::parse(r#"
+        filename = "scene.png"
+        pixels = "400x300"
+        upper_left = "-1.20,0.35"
+        lower_right = "-1,0.2"
+        limit = 1000
+        fractal = "tricorn"
+    "#).unwrap();
+
+    let cli = args(&["prog", "scene.png", "400x300", "-1.20,0.35", "-1,0.2", "--limit", "1000",
+                     "--fractal", "tricorn"]);
+    let (cli, cli_flags) = parse_flags(&cli, Flags::default()).unwrap();
+
+    // Both in full from the scene, and with the file and pixels given on the
+    // command line instead.
+    let defaults = || config_flags(&config, "scene.toml").unwrap();
+    for given in [&["prog"][..], &["prog", "other.png", "400x300"]] {
+        let (positional, flags) = parse_flags(&args(given), defaults()).unwrap();
+        assert_eq!(flags, cli_flags);
+        let positional = fill_positionals(positional, &config, |_| None, 4).unwrap();
+        assert_eq!(resolve_view(&positional, &flags).unwrap(), resolve_view(&cli, &cli_flags).unwrap());
+    }
+
+    // Flags on the command line override the scene.
+    let (_, flags) = parse_flags(&args(&["prog", "--limit", "50"]), defaults()).unwrap();
+    assert_eq!((flags.limit, flags.fractal), (50, Fractal::Tricorn));
+
+    assert!(matches!(fill_positionals(args(&["prog"]), &Config::default(), |_| None, 4),
+                     Err(MandelError::WrongArgCount { expected: 4, got: 0 })));
+    assert!(matches!(config_flags(&Config { palette: Some("mauve".to_string()), ..Config::default() },
+                                  "scene.toml"),
+                     Err(MandelError::BadConfig { .. })));
+    assert_eq!(config_path(&args(&["prog", "--config", "scene.toml"])).unwrap(), Some("scene.toml"));
+    assert!(config_path(&args(&["prog", "--config"])).is_err());
+}
+
+#[test]
+fn test_preset() {
+
+    let (positional, flags) = parse_flags(&args(&["prog", "out.png", "350x300", "--preset", "full"]),
+                                           Flags::default()).unwrap();
+    assert_eq!(resolve_view(&positional, &flags).unwrap(),
+               ((350, 300), Complex { re: -2.5, im: 1.5 }, Complex { re: 1.0, im: -1.5 }));
+
+    // The preset's limit applies unless --limit is given, before or after it.
+    let (_, flags) = parse_flags(&args(&["prog", "--preset", "seahorse"]), Flags::default()).unwrap();
+    assert_eq!(flags.limit, 1000);
+    let (_, flags) = parse_flags(&args(&["prog", "--limit", "50", "--preset", "seahorse"]),
+                                 Flags::default()).unwrap();
+    assert_eq!(flags.limit, 50);
+
+    assert!(matches!(parse_flags(&args(&["prog", "--preset", "cauliflower"]), Flags::default()),
+                     Err(MandelError::BadFlag(ref message)) if message.contains("triple-spiral")));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "-1,1", "1,-1", "--preset", "full"])),
+                     Err(MandelError::WrongArgCount { expected: 2, got: 4 })));
+    assert!(matches!(run(&args(&["prog", "out.png", "10x10", "--preset", "full", "--center", "0,0",
+                                 "--zoom", "1"])),
+                     Err(MandelError::BadFlag(_))));
+}
+
+#[test]
+fn test_positionals_from_environment() {
+
+    let var = |name: &str| match name {
+        "MANDEL_BOUNDS" => Some("400x300".to_string()),
+        "MANDEL_UL" => Some("-1.20,0.35".to_string()),
+        "MANDEL_LR" => Some("-1,0.2".to_string()),
+        _ => None
+    };
+
+    let cli = args(&["prog", "out.png", "400x300", "-1.20,0.35", "-1,0.2"]);
+    let flags = Flags::default();
+    let from_env = fill_positionals(args(&["prog", "out.png"]), &Config::default(), var, 4).unwrap();
+    assert_eq!(from_env, cli);
+    assert_eq!(resolve_view(&from_env, &flags).unwrap(), resolve_view(&cli, &flags).unwrap());
+
+    // Arguments on the command line, then the scene, come before the
+    // environment.
+    let config = Config { upper_left: Some("-2,1".to_string()), ..Config::default() };
+    let filled = fill_positionals(args(&["prog", "out.png", "80x60"]), &config, var, 4).unwrap();
+    assert_eq!(filled, ["prog", "out.png", "80x60", "-2,1", "-1,0.2"]);
+
+    // The file has no variable.
+    assert!(matches!(fill_positionals(args(&["prog"]), &Config::default(), var, 4),
+                     Err(MandelError::WrongArgCount { expected: 4, got: 0 })));
+}
+
+#[test]
+fn test_dry_run() {
+
+    // Nothing is rendered or written, even to a file that couldn't be.
+    let given = args(&["prog", "/nonexistent/dir/out.png", "400x300", "-2,1.5", "2,-1.5", "--dry-run"]);
+    assert!(run(&given).is_ok());
+
+    let (positional, flags) = parse_flags(&given, Flags::default()).unwrap();
+    let (bounds, upper_left, lower_right) = resolve_view(&positional, &flags).unwrap();
+    let report = dry_run_report(bounds, upper_left, lower_right, &flags);
+    let step = (lower_right.re - upper_left.re) / bounds.0 as f64;
+    assert_eq!(step, 0.01);
+    assert!(report.contains(&format!("pixel step:  {} across, {} down", step, -0.01)), "{}", report);
+    assert!(report.contains(&format!("{} bytes of image", 400 * 300 * 3)), "{}", report);
+}
+
+#[test]
+fn test_metadata_sidecar() {
+
+    use mandelbrot::metadata_filename;
+
+
+    let image = TempPath::new("meta.png");
+    let sidecar = TempPath(metadata_filename(&image));
+    run(&args(&["prog", &image, "40x30", "-1.20,0.35", "-1,0.2", "--limit", "100", "--palette", "ocean",
+                "--metadata", "--quiet"])).unwrap();
+
+    let json = std::fs::read_to_string(&sidecar).unwrap();
+    let meta = RenderMeta::from_json(&json).unwrap();
+
+    assert_eq!((meta.bounds, meta.upper_left, meta.lower_right),
+               ((40, 30), parse_point("-1.20,0.35", "").unwrap(), parse_point("-1,0.2", "").unwrap()));
+    assert_eq!((meta.limit, meta.palette, meta.fractal), (100, Palette::Ocean, Fractal::Mandelbrot));
+    assert_eq!(meta.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(RenderMeta::from_json(&meta.to_json()).unwrap(), meta);
+
+    assert!(matches!(run(&args(&["prog", "-", "40x30", "-1,1", "1,-1", "--metadata"])),
+                     Err(MandelError::BadFlag(_))));
+}
+
+#[test]
+fn test_resume() {
+
+    use mandelbrot::encode_png;
+
+
+    let dir = TempPath::new("resume");
+    std::fs::create_dir_all(&dir).unwrap();
+    let pattern = Path::new(&*dir).join("zoom.png");
+    let pattern = pattern.to_str().unwrap();
+    let frame = |n| numbered_filename(pattern, n);
+
+    // Frame 1 is already done, frame 2 was cut off mid-write, and frame 3
+    // was never started. A white image stands in for frame 1, so it's plain
+    // whether it was rendered over.
+    let white = encode_png(&[255; 20 * 15], (20, 15), BitDepth::Eight).unwrap();
+    std::fs::write(frame(1), &white).unwrap();
+    std::fs::write(frame(2), &white[..white.len() / 2]).unwrap();
+
+    run(&args(&["prog", pattern, "20x15", "-2,1.5", "2,-1.5", "--frames", "3", "--target", "-0.75,0.1",
+                "--target-scale", "0.5", "--resume", "--quiet"])).unwrap();
+
+    let written : Vec<_> = (1..=3).map(|n| std::fs::read(frame(n)).unwrap()).collect();
+
+    assert_eq!(written[0], white);
+    assert!(written[1].len() > white.len() / 2 && written[1] != white);
+    assert!(!written[2].is_empty());
+
+    assert!(matches!(run(&args(&["prog", pattern, "20x15", "-2,1.5", "2,-1.5", "--resume"])),
+                     Err(MandelError::BadFlag(_))));
+}
+
+#[test]
+fn test_scanline() {
+
+    use mandelbrot::{escape_time, pixel_to_point};
+
+    let (bounds, y) = ((30, 20), 9);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let iteration = Iteration { limit: 50, ..Iteration::default() };
+
+    let counts = render_scanline(bounds, y, upper_left, lower_right, iteration);
+    let expected: Vec<String> = (0..bounds.0)
+        .map(|x| {
+            let point = pixel_to_point(bounds, (x, y), upper_left, lower_right);
+            let count = escape_time(Complex { re: 0.0, im: 0.0 }, point, 50, 2.0);
+            count.map_or("-".to_string(), |n| n.to_string())
+        })
+        .collect();
+    assert_eq!(scanline_text(&counts), expected.join(","));
+    assert!(scanline_text(&counts).contains("-,"));
+    assert_eq!(scanline_text(&[Some(3), None, Some(0)]), "3,-,0");
+
+    assert!(run(&args(&["prog", "out.png", "30x20", "-2,1", "1,-1", "--scanline", "19"])).is_ok());
+    assert!(matches!(run(&args(&["prog", "out.png", "30x20", "-2,1", "1,-1", "--scanline", "20"])),
+                     Err(MandelError::BadFlag(_))));
+}
+
+#[test]
+fn test_preview_scale() {
+
+    let (plain, unscaled, preview) =
+        (TempPath::new("full-size.png"), TempPath::new("unscaled.png"), TempPath::new("preview.png"));
+    let view = ["41x30", "-2,1.2", "1,-1.2", "--palette", "ocean", "--quiet"];
+    run(&args(&[&["prog", &plain][..], &view].concat())).unwrap();
+    run(&args(&[&["prog", &unscaled][..], &view, &["--preview-scale", "1"]].concat())).unwrap();
+    run(&args(&[&["prog", &preview][..], &view, &["--preview-scale", "4"]].concat())).unwrap();
+
+    assert!(is_complete_png(&preview, (41, 30)));
+    let written = [&plain, &unscaled, &preview].map(|path| std::fs::read(path).unwrap());
+    assert_eq!(written[0], written[1]);
+    assert_ne!(written[0], written[2]);
+
+    let checkpointed = ["--preview-scale", "2", "--checkpoint", "5"];
+    assert!(matches!(run(&args(&[&["prog", "out.png"][..], &view, &checkpointed].concat())),
+                     Err(MandelError::BadFlag(_))));
+}
+
+#[test]
+fn test_show_palette() {
+
+    let path = TempPath::new("strip.png");
+
+    run(&args(&["prog", &path, "--show-palette", "ocean"])).unwrap();
+    assert!(is_complete_png(&path, PALETTE_STRIP_BOUNDS));
+
+    assert!(matches!(run(&args(&["prog", &path, "10x10", "--show-palette", "ocean"])),
+                     Err(MandelError::BadFlag(_))));
+}
+
+#[test]
+fn test_logging() {
+
+    use logging::set_log_writer;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    // Messages are gathered here instead of going to stderr.
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+
+    assert_eq!(log_level(None, 0), Ok(Some(DEFAULT_LOG_LEVEL)));
+    assert_eq!(log_level(None, 1), Ok(Some(Level::Info)));
+    assert_eq!(log_level(None, 5), Ok(Some(Level::Trace)));
+    assert_eq!(log_level(Some("debug"), 1), Ok(Some(Level::Debug)));
+    assert_eq!(log_level(Some("off"), 0), Ok(None));
+    assert_eq!(log_level(Some("off"), 2), Ok(Some(Level::Debug)));
+    assert_eq!(log_level(Some("rayon=trace"), 0), Ok(Some(DEFAULT_LOG_LEVEL)));
+    assert!(log_level(Some("loud"), 0).is_err());
+
+    // Only this test changes the level, so the others never see it change.
+    let (plain, verbose) = (TempPath::new("terse.png"), TempPath::new("verbose.png"));
+    let view = ["40x30", "-2,1.2", "1,-1.2", "--supersample", "2", "--palette", "fire", "--quiet"];
+    run(&args(&[&["prog", &plain][..], &view].concat())).unwrap();
+    let capture = Capture::default();
+    let previous = set_log_writer(Some(Box::new(capture.clone())));
+    set_max_level(Some(Level::Debug));
+    let result = run(&args(&[&["prog", &verbose][..], &view, &["-vv"]].concat()));
+    set_max_level(Some(DEFAULT_LOG_LEVEL));
+    set_log_writer(previous);
+    result.unwrap();
+
+    let written = [std::fs::read(&plain).unwrap(), std::fs::read(&verbose).unwrap()];
+    assert_eq!(written[0], written[1]);
+
+    let logged = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+    assert!(logged.contains("info: parsed arguments in "), "{}", logged);
+    assert!(logged.contains(&format!("debug: {}: rendering 80x60 pixels from ", &*verbose)), "{}", logged);
+    assert!(logged.contains(&format!("info: {}: allocated in ", &*verbose)), "{}", logged);
+    assert!(logged.contains(" rendered in "), "{}", logged);
+}
 
 #[test]
-fn test_lerp() {
+fn test_smooth_bailout() {
+
+    // Smooth coloring escapes at `SMOOTH_BAILOUT` unless told otherwise, and
+    // any other --bailout, nearer or further, moves the fractions with it.
+    let view = ["40x30", "-2,1.2", "1,-1.2", "--smooth", "--palette", "fire", "--quiet"];
+    let (plain, raised, near, far) = (TempPath::new("smooth.png"), TempPath::new("smooth-256.png"),
+                                      TempPath::new("smooth-near.png"), TempPath::new("smooth-far.png"));
+    run(&args(&[&["prog", &plain][..], &view].concat())).unwrap();
+    run(&args(&[&["prog", &raised][..], &view, &["--bailout", "256"]].concat())).unwrap();
+    run(&args(&[&["prog", &near][..], &view, &["--bailout", "4"]].concat())).unwrap();
+    run(&args(&[&["prog", &far][..], &view, &["--bailout", "1e6"]].concat())).unwrap();
+
+    let written = [&plain, &raised, &near, &far].map(|path| std::fs::read(path).unwrap());
+    assert_eq!(written[0], written[1]);
+    assert!(written[0] != written[2] && written[0] != written[3]);
+}
+
+#[test]
+fn test_profile() {
+
+    use mandelbrot::render;
+
+    let (image, csv) = (TempPath::new("profile.png"), TempPath::new("profile.csv"));
 
-    assert_eq!(lerp(10.0, 20.0, 0.0), 10.0);
-    assert_eq!(lerp(10.0, 20.0, 0.5), 15.0);
-    assert_eq!(lerp(10.0, 20.0, 1.0), 20.0);
+    // Supersampling profiles the rows actually rendered, twice as many.
+    run(&args(&["prog", &image, "40x30", "-2,1.2", "1,-1.2", "--supersample", "2", "--profile", &csv,
+                "--stats", "--quiet"])).unwrap();
+    let profile = std::fs::read_to_string(&csv).unwrap();
+
+    let mut lines = profile.lines();
+    assert_eq!(lines.next(), Some("row,total_iterations,in_set_pixels"));
+    let rows: Vec<Vec<u64>> = lines.map(|line| line.split(',').map(|n| n.parse().unwrap()).collect())
+        .collect();
+    assert_eq!(rows.iter().map(|row| row[0]).collect::<Vec<_>>(), (0..60).collect::<Vec<_>>());
+
+    let counts = render((80, 60), parse_point("-2,1.2", "").unwrap(), parse_point("1,-1.2", "").unwrap(),
+                        Iteration::default());
+    let stats = RenderStats::of(&counts, 255).unwrap();
+    assert_eq!(rows.iter().map(|row| row[1]).sum::<u64>(), stats.iterations);
+    assert_eq!(rows.iter().map(|row| row[2]).sum::<u64>(), stats.in_set as u64);
 }
 
 #[test]
-fn test_pixel_to_point() {
-    assert_eq!(pixel_to_point((100, 200), (25, 175),
-                              Complex { re: -1.0, im:  1.0 },
-                              Complex { re:  1.0, im: -1.0 }),
-               Complex { re: -0.5, im: -0.75 });
+fn test_transparent_interior() {
+
+    use image::png::PNGDecoder;
+    use image::{ColorType, DecodingResult, ImageDecoder};
+    use mandelbrot::render;
+
+    let path = TempPath::new("transparent.png");
+
+    run(&args(&["prog", &path, "40x30", "-2,1.2", "1,-1.2", "--palette", "fire", "--gamma", "2.2",
+                "--transparent-interior", "--quiet"])).unwrap();
+    let mut decoder = PNGDecoder::new(std::fs::File::open(&path).unwrap());
+    assert_eq!(decoder.colortype().unwrap(), ColorType::RGBA(8));
+    let DecodingResult::U8(rgba) = decoder.read_image().unwrap() else { panic!("expected 8-bit samples") };
+
+    let counts = render((40, 30), parse_point("-2,1.2", "").unwrap(), parse_point("1,-1.2", "").unwrap(),
+                        Iteration::default());
+    let Counts::Whole(counts) = counts else { panic!("expected whole counts") };
+    assert_eq!(rgba.len(), counts.len() * 4);
+    for (pixel, count) in rgba.chunks(4).zip(&counts) {
+        assert_eq!(pixel[3], if count.is_some() { 255 } else { 0 });
+    }
 }
 
 #[test]
-fn test_parse_pair() {
+fn test_mask() {
 
-    assert_eq!(parse_pair::<i32>("", ','), None);
-    assert_eq!(parse_pair::<i32>("10,", ','), None);
-    assert_eq!(parse_pair::<i32>(",10", ','), None);
-    assert_eq!(parse_pair::<i32>("10,20", ','), Some((10, 20)));
-    assert_eq!(parse_pair::<i32>("10,20xy", ','), None);
-    assert_eq!(parse_pair::<f64>("0.5x",    'x'), None);
-    assert_eq!(parse_pair::<f64>("0.5x1.5", 'x'), Some((0.5, 1.5)));
+    let (mask, plain, masked) =
+        (TempPath::new("mask.png"), TempPath::new("unmasked.png"), TempPath::new("masked.png"));
+
+    // The mask covers the left half of the image.
+    let stencil: Vec<u8> = (0..40 * 30).map(|i| if i % 40 < 20 { 255 } else { 0 }).collect();
+    write_image(&mask, &stencil, (40, 30), BitDepth::Eight).unwrap();
+
+    let view = ["40x30", "-2,1.2", "1,-1.2", "--palette", "fire", "--quiet"];
+    run(&args(&[&["prog", &plain][..], &view].concat())).unwrap();
+    run(&args(&[&["prog", &masked][..], &view, &["--mask", &mask, "--mask-fill", "0,0,255"]].concat()))
+        .unwrap();
+    // Only the right half is painted over, and no fire color is pure blue.
+    let diff = compare_pngs(&plain, &masked).unwrap();
+    assert_eq!(diff.pixels, 20 * 30);
+
+    let small = ["20x30", "-2,1.2", "1,-1.2", "--mask", &mask, "--quiet"];
+    assert!(matches!(run(&args(&[&["prog", &masked][..], &small].concat())),
+                     Err(MandelError::BadMask(_))));
 }
 
 #[test]
-fn test_parse_complex() {
+fn test_checkpoint() {
 
-    assert_eq!(parse_complex("1.25,-0.0625"), Some(Complex{re:1.25, im:-0.0625}));
-    assert_eq!(parse_complex("0.0625,"), None);
-    assert_eq!(parse_complex(",-0.0625"), None);
-}
\ No newline at end of file
+    let (plain, checkpointed) = (TempPath::new("plain.png"), TempPath::new("checkpointed.png"));
+    let view = ["40x30", "-2,1.2", "1,-1.2", "--supersample", "2", "--color", "histogram", "--quiet"];
+    run(&args(&[&["prog", &plain][..], &view].concat())).unwrap();
+    run(&args(&[&["prog", &checkpointed][..], &view, &["--checkpoint", "7"]].concat())).unwrap();
+
+    let written = [std::fs::read(&plain).unwrap(), std::fs::read(&checkpointed).unwrap()];
+    assert_eq!(written[0], written[1]);
+
+    assert!(matches!(run(&args(&["prog", "-", "40x30", "-2,1.2", "1,-1.2", "--checkpoint", "7"])),
+                     Err(MandelError::BadFlag(_))));
+    assert!(matches!(run(&args(&["prog", "out.png", "40x30", "-2,1.2", "1,-1.2", "--checkpoint", "7",
+                                 "--samples", "4"])),
+                     Err(MandelError::BadFlag(_))));
+}
@@ -0,0 +1,90 @@
+//! Describing how an image was made, in a JSON file beside it.
+
+use num::Complex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::color::Palette;
+use crate::error::MandelError;
+use crate::escape::Fractal;
+
+/// Everything needed to make an image again, as `write_metadata` saves it.
+///
+/// Points are written as `[re, im]` pairs, and the palette and fractal by
+/// the names the command line uses for them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RenderMeta {
+    /// The width and height of the image in pixels.
+    pub bounds: (usize, usize),
+
+    /// The point at the upper-left corner of the image.
+    pub upper_left: Complex<f64>,
+
+    /// The point at the lower-right corner of the image.
+    pub lower_right: Complex<f64>,
+
+    /// The iteration limit for each point.
+    pub limit: usize,
+
+    /// The palette escaping points were colored with.
+    pub palette: Palette,
+
+    /// The fractal drawn.
+    pub fractal: Fractal,
+
+    /// The version of this crate that made the image.
+    pub version: String,
+
+    /// How long rendering took, in seconds.
+    pub duration: f64
+}
+
+impl RenderMeta {
+
+    /// Return the metadata as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+
+        // Every field has an obvious JSON form, so this can't fail.
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Parse the JSON `to_json` produces, describing what's wrong with it if
+    /// it can't be parsed.
+    pub fn from_json(text : &str) -> Result<RenderMeta, String> {
+
+        serde_json::from_str(text).map_err(|err| err.to_string())
+    }
+}
+
+/// Return the name of the metadata file for the image `filename`: the same
+/// name with `.json` added, like `zoom.png.json`.
+pub fn metadata_filename(filename : &str) -> String {
+
+    format!("{}.json", filename)
+}
+
+/// Write `meta` as JSON beside the image `filename`, to the file named by
+/// `metadata_filename`.
+pub fn write_metadata(filename : &str, meta : &RenderMeta) -> Result<(), MandelError> {
+
+    fs::write(metadata_filename(filename), meta.to_json() + "\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_render_meta_round_trips() {
+
+    let meta = RenderMeta { bounds: (800, 600), upper_left: Complex { re: -1.2, im: 0.35 },
+                            lower_right: Complex { re: -1.0, im: 0.2 }, limit: 1000,
+                            palette: Palette::Fire, fractal: Fractal::BurningShip,
+                            version: "0.1.0".to_string(), duration: 1.25 };
+
+    let json = meta.to_json();
+    assert!(json.contains("\"upper_left\": [\n    -1.2,\n    0.35\n  ]"), "{}", json);
+    assert!(json.contains("\"palette\": \"fire\""), "{}", json);
+    assert!(json.contains("\"fractal\": \"burningship\""), "{}", json);
+    assert_eq!(RenderMeta::from_json(&json).unwrap(), meta);
+    assert!(RenderMeta::from_json("{\"bounds\": [1, 1]}").is_err());
+
+    assert_eq!(metadata_filename("out/zoom.png"), "out/zoom.png.json");
+}
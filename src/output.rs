@@ -0,0 +1,992 @@
+//! Saving rendered images.
+
+use image::png::{PNGDecoder, PNGEncoder};
+use image::{ColorType, DecodingResult, ImageDecoder};
+use num::Complex;
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::MandelError;
+use crate::resample::halve;
+use crate::stats::RenderStats;
+
+/// The image file formats `write_image` can produce.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    /// Netpbm's binary graymap (`P5`) or pixmap (`P6`) format.
+    Ppm,
+    /// OpenEXR, holding each pixel's raw count as a 32-bit float rather than
+    /// a color; see `write_exr`. Only recognized with the `exr` feature.
+    Exr
+}
+
+impl ImageFormat {
+
+    /// Choose the format from the extension of `filename`, ignoring case. The
+    /// filename `-`, meaning standard output, is written as a PNG.
+    pub fn from_filename(filename : &str) -> Result<ImageFormat, MandelError> {
+
+        if filename == "-" {
+            return Ok(ImageFormat::Png);
+        }
+
+        let extension = Path::new(filename).extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("png") => Ok(ImageFormat::Png),
+            Some("ppm") | Some("pgm") => Ok(ImageFormat::Ppm),
+            Some("exr") if cfg!(feature = "exr") => Ok(ImageFormat::Exr),
+            _ => Err(MandelError::UnknownFormat(filename.to_string()))
+        }
+    }
+}
+
+/// How many bits each channel of a pixel takes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BitDepth {
+    Eight,
+    /// Two bytes per channel, most significant first, as both PNG and PPM
+    /// expect; see `big_endian_bytes`.
+    Sixteen
+}
+
+impl std::str::FromStr for BitDepth {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BitDepth, String> {
+
+        match s {
+            "8" => Ok(BitDepth::Eight),
+            "16" => Ok(BitDepth::Sixteen),
+            _ => Err(format!("unsupported bit depth '{}': use 8 or 16", s))
+        }
+    }
+}
+
+impl BitDepth {
+
+    /// The number of bytes each channel takes.
+    pub fn bytes(self) -> usize {
+
+        match self {
+            BitDepth::Eight => 1,
+            BitDepth::Sixteen => 2
+        }
+    }
+
+    /// The largest value a channel can hold.
+    fn max_value(self) -> u16 {
+
+        match self {
+            BitDepth::Eight => 255,
+            BitDepth::Sixteen => 65535
+        }
+    }
+}
+
+/// Return the bytes of the 16-bit `samples`, most significant byte first, as
+/// `write_image` expects them at `BitDepth::Sixteen`.
+pub fn big_endian_bytes(samples : &[u16]) -> Vec<u8> {
+
+    samples.iter().flat_map(|sample| sample.to_be_bytes()).collect()
+}
+
+/// Write `pixels`, whose dimensions are given by `bounds`, to the file named
+/// `filename`, in the format its extension calls for. If `filename` is `-`,
+/// write a PNG to standard output instead.
+///
+/// The buffer may hold one grayscale channel, three RGB channels or four RGBA
+/// channels per pixel, each `depth` wide; which one is worked out from its
+/// length. Only PNGs can hold the alpha channel.
+pub fn write_image(filename: &str, pixels: &[u8], bounds : (usize, usize), depth : BitDepth)
+    -> Result<(), MandelError> {
+
+    match ImageFormat::from_filename(filename)? {
+        ImageFormat::Png => write_png(filename, pixels, bounds, depth),
+        ImageFormat::Ppm => write_ppm(filename, pixels, bounds, depth),
+        ImageFormat::Exr => Err(MandelError::UnknownFormat(filename.to_string()))
+    }
+}
+
+/// How long `retry` waits before its first retry; it doubles the wait before
+/// each one after that.
+pub const RETRY_BACKOFF : Duration = Duration::from_millis(100);
+
+/// Call `attempt` until it succeeds or has failed `retries + 1` times, and
+/// return what it last returned. Only I/O errors, which may be transient, as
+/// on a network filesystem, are retried; `backoff` is the wait before the
+/// first retry, doubling each time after. `on_retry` is given each error
+/// that's retried and the wait before doing so, to report it.
+pub fn retry<T, F, R>(retries : usize, backoff : Duration, mut attempt : F, mut on_retry : R)
+                      -> Result<T, MandelError>
+    where F : FnMut() -> Result<T, MandelError>, R : FnMut(&MandelError, Duration)
+{
+    let mut wait = backoff;
+    for _ in 0..retries {
+        match attempt() {
+            Err(err @ MandelError::Io(_)) => on_retry(&err, wait),
+            result => return result
+        }
+        std::thread::sleep(wait);
+        wait *= 2;
+    }
+    attempt()
+}
+
+/// Like `write_image`, but if creating or writing the file fails, try again
+/// up to `retries` more times with `retry`, waiting `RETRY_BACKOFF` before
+/// the first and passing `on_retry` along. Standard output is only written
+/// once, since a second attempt would follow whatever of the first got
+/// through.
+pub fn write_image_retrying<R>(filename : &str, pixels : &[u8], bounds : (usize, usize), depth : BitDepth,
+                               retries : usize, on_retry : R) -> Result<(), MandelError>
+    where R : FnMut(&MandelError, Duration)
+{
+    let retries = if filename == "-" { 0 } else { retries };
+    retry(retries, RETRY_BACKOFF, || write_image(filename, pixels, bounds, depth), on_retry)
+}
+
+/// Write `values`, one per pixel of an image of `bounds` pixels in row-major
+/// order, to the file named `filename` as a single-channel 32-bit float EXR,
+/// such as `Counts::values` returns.
+#[cfg(feature = "exr")]
+pub fn write_exr(filename : &str, values : &[f32], bounds : (usize, usize)) -> Result<(), MandelError> {
+
+    use exr::prelude::*;
+
+    let channel = AnyChannel::new("Y", FlatSamples::F32(values.to_vec()));
+    let image = Image::from_channels(bounds, AnyChannels::sort(SmallVec::from_vec(vec![channel])));
+    image.write().to_file(filename).map_err(|err| match err {
+        Error::Io(err) => MandelError::Io(err),
+        err => MandelError::Io(io::Error::other(err))
+    })
+}
+
+/// Return the points of `orbit` as CSV: a `re,im` header, then one line per
+/// point, each part written with as many digits as it takes to read it back
+/// exactly.
+pub fn orbit_csv(orbit : &[Complex<f64>]) -> String {
+
+    let mut csv = String::from("re,im\n");
+    for z in orbit {
+        csv += &format!("{},{}\n", z.re, z.im);
+    }
+    csv
+}
+
+/// Write `orbit` to the file named `filename` as `orbit_csv` formats it.
+pub fn write_orbit(filename : &str, orbit : &[Complex<f64>]) -> Result<(), MandelError> {
+
+    std::fs::write(filename, orbit_csv(orbit))?;
+    Ok(())
+}
+
+/// Return the rows' totals from `RenderStats::of_rows` as CSV: a
+/// `row,total_iterations,in_set_pixels` header, then one line per row,
+/// numbered from zero at the top.
+pub fn profile_csv(rows : &[RenderStats]) -> String {
+
+    let mut csv = String::from("row,total_iterations,in_set_pixels\n");
+    for (row, stats) in rows.iter().enumerate() {
+        csv += &format!("{},{},{}\n", row, stats.iterations, stats.in_set);
+    }
+    csv
+}
+
+/// Write `rows` to the file named `filename` as `profile_csv` formats them.
+pub fn write_profile(filename : &str, rows : &[RenderStats]) -> Result<(), MandelError> {
+
+    std::fs::write(filename, profile_csv(rows))?;
+    Ok(())
+}
+
+/// Return `filename` with `_NNNN`, for the frame number `n`, inserted before
+/// its extension, like `frame_0001.png` for `frame.png` and 1.
+pub fn numbered_filename(filename : &str, n : usize) -> String {
+
+    let path = Path::new(filename);
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("");
+    let numbered = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => format!("{}_{:04}.{}", stem, n, ext),
+        None => format!("{}_{:04}", stem, n)
+    };
+
+    path.with_file_name(numbered).to_string_lossy().into_owned()
+}
+
+/// Write `pixels` to the file named `filename` as a PNG, or to standard
+/// output if `filename` is `-`.
+pub fn write_png(filename: &str, pixels: &[u8], bounds : (usize, usize), depth : BitDepth)
+    -> Result<(), MandelError> {
+
+    let png = encode_png(pixels, bounds, depth)?;
+
+    if filename == "-" {
+        let stdout = io::stdout();
+        let mut output = stdout.lock();
+        output.write_all(&png)?;
+        output.flush()?;
+    } else {
+        std::fs::write(filename, png)?;
+    }
+
+    Ok(())
+}
+
+/// Encode `pixels` as a PNG in memory and return its bytes, for callers that
+/// want to send the image somewhere other than a file, or look at it in a
+/// test.
+pub fn encode_png(pixels: &[u8], bounds : (usize, usize), depth : BitDepth) -> io::Result<Vec<u8>> {
+
+    let mut png = Cursor::new(Vec::new());
+    write_png_to(&mut png, pixels, bounds, depth)?;
+    Ok(png.into_inner())
+}
+
+/// Encode `pixels` as a PNG and write it to `output`.
+pub fn write_png_to<W: Write>(output: W, pixels: &[u8], bounds : (usize, usize), depth : BitDepth)
+    -> io::Result<()> {
+
+    let bits = depth.bytes() as u8 * 8;
+    let color_type = match channels(pixels, bounds, depth) {
+        1 => ColorType::Gray(bits),
+        3 => ColorType::RGB(bits),
+        _ => ColorType::RGBA(bits)
+    };
+
+    let encoder = PNGEncoder::new(output);
+
+    encoder.encode(pixels, bounds.0 as u32, bounds.1 as u32, color_type)
+}
+
+/// Return whether the file named `filename` holds a whole PNG of `bounds`
+/// pixels: one that starts with the PNG signature and decodes to the end.
+/// A missing file doesn't, and neither does one cut short by a render that
+/// died while writing it.
+pub fn is_complete_png(filename : &str, bounds : (usize, usize)) -> bool {
+
+    let Ok(png) = std::fs::read(filename) else {
+        return false;
+    };
+    if !png.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return false;
+    }
+
+    let mut decoder = PNGDecoder::new(Cursor::new(png));
+    decoder.dimensions().ok() == Some((bounds.0 as u32, bounds.1 as u32)) && decoder.read_image().is_ok()
+}
+
+/// An image's samples, laid out as `write_image` takes them, with its size
+/// and depth.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Raster {
+    /// One or three channels for each pixel, in row-major order.
+    pub pixels: Vec<u8>,
+
+    /// The width and height of the image, in pixels.
+    pub bounds: (usize, usize),
+
+    /// How wide each channel is.
+    pub depth: BitDepth
+}
+
+/// Read the PNG tiles named by `filenames`, which must all be the same size,
+/// color type and depth, and join them into one image, `grid.0` tiles across
+/// and `grid.1` down. The tiles are in row-major order: left to right
+/// across the top row, then on down.
+pub fn stitch_pngs(filenames : &[String], grid : (usize, usize)) -> Result<Raster, MandelError> {
+
+    if grid.0 == 0 || grid.1 == 0 {
+        return Err(MandelError::BadTiles("the grid must have at least one tile".to_string()));
+    }
+    if filenames.len() != grid.0 * grid.1 {
+        return Err(MandelError::BadTiles(format!("a {}x{} grid needs {} tiles, but {} were given",
+                                                 grid.0, grid.1, grid.0 * grid.1, filenames.len())));
+    }
+
+    let tiles = filenames.iter()
+        .map(|filename| read_png(filename).map_err(|err| {
+            MandelError::BadTiles(format!("error reading '{}': {}", filename, err))
+        }))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (first, bounds) = (&filenames[0], tiles[0].bounds);
+    for (filename, tile) in filenames.iter().zip(&tiles) {
+        if tile.bounds != bounds {
+            let (width, height) = tile.bounds;
+            return Err(MandelError::BadTiles(format!("'{}' is {}x{}, but '{}' is {}x{}", filename, width, height,
+                                                     first, bounds.0, bounds.1)));
+        }
+        if tile.depth != tiles[0].depth || tile.pixels.len() != tiles[0].pixels.len() {
+            return Err(MandelError::BadTiles(format!("'{}' doesn't have the same color type and depth \
+                                                      as '{}'", filename, first)));
+        }
+    }
+
+    let row_bytes = tiles[0].pixels.len() / bounds.1.max(1);
+    let mut pixels = Vec::with_capacity(tiles[0].pixels.len() * tiles.len());
+    for tile_row in tiles.chunks(grid.0) {
+        for y in 0..bounds.1 {
+            for tile in tile_row {
+                pixels.extend_from_slice(&tile.pixels[y * row_bytes..(y + 1) * row_bytes]);
+            }
+        }
+    }
+
+    Ok(Raster { pixels, bounds: (bounds.0 * grid.0, bounds.1 * grid.1), depth: tiles[0].depth })
+}
+
+/// How two images differ, as `compare_pngs` finds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelDiff {
+    /// How many pixels have any sample that differs.
+    pub pixels: usize,
+
+    /// The largest absolute difference between two corresponding samples,
+    /// up to 255 for 8-bit images and 65535 for 16-bit ones.
+    pub max: u32
+}
+
+/// Compare the PNGs named `first` and `second`, which must be the same size,
+/// color type and depth, pixel by pixel, for checking that a change to the
+/// renderer leaves its output alone.
+pub fn compare_pngs(first : &str, second : &str) -> Result<PixelDiff, MandelError> {
+
+    let read = |filename : &str| read_png(filename).map_err(|err| {
+        MandelError::BadComparison(format!("error reading '{}': {}", filename, err))
+    });
+    let (a, b) = (read(first)?, read(second)?);
+
+    if a.bounds != b.bounds {
+        let (width, height) = b.bounds;
+        return Err(MandelError::BadComparison(format!("'{}' is {}x{}, but '{}' is {}x{}", second, width,
+                                                      height, first, a.bounds.0, a.bounds.1)));
+    }
+    if a.depth != b.depth || a.pixels.len() != b.pixels.len() {
+        return Err(MandelError::BadComparison(format!("'{}' doesn't have the same color type and depth \
+                                                       as '{}'", second, first)));
+    }
+
+    let bytes = a.depth.bytes();
+    let pixel = a.pixels.len() / (a.bounds.0 * a.bounds.1).max(1);
+    let sample = |chunk : &[u8]| chunk.iter().fold(0, |value, &byte| value << 8 | byte as u32);
+    let mut diff = PixelDiff { pixels: 0, max: 0 };
+    for (a, b) in a.pixels.chunks(pixel.max(1)).zip(b.pixels.chunks(pixel.max(1))) {
+        let most = a.chunks(bytes).zip(b.chunks(bytes))
+            .map(|(a, b)| sample(a).abs_diff(sample(b)))
+            .max()
+            .unwrap_or(0);
+        if most > 0 {
+            diff.pixels += 1;
+            diff.max = diff.max.max(most);
+        }
+    }
+
+    Ok(diff)
+}
+
+/// Read the mask PNG named `filename`, which must be `bounds` pixels in size,
+/// and return whether each of its pixels, in row-major order, is set: has
+/// any sample other than 0. A 1-bit PNG reads as black and white, so either
+/// it or any other grayscale or color PNG will do.
+pub fn read_mask(filename : &str, bounds : (usize, usize)) -> Result<Vec<bool>, MandelError> {
+
+    let image = read_png(filename).map_err(|err| {
+        MandelError::BadMask(format!("error reading '{}': {}", filename, err))
+    })?;
+    if image.bounds != bounds {
+        let (width, height) = image.bounds;
+        return Err(MandelError::BadMask(format!("'{}' is {}x{}, but the image is {}x{}", filename, width,
+                                                height, bounds.0, bounds.1)));
+    }
+
+    let pixel = image.pixels.len() / (bounds.0 * bounds.1).max(1);
+    Ok(image.pixels.chunks(pixel.max(1)).map(|samples| samples.iter().any(|&sample| sample != 0)).collect())
+}
+
+/// Check that the file named `filename` is a PNG that decodes, is `bounds`
+/// pixels in size, and isn't all one color, as an image rendered from
+/// corners that don't frame anything of interest tends to be.
+pub fn verify_png(filename : &str, bounds : (usize, usize)) -> Result<(), MandelError> {
+
+    let image = read_png(filename).map_err(|err| {
+        MandelError::FailedVerification(format!("error reading '{}': {}", filename, err))
+    })?;
+
+    if image.bounds != bounds {
+        let (width, height) = image.bounds;
+        return Err(MandelError::FailedVerification(format!("'{}' is {}x{}, but should be {}x{}", filename,
+                                                           width, height, bounds.0, bounds.1)));
+    }
+    let pixel = image.pixels.len() / (bounds.0 * bounds.1).max(1);
+    let mut pixels = image.pixels.chunks(pixel.max(1));
+    let first = pixels.next();
+    if pixels.all(|other| Some(other) == first) {
+        let message = format!("every pixel of '{}' is the same color", filename);
+        return Err(MandelError::FailedVerification(message));
+    }
+
+    Ok(())
+}
+
+/// How many pixels wide and high the tiles of `write_pyramid` are, but for
+/// those cut short at the right and bottom edges.
+pub const PYRAMID_TILE_SIZE : usize = 256;
+
+/// Return the number of the level of a deep-zoom pyramid that holds an image
+/// of `bounds` pixels at full size. As in the Deep Zoom format, level 0 is a
+/// single pixel and each level doubles the one before, so this is the number
+/// of halvings it takes to get the longer side down to 1.
+pub fn pyramid_level(bounds : (usize, usize)) -> usize {
+
+    bounds.0.max(bounds.1).max(1).next_power_of_two().trailing_zeros() as usize
+}
+
+/// Return how many tiles `tile_size` pixels square it takes to cover an image
+/// of `bounds` pixels, across and down.
+pub fn tile_grid(bounds : (usize, usize), tile_size : usize) -> (usize, usize) {
+
+    (bounds.0.div_ceil(tile_size), bounds.1.div_ceil(tile_size))
+}
+
+/// Cut `image` into tiles `tile_size` pixels square, those along the right
+/// and bottom edges cut short to fit, and write each as a PNG named
+/// `COLUMN_ROW.png`, counting from 0 at the upper left, in the directory
+/// `dir`, creating it if need be. Return the number of tiles written.
+pub fn write_tiles(dir : &Path, image : &Raster, tile_size : usize) -> Result<usize, MandelError> {
+
+    std::fs::create_dir_all(dir)?;
+    let bounds = image.bounds;
+    let pixel = image.pixels.len() / (bounds.0 * bounds.1).max(1);
+    let grid = tile_grid(bounds, tile_size);
+
+    for row in 0..grid.1 {
+        for column in 0..grid.0 {
+            let (left, top) = (column * tile_size, row * tile_size);
+            let size = (tile_size.min(bounds.0 - left), tile_size.min(bounds.1 - top));
+            let mut tile = Vec::with_capacity(size.0 * size.1 * pixel);
+            for y in top..top + size.1 {
+                let start = (y * bounds.0 + left) * pixel;
+                tile.extend_from_slice(&image.pixels[start..start + size.0 * pixel]);
+            }
+            let filename = dir.join(format!("{}_{}.png", column, row));
+            write_png(&filename.to_string_lossy(), &tile, size, image.depth)?;
+        }
+    }
+
+    Ok(grid.0 * grid.1)
+}
+
+/// Write the top `levels` levels of a deep-zoom pyramid of the 8-bit `image`
+/// to the directory `dir`, each level's tiles, as `write_tiles` cuts them,
+/// in a directory of its own named by the level's number, as
+/// `pyramid_level` counts them: that is, `DIR/LEVEL/COLUMN_ROW.png`. The top
+/// level is `image` itself, and each level below it is the one above halved.
+/// Return the size of each level written, from the top down.
+pub fn write_pyramid(dir : &Path, image : &Raster, levels : usize, tile_size : usize)
+    -> Result<Vec<(usize, usize)>, MandelError> {
+
+    assert_eq!(image.depth, BitDepth::Eight, "pyramids are of 8-bit images");
+    let top = pyramid_level(image.bounds);
+    let mut written = Vec::new();
+    let mut level = image.clone();
+
+    for number in (0..=top).rev().take(levels) {
+        if number < top {
+            level = Raster { pixels: halve(&level.pixels, level.bounds),
+                             bounds: (level.bounds.0.div_ceil(2), level.bounds.1.div_ceil(2)), ..level };
+        }
+        write_tiles(&dir.join(number.to_string()), &level, tile_size)?;
+        written.push(level.bounds);
+    }
+
+    Ok(written)
+}
+
+/// Decode the grayscale, RGB or RGBA PNG named `filename`, or say why it
+/// couldn't be.
+///
+/// `image` cuts 16-bit samples down to 8 bits as it decodes them, so 16-bit
+/// PNGs are decoded with `png` itself instead, untransformed, which leaves
+/// the samples big-endian, just as a `Raster` holds them. Its decoder can't
+/// yet undo the interlacing of 16-bit images, so those are refused.
+fn read_png(filename : &str) -> Result<Raster, String> {
+
+    let unsupported = || "only 8- and 16-bit grayscale, RGB and RGBA images can be read".to_string();
+    let png = std::fs::read(filename).map_err(|err| err.to_string())?;
+
+    let mut decoder = png::Decoder::new(Cursor::new(&png));
+    png::HasParameters::set(&mut decoder, png::TRANSFORM_IDENTITY);
+    let (info, mut reader) = decoder.read_info().map_err(|err| err.to_string())?;
+    let bounds = (info.width as usize, info.height as usize);
+    if info.bit_depth == png::BitDepth::Sixteen {
+        use png::ColorType::{Grayscale, RGB, RGBA};
+        if !matches!(info.color_type, Grayscale | RGB | RGBA) {
+            return Err(unsupported());
+        }
+        if reader.info().interlaced {
+            return Err("interlaced 16-bit images can't be read".to_string());
+        }
+        let mut pixels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut pixels).map_err(|err| err.to_string())?;
+        return Ok(Raster { pixels, bounds, depth: BitDepth::Sixteen });
+    }
+
+    let mut decoder = PNGDecoder::new(Cursor::new(png));
+    match decoder.colortype().map_err(|err| err.to_string())? {
+        ColorType::Gray(8) | ColorType::RGB(8) | ColorType::RGBA(8) => {}
+        _ => return Err(unsupported())
+    }
+    match decoder.read_image().map_err(|err| err.to_string())? {
+        DecodingResult::U8(pixels) => Ok(Raster { pixels, bounds, depth: BitDepth::Eight }),
+        DecodingResult::U16(_) => Err("unexpected 16-bit samples".to_string())
+    }
+}
+
+/// Write `pixels` to the file named `filename` as a binary PPM: `P5` for a
+/// grayscale buffer, `P6` for an RGB one.
+pub fn write_ppm(filename: &str, pixels: &[u8], bounds : (usize, usize), depth : BitDepth)
+    -> Result<(), MandelError> {
+
+    let mut output = BufWriter::new(File::create(filename)?);
+    write_ppm_to(&mut output, pixels, bounds, depth)?;
+    output.flush()?;
+
+    Ok(())
+}
+
+/// Write the PPM header and raw bytes for `pixels` to `output`. At 16 bits,
+/// the header's maximum value is 65535 rather than 255.
+pub fn write_ppm_to<W: Write>(output: &mut W, pixels: &[u8], bounds : (usize, usize), depth : BitDepth)
+    -> io::Result<()> {
+
+    let magic = match channels(pixels, bounds, depth) {
+        1 => "P5",
+        3 => "P6",
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "PPM files can't hold an alpha channel"))
+    };
+
+    write!(output, "{}\n{} {}\n{}\n", magic, bounds.0, bounds.1, depth.max_value())?;
+    output.write_all(pixels)
+}
+
+/// Return how many channels of `depth` `pixels` stores for each of the
+/// `bounds.0 * bounds.1` pixels: 1 for grayscale, 3 for RGB or 4 for RGBA.
+fn channels(pixels: &[u8], bounds : (usize, usize), depth : BitDepth) -> usize {
+
+    let pixel_bytes = bounds.0 * bounds.1 * depth.bytes();
+    let channels = pixels.len() / pixel_bytes.max(1);
+    assert!(pixels.len() == pixel_bytes * channels && matches!(channels, 1 | 3 | 4),
+            "pixel buffer doesn't hold a grayscale, RGB or RGBA image of the given bounds");
+    channels
+}
+
+#[test]
+fn test_image_format_from_filename() {
+
+    assert_eq!(ImageFormat::from_filename("out.png").unwrap(), ImageFormat::Png);
+    assert_eq!(ImageFormat::from_filename("dir.d/OUT.PPM").unwrap(), ImageFormat::Ppm);
+    assert!(matches!(ImageFormat::from_filename("out.jpg"), Err(MandelError::UnknownFormat(_))));
+    assert!(matches!(ImageFormat::from_filename("png"), Err(MandelError::UnknownFormat(_))));
+    assert_eq!(ImageFormat::from_filename("-").unwrap(), ImageFormat::Png);
+}
+
+#[test]
+fn test_write_ppm_to() {
+
+    let mut gray = Vec::new();
+    write_ppm_to(&mut gray, &[0, 128, 255, 7, 8, 9], (3, 2), BitDepth::Eight).unwrap();
+    assert_eq!(&gray[..11], format!("P5\n{} {}\n255\n", 3, 2).as_bytes());
+    assert_eq!(&gray[11..], &[0, 128, 255, 7, 8, 9]);
+
+    let mut rgb = Vec::new();
+    write_ppm_to(&mut rgb, &[1, 2, 3, 4, 5, 6], (2, 1), BitDepth::Eight).unwrap();
+    assert_eq!(&rgb[..11], b"P6\n2 1\n255\n");
+    assert_eq!(rgb.len(), 11 + 6);
+
+    let rgba = write_ppm_to(&mut Vec::new(), &[1, 2, 3, 0, 4, 5, 6, 255], (2, 1), BitDepth::Eight);
+    assert_eq!(rgba.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_write_png_to() {
+
+    let mut png = Vec::new();
+    write_png_to(&mut png, &[0, 64, 128, 192, 255, 32], (3, 2), BitDepth::Eight).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+    // The IHDR chunk's bit depth and color type follow the width and height.
+    let mut png = Vec::new();
+    write_png_to(&mut png, &big_endian_bytes(&[0, 16384, 32768, 49152, 65535, 8192]), (3, 2),
+                 BitDepth::Sixteen).unwrap();
+    assert_eq!(&png[24..26], &[16, 0]);
+
+    let mut png = Vec::new();
+    write_png_to(&mut png, &[1, 2, 3, 0, 4, 5, 6, 255], (2, 1), BitDepth::Eight).unwrap();
+    assert_eq!(&png[24..26], &[8, 6]);
+}
+
+#[test]
+fn test_retry() {
+
+    use std::cell::Cell;
+
+    // A writer whose first write fails, as a flaky network filesystem's
+    // might, and which keeps whatever it's given after that.
+    struct Flaky<'a> { failures: &'a Cell<usize>, written: Vec<u8> }
+    impl Write for Flaky<'_> {
+        fn write(&mut self, bytes : &[u8]) -> io::Result<usize> {
+            if self.failures.get() > 0 {
+                self.failures.set(self.failures.get() - 1);
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "the server went away"));
+            }
+            self.written.extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let pixels = [0, 64, 128, 192, 255, 32];
+    let failures = Cell::new(1);
+    let (mut attempts, mut retried) = (0, Vec::new());
+    let png = retry(1, Duration::ZERO, || {
+        attempts += 1;
+        let mut output = Flaky { failures: &failures, written: Vec::new() };
+        write_png_to(&mut output, &pixels, (3, 2), BitDepth::Eight)?;
+        Ok(output.written)
+    }, |err, wait| retried.push((matches!(err, MandelError::Io(_)), wait))).unwrap();
+    assert_eq!(attempts, 2);
+    assert_eq!(retried, [(true, Duration::ZERO)]);
+    assert_eq!(png, encode_png(&pixels, (3, 2), BitDepth::Eight).unwrap());
+
+    // Without a retry, the failure is what comes back.
+    failures.set(1);
+    let result = retry(0, Duration::ZERO, || {
+        let output = Flaky { failures: &failures, written: Vec::new() };
+        Ok(write_png_to(output, &pixels, (3, 2), BitDepth::Eight)?)
+    }, |_, _| panic!("retried without any retries"));
+    assert!(matches!(result, Err(MandelError::Io(_))));
+
+    // Errors other than I/O ones won't go away by trying again.
+    let mut attempts = 0;
+    let result : Result<(), _> = retry(3, Duration::ZERO, || {
+        attempts += 1;
+        Err(MandelError::UnknownFormat("out.gif".to_string()))
+    }, |_, _| {});
+    assert!(matches!(result, Err(MandelError::UnknownFormat(_))) && attempts == 1);
+}
+
+#[test]
+fn test_encode_png() {
+
+    let png = encode_png(&[0, 64, 128, 192, 255, 32], (3, 2), BitDepth::Eight).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+    let mut decoder = PNGDecoder::new(Cursor::new(png));
+    assert_eq!(decoder.dimensions().unwrap(), (3, 2));
+    assert!(matches!(decoder.read_image().unwrap(),
+                     image::DecodingResult::U8(ref pixels) if pixels == &[0, 64, 128, 192, 255, 32]));
+}
+
+#[test]
+fn test_is_complete_png() {
+
+    let path = std::env::temp_dir().join(format!("mandelbrot-complete-{}.png", std::process::id()));
+    let path = path.to_str().unwrap();
+    assert!(!is_complete_png(path, (3, 2)));
+
+    let png = encode_png(&[0, 64, 128, 192, 255, 32], (3, 2), BitDepth::Eight).unwrap();
+    std::fs::write(path, &png).unwrap();
+    assert!(is_complete_png(path, (3, 2)));
+    assert!(!is_complete_png(path, (2, 3)));
+
+    for length in [0, 8, 40, png.len() - 20, png.len() - 12, png.len() - 1] {
+        std::fs::write(path, &png[..length]).unwrap();
+        assert!(!is_complete_png(path, (3, 2)), "a PNG cut to {} bytes should be incomplete", length);
+    }
+
+    let mut wrong_magic = png.clone();
+    wrong_magic[1] = b'Q';
+    std::fs::write(path, wrong_magic).unwrap();
+    assert!(!is_complete_png(path, (3, 2)));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_profile_csv() {
+
+    let rows = [RenderStats { iterations: 510, in_set: 2, pixels: 3 },
+                RenderStats { iterations: 7, in_set: 0, pixels: 3 }];
+    assert_eq!(profile_csv(&rows), "row,total_iterations,in_set_pixels\n0,510,2\n1,7,0\n");
+}
+
+#[test]
+fn test_write_orbit() {
+
+    use crate::{escape_time, orbit, reference_orbit};
+
+    let path = std::env::temp_dir().join(format!("mandelbrot-orbit-{}.csv", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    // Views centered on a point that escapes, and on one in the set.
+    for (upper_left, lower_right) in [(Complex { re: -0.5, im: 0.9 }, Complex { re: 0.1, im: 0.5 }),
+                                      (Complex { re: -2.5, im: 1.5 }, Complex { re: 1.0, im: -1.5 })] {
+        let center = (upper_left + lower_right) / 2.0;
+        write_orbit(path, &reference_orbit(upper_left, lower_right, 1000)).unwrap();
+
+        let csv = std::fs::read_to_string(path).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("re,im"));
+        let points : Vec<Complex<f64>> = lines.map(|line| {
+            let (re, im) = line.split_once(',').unwrap();
+            Complex { re: re.parse().unwrap(), im: im.parse().unwrap() }
+        }).collect();
+
+        assert_eq!(points[..3], orbit(center, 1000).take(3).collect::<Vec<_>>()[..]);
+        assert_eq!(points.len(), escape_time(Complex::new(0.0, 0.0), center, 1000, 2.0).unwrap_or(1000));
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_numbered_filename() {
+
+    assert_eq!(numbered_filename("frame.png", 1), "frame_0001.png");
+    assert_eq!(numbered_filename("out/zoom.ppm", 120), "out/zoom_0120.ppm");
+    assert_eq!(numbered_filename("frames", 7), "frames_0007");
+}
+
+#[test]
+fn test_sixteen_bit_output() {
+
+    assert_eq!(big_endian_bytes(&[0x1234, 0xff00]), [0x12, 0x34, 0xff, 0x00]);
+    assert_eq!("16".parse(), Ok(BitDepth::Sixteen));
+    assert!("12".parse::<BitDepth>().is_err());
+
+    let bounds = (5, 4);
+    let eight = vec![200u8; bounds.0 * bounds.1];
+    let sixteen = big_endian_bytes(&vec![51400u16; bounds.0 * bounds.1]);
+    assert_eq!(sixteen.len(), 2 * eight.len());
+
+    let mut narrow = Vec::new();
+    write_ppm_to(&mut narrow, &eight, bounds, BitDepth::Eight).unwrap();
+    let mut wide = Vec::new();
+    write_ppm_to(&mut wide, &sixteen, bounds, BitDepth::Sixteen).unwrap();
+    assert!(wide.starts_with(b"P5\n5 4\n65535\n"));
+    assert_eq!(wide.len() - b"P5\n5 4\n65535\n".len(), 2 * (narrow.len() - b"P5\n5 4\n255\n".len()));
+}
+
+#[cfg(feature = "exr")]
+#[test]
+fn test_write_exr() {
+
+    use crate::Counts;
+
+    let path = std::env::temp_dir().join(format!("mandelbrot-values-{}.exr", std::process::id()));
+    let path = path.to_str().unwrap();
+    assert_eq!(ImageFormat::from_filename(path).unwrap(), ImageFormat::Exr);
+
+    let counts = Counts::Smooth(vec![Some(0.25), Some(3.5), None, Some(1e-3), Some(254.875), Some(17.1)]);
+    let values = counts.values();
+    write_exr(path, &values, (3, 2)).unwrap();
+
+    let image = exr::prelude::read_first_flat_layer_from_file(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!((image.layer_data.size.0, image.layer_data.size.1), (3, 2));
+    match &image.layer_data.channel_data.list[0].sample_data {
+        exr::prelude::FlatSamples::F32(read) => {
+            let expected = [0.25, 3.5, -1.0, 1e-3, 254.875, 17.1];
+            assert!(read.iter().zip(expected).all(|(read, value)| (read - value).abs() < 1e-6), "{:?}", read);
+        }
+        other => panic!("expected 32-bit float samples, got {:?}", other)
+    }
+}
+
+#[test]
+fn test_stitch_pngs() {
+
+    use crate::{map_colors, render, render_tile, ColorScheme, Iteration, Palette};
+
+    let dir = std::env::temp_dir().join(format!("mandelbrot-stitch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |name : &str| dir.join(name).to_str().unwrap().to_string();
+
+    let bounds = (40, 30);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.2 }, Complex { re: 1.0, im: -1.2 });
+    let iteration = Iteration::default();
+    let scheme = ColorScheme { palette: Palette::Fire, ..ColorScheme::default() };
+    let full = map_colors(&render(bounds, upper_left, lower_right, iteration), iteration.limit, &scheme);
+
+    // Quadrants, top left, top right, bottom left, bottom right.
+    let half = (bounds.0 / 2, bounds.1 / 2);
+    let mut tiles = Vec::new();
+    for (n, origin) in [(0, 0), (half.0, 0), (0, half.1), half].into_iter().enumerate() {
+        let counts = render_tile(bounds, origin, half, upper_left, lower_right, iteration);
+        tiles.push(path(&format!("tile_{}.png", n)));
+        write_png(&tiles[n], &map_colors(&counts, iteration.limit, &scheme), half, BitDepth::Eight).unwrap();
+    }
+
+    let stitched = stitch_pngs(&tiles, (2, 2)).unwrap();
+    assert_eq!((stitched.bounds, stitched.depth), (bounds, BitDepth::Eight));
+    assert!(stitched.pixels == full, "the stitched tiles should match the single render");
+
+    // 16-bit tiles keep every bit of their samples, low bytes included.
+    let grays : Vec<u16> = (0..bounds.0 * bounds.1).map(|i| (i * 257 + 3) as u16).collect();
+    let mut deep = Vec::new();
+    for (n, origin) in [(0, 0), (half.0, 0), (0, half.1), half].into_iter().enumerate() {
+        let tile : Vec<u16> = (origin.1..origin.1 + half.1)
+            .flat_map(|y| grays[y * bounds.0 + origin.0..][..half.0].to_vec())
+            .collect();
+        deep.push(path(&format!("deep_{}.png", n)));
+        write_png(&deep[n], &big_endian_bytes(&tile), half, BitDepth::Sixteen).unwrap();
+    }
+    let stitched = stitch_pngs(&deep, (2, 2)).unwrap();
+    assert_eq!((stitched.bounds, stitched.depth), (bounds, BitDepth::Sixteen));
+    assert!(stitched.pixels == big_endian_bytes(&grays), "the 16-bit tiles should stitch back exactly");
+    let mixed = [&tiles[..3], &deep[3..]].concat();
+    assert!(stitch_pngs(&mixed, (2, 2)).unwrap_err().to_string().contains("same color type and depth"));
+
+    // Rows of tiles stack just as well.
+    let column = stitch_pngs(&tiles[..2], (1, 2)).unwrap();
+    assert_eq!(column.bounds, (half.0, bounds.1));
+    assert_eq!(column.pixels.len(), half.0 * bounds.1 * 3);
+
+    let wrong_size = path("wrong_size.png");
+    write_png(&wrong_size, &[0; 4], (2, 2), BitDepth::Eight).unwrap();
+    let three = &tiles[..3];
+    for (tiles, message) in [(three.to_vec(), "needs 4 tiles"),
+                             ([three, &[path("missing.png")]].concat(), "error reading"),
+                             ([three, &[wrong_size]].concat(), "is 2x2, but")] {
+        let err = stitch_pngs(&tiles, (2, 2)).unwrap_err().to_string();
+        assert!(err.contains(message), "{:?} should mention {:?}", err, message);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_write_pyramid() {
+
+    let dir = std::env::temp_dir().join(format!("mandelbrot-pyramid-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert_eq!((pyramid_level((1, 1)), pyramid_level((256, 100)), pyramid_level((600, 300))), (0, 8, 10));
+    assert_eq!(tile_grid((600, 300), 256), (3, 2));
+
+    // Each level has as many tiles as it takes to cover it, the last of each
+    // row and column cut short.
+    let image = Raster { pixels: (0..600 * 300 * 3).map(|i| (i % 251) as u8).collect(), bounds: (600, 300),
+                         depth: BitDepth::Eight };
+    let levels = write_pyramid(&dir, &image, 4, 256).unwrap();
+    assert_eq!(levels, [(600, 300), (300, 150), (150, 75), (75, 38)]);
+    for (level, bounds) in (7..=10).rev().zip(levels) {
+        let tiles = std::fs::read_dir(dir.join(level.to_string())).unwrap().count();
+        let grid = (bounds.0.div_ceil(256), bounds.1.div_ceil(256));
+        assert_eq!(tiles, grid.0 * grid.1, "level {}", level);
+        assert_eq!(grid, tile_grid(bounds, 256));
+    }
+    assert!(is_complete_png(&dir.join("10/2_1.png").to_string_lossy(), (88, 44)));
+    assert!(is_complete_png(&dir.join("9/1_0.png").to_string_lossy(), (44, 150)));
+    assert!(!dir.join("6").exists());
+
+    // The top-left tile is the image's own upper-left corner.
+    let corner = read_png(&dir.join("10/0_0.png").to_string_lossy()).unwrap();
+    assert_eq!(corner.bounds, (256, 256));
+    assert_eq!(corner.pixels[..256 * 3], image.pixels[..256 * 3]);
+    assert_eq!(corner.pixels[256 * 3..512 * 3], image.pixels[600 * 3..(600 + 256) * 3]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_read_mask() {
+
+    let path = std::env::temp_dir().join(format!("mandelbrot-mask-{}.png", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    write_png(path, &[0, 255, 1, 0, 0, 0], (3, 2), BitDepth::Eight).unwrap();
+    assert_eq!(read_mask(path, (3, 2)).unwrap(), [false, true, true, false, false, false]);
+    assert!(matches!(read_mask(path, (2, 3)), Err(MandelError::BadMask(_))));
+
+    write_png(path, &[0, 0, 0, 0, 9, 0], (2, 1), BitDepth::Eight).unwrap();
+    assert_eq!(read_mask(path, (2, 1)).unwrap(), [false, true]);
+
+    std::fs::remove_file(path).unwrap();
+    assert!(matches!(read_mask(path, (2, 1)), Err(MandelError::BadMask(_))));
+}
+
+#[test]
+fn test_verify_png() {
+
+    use crate::{map_colors, render, ColorScheme, Iteration};
+
+    let dir = std::env::temp_dir();
+    let path = |name : &str| dir.join(format!("mandelbrot-verify-{}-{}.png", std::process::id(), name))
+        .to_str().unwrap().to_string();
+    let (rendered, black, truncated) = (path("rendered"), path("black"), path("truncated"));
+
+    let counts = render((20, 15), Complex { re: -2.0, im: 1.2 }, Complex { re: 1.0, im: -1.2 },
+                        Iteration::default());
+    write_png(&rendered, &map_colors(&counts, 255, &ColorScheme::default()), (20, 15), BitDepth::Eight)
+        .unwrap();
+    verify_png(&rendered, (20, 15)).unwrap();
+    assert!(matches!(verify_png(&rendered, (15, 20)), Err(MandelError::FailedVerification(_))));
+
+    // Corners inside the set render nothing but black.
+    write_png(&black, &[0; 20 * 15 * 3], (20, 15), BitDepth::Eight).unwrap();
+    assert!(matches!(verify_png(&black, (20, 15)),
+                     Err(MandelError::FailedVerification(ref message)) if message.contains("same color")));
+
+    let png = std::fs::read(&rendered).unwrap();
+    std::fs::write(&truncated, &png[..png.len() / 2]).unwrap();
+    assert!(matches!(verify_png(&truncated, (20, 15)), Err(MandelError::FailedVerification(_))));
+    assert!(matches!(verify_png(&path("missing"), (20, 15)), Err(MandelError::FailedVerification(_))));
+
+    for filename in [rendered, black, truncated] {
+        std::fs::remove_file(filename).unwrap();
+    }
+}
+
+#[test]
+fn test_compare_pngs() {
+
+    let dir = std::env::temp_dir();
+    let path = |name : &str| dir.join(format!("mandelbrot-compare-{}-{}.png", std::process::id(), name))
+        .to_str().unwrap().to_string();
+    let (original, changed, small, wide) = (path("original"), path("changed"), path("small"), path("wide"));
+
+    let pixels : Vec<u8> = (0..6 * 4 * 3).map(|i| (i * 7 % 256) as u8).collect();
+    write_png(&original, &pixels, (6, 4), BitDepth::Eight).unwrap();
+    assert_eq!(compare_pngs(&original, &original).unwrap(), PixelDiff { pixels: 0, max: 0 });
+
+    // Changing one channel of one pixel shows up as one pixel, by that much.
+    let mut modified = pixels.clone();
+    modified[3 * 7 + 1] = modified[3 * 7 + 1].wrapping_add(40);
+    write_png(&changed, &modified, (6, 4), BitDepth::Eight).unwrap();
+    let diff = compare_pngs(&original, &changed).unwrap();
+    assert_eq!(diff.pixels, 1);
+    assert_eq!(diff.max, (pixels[3 * 7 + 1] as u32).abs_diff(modified[3 * 7 + 1] as u32));
+
+    // 16-bit samples are compared whole, low bytes and all.
+    write_png(&wide, &big_endian_bytes(&[1000, 2000]), (2, 1), BitDepth::Sixteen).unwrap();
+    write_png(&small, &big_endian_bytes(&[1000, 1999]), (2, 1), BitDepth::Sixteen).unwrap();
+    assert_eq!(compare_pngs(&wide, &small).unwrap(), PixelDiff { pixels: 1, max: 1 });
+
+    write_png(&small, &pixels[..3 * 3 * 3], (3, 3), BitDepth::Eight).unwrap();
+    assert!(matches!(compare_pngs(&original, &small), Err(MandelError::BadComparison(_))));
+    assert!(matches!(compare_pngs(&original, &path("missing")), Err(MandelError::BadComparison(_))));
+
+    for filename in [original, changed, small, wide] {
+        std::fs::remove_file(filename).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+//! Parsing coordinates and dimensions from command-line strings.
+
+use num::{Complex, Float};
+use std::str::FromStr;
+
+use crate::error::{MandelError, ParseError};
+
+/// Parse the string `s` as a coordinate pair, like `"400x600"` or `"1.0,0.5"`.
+///
+/// Specifically, `s` should have the form <left><sep><right>, where <sep> is
+/// the character given by the `separator` argument, and <left> and <right> are
+/// both strings that can be parsed by `T::from_str`. `separator` must be an
+/// ASCII character. Whitespace around either half is ignored, as in
+/// `" -1.20 , 0.35 "` copied from elsewhere, but not whitespace within one.
+///
+/// If `s` has the proper form, return `Some<(x, y)>`. If it doesn't parse
+/// correctly, return `None`.
+pub fn parse_pair<T: FromStr>(s: &str, separator:char) -> Option<(T, T)> {
+
+    match s.find(separator) {
+
+        None => None,
+        Some(index) => {
+
+            match (T::from_str(s[..index].trim()), T::from_str(s[index+1..].trim())) {
+                (Ok(l), Ok(r)) => Some((l, r)),
+                _ => None
+            }
+        }
+
+    }
+}
+
+/// The separators `parse_pair_auto` tries, in order. `-` is deliberately not
+/// among them, since it's the sign of a negative number.
+const PAIR_SEPARATORS: [char; 3] = ['x', ',', ':'];
+
+/// Parse the string `s` as a coordinate pair like `parse_pair` does, but
+/// without insisting on a particular separator: try `x`, `,` and `:` in turn,
+/// and return the first pair whose halves both parse.
+///
+/// This is for forgiving users who type `"1024,768"` where `"1024x768"` was
+/// expected. It's no help with a pair like `"-1-2"`, since a minus sign is
+/// never taken as a separator.
+pub fn parse_pair_auto<T: FromStr>(s: &str) -> Option<(T, T)> {
+
+    PAIR_SEPARATORS.iter().find_map(|&separator| parse_pair(s, separator))
+}
+
+/// Parse a pair of floating-point numbers separated by a comma as a complex
+/// number, of `f32` or `f64` parts, returning `None` if `try_parse_complex`
+/// can't.
+pub fn parse_complex<T : FromStr + Float>(s : &str) -> Option<Complex<T>> {
+
+    try_parse_complex(s).ok()
+}
+
+/// Like `parse_complex`, but report what was wrong with `s` when it isn't a
+/// complex number: a missing comma, or which part failed and its text.
+///
+/// `T::from_str` happily accepts `inf` and `nan`, and turns `1e400` into
+/// infinity, none of which makes sense as a point to render, so a pair with
+/// either part not finite is rejected too. As with `parse_pair`, whitespace
+/// around either part is ignored.
+pub fn try_parse_complex<T : FromStr + Float>(s : &str) -> Result<Complex<T>, ParseError> {
+
+    let (re, im) = s.split_once(',').ok_or(ParseError::MissingComma)?;
+    let part = |part : &'static str, text : &str| match T::from_str(text.trim()) {
+        Ok(value) if value.is_finite() => Ok(value),
+        Ok(_) => Err(ParseError::NotFinite { part, text: text.to_string() }),
+        Err(_) => Err(ParseError::BadPart { part, text: text.to_string() })
+    };
+
+    Ok(Complex { re: part("real", re)?, im: part("imaginary", im)? })
+}
+
+/// Parse image dimensions like `"1024x768"`, reporting a failure as
+/// `MandelError::BadDimensions`. An image with no pixels along either side is
+/// a failure too.
+pub fn parse_dimensions(s : &str) -> Result<(usize, usize), MandelError> {
+
+    match parse_pair(s, 'x') {
+        Some((width, height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(MandelError::BadDimensions(s.to_string()))
+    }
+}
+
+/// Parse a color like `"255,128,0"`: its red, green and blue channels, each
+/// from 0 to 255, separated by commas, with whitespace around each ignored.
+/// Return `None` unless there are exactly three channels that all parse.
+pub fn parse_color(s : &str) -> Option<[u8; 3]> {
+
+    let channels : Vec<u8> = s.split(',').map(|channel| channel.trim().parse().ok()).collect::<Option<_>>()?;
+    channels.try_into().ok()
+}
+
+/// Parse a complex number with `try_parse_complex`, reporting a failure as
+/// `MandelError::BadComplex` naming `argument` and the reason, or as
+/// `MandelError::NotFinite` if it parsed but a part is infinite or NaN.
+pub fn parse_point<T : FromStr + Float>(s : &str, argument : &'static str) -> Result<Complex<T>, MandelError> {
+
+    let value = s.to_string();
+    try_parse_complex(s).map_err(|reason| match reason {
+        ParseError::NotFinite { .. } => MandelError::NotFinite { argument, value },
+        reason => MandelError::BadComplex { argument, value, reason }
+    })
+}
+
+#[test]
+fn test_parse_pair() {
+
+    assert_eq!(parse_pair::<i32>("", ','), None);
+    assert_eq!(parse_pair::<i32>("10,", ','), None);
+    assert_eq!(parse_pair::<i32>(",10", ','), None);
+    assert_eq!(parse_pair::<i32>("10,20", ','), Some((10, 20)));
+    assert_eq!(parse_pair::<i32>("10,20xy", ','), None);
+    assert_eq!(parse_pair::<f64>("0.5x",    'x'), None);
+    assert_eq!(parse_pair::<f64>("0.5x1.5", 'x'), Some((0.5, 1.5)));
+
+    // Whitespace around the halves is ignored, but not within them.
+    assert_eq!(parse_pair::<f64>(" -1.20 , 0.35 ", ','), Some((-1.2, 0.35)));
+    assert_eq!(parse_pair::<usize>("1024 x\t768\n", 'x'), Some((1024, 768)));
+    assert_eq!(parse_pair::<i32>("1 2,3", ','), None);
+    assert_eq!(parse_pair::<i32>("1,2 3", ','), None);
+    assert_eq!(parse_pair::<i32>(" ,3", ','), None);
+}
+
+#[test]
+fn test_parse_pair_auto() {
+
+    assert_eq!(parse_pair_auto::<usize>("1024x768"), Some((1024, 768)));
+    assert_eq!(parse_pair_auto::<usize>("1024,768"), Some((1024, 768)));
+    assert_eq!(parse_pair_auto::<usize>("1024:768"), Some((1024, 768)));
+    assert_eq!(parse_pair_auto::<f64>("-1.2x0.35"), Some((-1.2, 0.35)));
+    assert_eq!(parse_pair_auto::<f64>("-1.2,-0.35"), Some((-1.2, -0.35)));
+    assert_eq!(parse_pair_auto::<f64>("-1.2:-0.35"), Some((-1.2, -0.35)));
+    assert_eq!(parse_pair_auto::<f64>("1e-3x2"), Some((1e-3, 2.0)));
+
+    // A minus sign is part of a number, never a separator.
+    assert_eq!(parse_pair_auto::<f64>("-1.2-0.35"), None);
+    assert_eq!(parse_pair_auto::<i32>("10"), None);
+    assert_eq!(parse_pair_auto::<i32>("10x20,30"), None);
+}
+
+#[test]
+fn test_parse_complex() {
+
+    assert_eq!(parse_complex("1.25,-0.0625"), Some(Complex{re:1.25, im:-0.0625}));
+    assert_eq!(parse_complex::<f64>("0.0625,"), None);
+    assert_eq!(parse_complex::<f64>(",-0.0625"), None);
+    assert_eq!(parse_complex("1.25,-0.0625"), Some(Complex{re:1.25f32, im:-0.0625}));
+    assert_eq!(parse_complex::<f32>("1.25,i"), None);
+
+    assert_eq!(parse_complex::<f64>("inf,0"), None);
+    assert_eq!(parse_complex::<f64>("0,nan"), None);
+    assert_eq!(parse_complex::<f64>("1e400,0"), None);
+    assert_eq!(parse_complex::<f32>("1e39,0"), None);
+    assert_eq!(parse_complex("1e300,-1e-300"), Some(Complex{re:1e300, im:-1e-300}));
+}
+
+#[test]
+fn test_try_parse_complex() {
+
+    let bad = |part, text : &str| Err(ParseError::BadPart { part, text: text.to_string() });
+
+    assert_eq!(try_parse_complex("1.25,-0.0625"), Ok(Complex { re: 1.25, im: -0.0625 }));
+    assert_eq!(try_parse_complex::<f64>("1.2.3,0.5"), bad("real", "1.2.3"));
+    assert_eq!(try_parse_complex::<f64>("0.5,1.2.3"), bad("imaginary", "1.2.3"));
+    assert_eq!(try_parse_complex::<f64>(",-0.0625"), bad("real", ""));
+    assert_eq!(try_parse_complex::<f64>("1.25,i"), bad("imaginary", "i"));
+    assert_eq!(try_parse_complex::<f64>("x,y"), bad("real", "x"));
+    assert_eq!(try_parse_complex::<f64>("1,2,3"), bad("imaginary", "2,3"));
+    assert_eq!(try_parse_complex::<f64>("1.5"), Err(ParseError::MissingComma));
+    assert_eq!(try_parse_complex(" -1.20, 0.35 "), Ok(Complex { re: -1.2, im: 0.35 }));
+    assert_eq!(try_parse_complex::<f64>("1 2,3"), bad("real", "1 2"));
+    assert_eq!(try_parse_complex::<f64>("1, - 2"), bad("imaginary", " - 2"));
+    assert_eq!(try_parse_complex::<f64>("0,nan"),
+               Err(ParseError::NotFinite { part: "imaginary", text: "nan".to_string() }));
+    assert_eq!(try_parse_complex::<f32>("1e39,0"),
+               Err(ParseError::NotFinite { part: "real", text: "1e39".to_string() }));
+}
+
+#[test]
+fn test_parse_dimensions() {
+
+    assert_eq!(parse_dimensions("1024x768").unwrap(), (1024, 768));
+    assert!(matches!(parse_dimensions("1024,768"),
+                     Err(MandelError::BadDimensions(ref value)) if value == "1024,768"));
+    assert!(matches!(parse_dimensions("0x0"), Err(MandelError::BadDimensions(_))));
+    assert!(matches!(parse_dimensions("1024x0"), Err(MandelError::BadDimensions(_))));
+}
+
+#[test]
+fn test_parse_point() {
+
+    assert_eq!(parse_point("-1.5,0.5", "center").unwrap(), Complex { re: -1.5, im: 0.5 });
+    assert!(matches!(parse_point::<f64>("-1.5", "center"),
+                     Err(MandelError::BadComplex { argument: "center", ref value, .. }) if value == "-1.5"));
+    assert!(matches!(parse_point::<f64>("1.2.3,0.5", "center"),
+                     Err(MandelError::BadComplex { reason: ParseError::BadPart { part: "real", .. }, .. })));
+
+    for value in ["inf,0", "0,nan", "1e400,0"] {
+        assert!(matches!(parse_point::<f64>(value, "upper-left corner"),
+                         Err(MandelError::NotFinite { argument: "upper-left corner", .. })));
+    }
+}
+
+#[test]
+fn test_parse_color() {
+
+    assert_eq!(parse_color("255,128,0"), Some([255, 128, 0]));
+    assert_eq!(parse_color(" 1, 2 ,3 "), Some([1, 2, 3]));
+    for bad in ["1,2", "1,2,3,4", "1,2,256", "1,-2,3", "red", ""] {
+        assert_eq!(parse_color(bad), None, "{:?} should be rejected", bad);
+    }
+}
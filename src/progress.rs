@@ -0,0 +1,88 @@
+//! Reporting how far through a render we are.
+
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A count of completed rows, shared by all the rendering threads, that
+/// prints the completed percentage to stderr as it rises.
+///
+/// The line is rewritten in place with `\r`, and only when the whole-number
+/// percentage changes, so there are at most 101 updates however large the
+/// image is. Nothing is ever written to stdout.
+pub struct Progress {
+    total: usize,
+    done: AtomicUsize,
+    reported: AtomicUsize,
+    visible: bool
+}
+
+impl Progress {
+
+    /// Return a `Progress` for a render of `total` rows that reports to stderr.
+    pub fn new(total : usize) -> Progress {
+        Progress { total, done: AtomicUsize::new(0), reported: AtomicUsize::new(0), visible: true }
+    }
+
+    /// Return a `Progress` that counts rows but never prints anything.
+    pub fn hidden(total : usize) -> Progress {
+        Progress { visible: false, ..Progress::new(total) }
+    }
+
+    /// Record that one more row is finished, updating the display if the
+    /// percentage has moved on.
+    pub fn row_done(&self) {
+
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if !self.visible {
+            return;
+        }
+
+        // Only the thread that moves `reported` forward prints, so no
+        // percentage is shown twice. A stale update may lose the race and be
+        // skipped, which is fine: a later row reports a newer figure.
+        let now = percent(done, self.total);
+        let before = self.reported.load(Ordering::Relaxed);
+        if now > before &&
+            self.reported.compare_exchange(before, now, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            let _ = write!(std::io::stderr(), "\rrendering: {:3}%", now);
+        }
+    }
+
+    /// Return the number of rows finished so far.
+    pub fn completed(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    /// End the progress line, so later output starts on a fresh line.
+    pub fn finish(&self) {
+
+        if self.visible {
+            eprintln!("\rrendering: {:3}%", percent(self.completed(), self.total));
+        }
+    }
+}
+
+/// Return `done` as a whole-number percentage of `total`, rounding down.
+fn percent(done : usize, total : usize) -> usize {
+
+    (done.min(total) * 100).checked_div(total).unwrap_or(100)
+}
+
+#[test]
+fn test_percent() {
+
+    assert_eq!(percent(0, 768), 0);
+    assert_eq!(percent(767, 768), 99);
+    assert_eq!(percent(768, 768), 100);
+    assert_eq!(percent(3, 0), 100);
+}
+
+#[test]
+fn test_row_done_counts() {
+
+    let progress = Progress::hidden(10);
+    for _ in 0..4 {
+        progress.row_done();
+    }
+    assert_eq!(progress.completed(), 4);
+}
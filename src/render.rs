@@ -0,0 +1,1829 @@
+//! Mapping pixels onto the complex plane and rendering whole images.
+
+use num::{Complex, Float};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::color::{map_colors, map_gray, ColorScheme, Palette};
+use crate::double_double::DoubleDouble;
+use crate::error::MandelError;
+use crate::escape::{escape_time_simd, Fractal, Iteration};
+use crate::progress::Progress;
+use crate::random::Rng;
+use crate::view::PRESETS;
+
+/// Linearly interpolate between `a` and `b`: `t = 0` gives `a`, `t = 1` gives `b`.
+pub fn lerp<T : Float>(a : T, b : T, t : T) -> T {
+
+    a * (T::one() - t) + b * t
+}
+
+/// Like `lerp`, but with `t` clamped to `0..=1` first, so the result always
+/// lies between `a` and `b`, however far out `t` strays.
+pub fn lerp_clamped<T : Float>(a : T, b : T, t : T) -> T {
+
+    lerp(a, b, t.max(T::zero()).min(T::one()))
+}
+
+/// Given the row and column of a pixel in the output image, return the
+/// corresponding point on the complex plane.
+///
+/// `bounds` is a pair giving the width and height of the image in pixels.
+/// `pixel` is a (column, row) pair indicating a particular pixel in that image.
+/// The `upper_left` and `lower_right` parameters are points on the complex
+/// plane designating the area our image covers, in either `f32` or `f64`.
+pub fn pixel_to_point<T : Float>(bounds : (usize, usize),
+                                 pixel : (usize, usize),
+                                 upper_left : Complex<T>,
+                                 lower_right : Complex<T>) -> Complex<T> {
+
+    subpixel_to_point(bounds, pixel, (T::zero(), T::zero()), upper_left, lower_right)
+}
+
+/// Like `pixel_to_point`, but for the point `offset` of the way across and
+/// down `pixel`, where `(0, 0)` is its upper-left corner, the point
+/// `pixel_to_point` gives, and `(1, 1)` its lower-right one.
+pub fn subpixel_to_point<T : Float>(bounds : (usize, usize),
+                                    pixel : (usize, usize),
+                                    offset : (T, T),
+                                    upper_left : Complex<T>,
+                                    lower_right : Complex<T>) -> Complex<T> {
+
+    let fraction = |n : usize, offset : T, of : usize| (T::from(n).unwrap() + offset) / T::from(of).unwrap();
+
+    Complex{
+        re:lerp(upper_left.re, lower_right.re, fraction(pixel.0, offset.0, bounds.0)),
+        im:lerp(upper_left.im, lower_right.im, fraction(pixel.1, offset.1, bounds.1))
+    }
+}
+
+/// Like `pixel_to_point`, but in double-double, so the pixels of a view too
+/// narrow for `f64` to tell them apart still get points of their own. The
+/// corners are `f64`s, so the view can only be placed as finely as `f64`
+/// allows, but it can be up to about the image's width in pixels narrower.
+pub fn pixel_to_point_double_double(bounds : (usize, usize),
+                                    pixel : (usize, usize),
+                                    upper_left : Complex<f64>,
+                                    lower_right : Complex<f64>) -> Complex<DoubleDouble> {
+
+    let lerp = |a : f64, b : f64, n : usize, of : usize| {
+        DoubleDouble::from(a) + DoubleDouble::sum(b, -a) * n as f64 / of as f64
+    };
+
+    Complex {
+        re: lerp(upper_left.re, lower_right.re, pixel.0, bounds.0),
+        im: lerp(upper_left.im, lower_right.im, pixel.1, bounds.1)
+    }
+}
+
+/// The raw escape values for every pixel of an image, in row-major order,
+/// before any colors have been chosen.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Counts {
+    /// Whole iteration counts, from `Iteration::escape_time`.
+    Whole(Vec<Option<usize>>),
+    /// Fractional iteration counts, from `Iteration::escape_time_smooth`.
+    Smooth(Vec<Option<f64>>),
+    /// The closest each orbit came to an orbit trap, from
+    /// `Iteration::escape_time_trapped`.
+    Trapped(Vec<f64>),
+    /// Each point's estimated distance from the set, from
+    /// `Iteration::distance_estimate`, measured in pixels.
+    Distance(Vec<Option<f64>>),
+    /// Whole iteration counts, each with the last point of its orbit, from
+    /// `Iteration::escape_time_final`.
+    Final(Vec<(Option<usize>, Complex<f64>)>),
+    /// Whole iteration counts, each with the period of the cycle the orbit
+    /// settled into if it never escaped and one was found, from
+    /// `Iteration::escape_time_periodic`.
+    Periodic(Vec<(Option<usize>, Option<usize>)>)
+}
+
+impl Counts {
+
+    /// The number of pixels these counts cover.
+    pub fn len(&self) -> usize {
+
+        match self {
+            Counts::Whole(counts) => counts.len(),
+            Counts::Smooth(counts) => counts.len(),
+            Counts::Trapped(distances) => distances.len(),
+            Counts::Distance(distances) => distances.len(),
+            Counts::Final(orbits) => orbits.len(),
+            Counts::Periodic(orbits) => orbits.len()
+        }
+    }
+
+    /// Whether these counts cover no pixels at all.
+    pub fn is_empty(&self) -> bool {
+
+        self.len() == 0
+    }
+
+    /// Each pixel's raw value as a float: its count, whole or smooth, or its
+    /// distance, with `-1` for the points that never escaped.
+    pub fn values(&self) -> Vec<f32> {
+
+        let or_inside = |value : Option<f64>| value.map_or(-1.0, |value| value as f32);
+        match self {
+            Counts::Whole(counts) => counts.iter().map(|count| or_inside(count.map(|n| n as f64))).collect(),
+            Counts::Smooth(counts) => counts.iter().map(|&count| or_inside(count)).collect(),
+            Counts::Trapped(distances) => distances.iter().map(|&distance| distance as f32).collect(),
+            Counts::Distance(distances) => distances.iter().map(|&distance| or_inside(distance)).collect(),
+            Counts::Final(orbits) =>
+                orbits.iter().map(|(count, _)| or_inside(count.map(|n| n as f64))).collect(),
+            Counts::Periodic(orbits) =>
+                orbits.iter().map(|(count, _)| or_inside(count.map(|n| n as f64))).collect()
+        }
+    }
+
+    /// Return the counts of an image `width` pixels wide built from the rows
+    /// of these counts that `sources` names, in order, each turned upside
+    /// down across the real axis if it's marked as mirrored. Only the final
+    /// points of orbits change when mirrored; counts and distances don't.
+    fn select_rows(&self, width : usize, sources : &[(usize, bool)]) -> Counts {
+
+        fn pick<T : Clone>(values : &[T], width : usize, sources : &[(usize, bool)],
+                           mirror : impl Fn(&T) -> T) -> Vec<T> {
+
+            let mirror = &mirror;
+            sources.iter()
+                .flat_map(|&(y, mirrored)| values[y * width..(y + 1) * width].iter()
+                    .map(move |value| if mirrored { mirror(value) } else { value.clone() }))
+                .collect()
+        }
+
+        match self {
+            Counts::Whole(counts) => Counts::Whole(pick(counts, width, sources, Clone::clone)),
+            Counts::Smooth(counts) => Counts::Smooth(pick(counts, width, sources, Clone::clone)),
+            Counts::Trapped(distances) => Counts::Trapped(pick(distances, width, sources, Clone::clone)),
+            Counts::Distance(distances) => Counts::Distance(pick(distances, width, sources, Clone::clone)),
+            Counts::Final(orbits) =>
+                Counts::Final(pick(orbits, width, sources, |&(count, z)| (count, z.conj()))),
+            Counts::Periodic(orbits) => Counts::Periodic(pick(orbits, width, sources, Clone::clone))
+        }
+    }
+
+    /// Remove every pixel's counts, keeping the kind of counts and the memory
+    /// they took, so the same amount more can be added without allocating.
+    pub fn clear(&mut self) {
+
+        match self {
+            Counts::Whole(counts) => counts.clear(),
+            Counts::Smooth(counts) => counts.clear(),
+            Counts::Trapped(distances) => distances.clear(),
+            Counts::Distance(distances) => distances.clear(),
+            Counts::Final(orbits) => orbits.clear(),
+            Counts::Periodic(orbits) => orbits.clear()
+        }
+    }
+
+    /// Append the counts of `more`, which must be of the same kind, after
+    /// these, as if for the rows below them.
+    pub fn extend(&mut self, more : Counts) {
+
+        match (self, more) {
+            (Counts::Whole(counts), Counts::Whole(more)) => counts.extend(more),
+            (Counts::Smooth(counts), Counts::Smooth(more)) => counts.extend(more),
+            (Counts::Trapped(distances), Counts::Trapped(more)) => distances.extend(more),
+            (Counts::Distance(distances), Counts::Distance(more)) => distances.extend(more),
+            (Counts::Final(orbits), Counts::Final(more)) => orbits.extend(more),
+            (Counts::Periodic(orbits), Counts::Periodic(more)) => orbits.extend(more),
+            _ => panic!("can't extend counts with counts of a different kind")
+        }
+    }
+}
+
+/// Compute the escape count of every pixel in a rectangle of the Mandelbrot
+/// set.
+///
+/// The `bounds` argument gives the width and height of the image in pixels.
+/// The `upper_left` and `lower_right` arguments specify points on the complex
+/// plane corresponding to the upper-left and lower-right corners of the image.
+/// Each point is evaluated by `iteration`, giving distance estimates if
+/// `iteration.distance` is set, trap distances if `iteration.trap` is set,
+/// smooth counts if `iteration.smooth` is set, and
+/// whole counts otherwise. Pass the result to
+/// `map_colors` to turn it into pixels.
+///
+/// Rows are rendered in parallel with rayon; each row is independent. Call
+/// this from `ThreadPool::install` to control how many threads it uses. Each
+/// thread takes the next unrendered row from a shared counter as it finishes
+/// one, so the work stays evenly divided however many threads there are, even
+/// when the rows through the set take far longer than those around it.
+///
+/// On a view that straddles the real axis, rows below it that mirror rows
+/// above it exactly are copied from them rather than evaluated, when
+/// `Iteration::is_symmetric` says their counts must match, so a view
+/// centered on the axis costs little more than its upper half.
+pub fn render(bounds : (usize, usize),
+        upper_left : Complex<f64>,
+        lower_right : Complex<f64>,
+        iteration : Iteration) -> Counts {
+
+    let progress = Progress::hidden(bounds.1);
+    render_with_progress(bounds, upper_left, lower_right, iteration, &progress)
+}
+
+/// Like `render`, but call `progress.row_done()` as each row is finished.
+pub fn render_with_progress(bounds : (usize, usize),
+        upper_left : Complex<f64>,
+        lower_right : Complex<f64>,
+        iteration : Iteration,
+        progress : &Progress) -> Counts {
+
+    if iteration.iterates_double_double() {
+        return render_double_double(bounds, upper_left, lower_right, iteration, progress);
+    }
+
+    let (re, im) = pixel_coordinates(bounds, upper_left, lower_right);
+    let pixel_size = ((lower_right.re - upper_left.re) / bounds.0 as f64).abs();
+
+    // A row whose imaginary part is exactly the negation of one above it
+    // mirrors that row's counts, so only the rows above the real axis need
+    // evaluating on a view that straddles it.
+    let sources = mirror_sources(&im, iteration);
+    if sources.iter().all(|&(_, mirrored)| !mirrored) {
+        return render_grid(&re, &im, pixel_size, iteration, progress);
+    }
+
+    let evaluated : Vec<f64> = im.iter().zip(&sources)
+        .filter(|(_, &(_, mirrored))| !mirrored)
+        .map(|(&im, _)| im)
+        .collect();
+    let counts = render_grid(&re, &evaluated, pixel_size, iteration, progress);
+    for _ in evaluated.len()..im.len() {
+        progress.row_done();
+    }
+    counts.select_rows(bounds.0, &sources)
+}
+
+/// Like `render_with_progress`, for the settings that
+/// `Iteration::iterates_double_double` is true of, but with each pixel's
+/// point from `pixel_to_point_double_double`. No row is mirrored from
+/// another, since a view deep enough to need this is far too narrow to be
+/// centered on the real axis.
+fn render_double_double(bounds : (usize, usize),
+                        upper_left : Complex<f64>,
+                        lower_right : Complex<f64>,
+                        iteration : Iteration,
+                        progress : &Progress) -> Counts {
+
+    let point = |x, y| pixel_to_point_double_double(bounds, (x, y), upper_left, lower_right);
+    let re : Vec<DoubleDouble> = (0..bounds.0).map(|x| point(x, 0).re).collect();
+    let im : Vec<DoubleDouble> = (0..bounds.1).map(|y| point(0, y).im).collect();
+
+    // `fill_rows` hands out `f64` coordinates, so it's given each pixel's
+    // column and row instead, to look its point up by.
+    let columns : Vec<f64> = (0..bounds.0).map(|x| x as f64).collect();
+    let rows : Vec<f64> = (0..bounds.1).map(|y| y as f64).collect();
+    Counts::Whole(fill_rows(&columns, &rows, None, iteration.tile_size, progress, |counts, columns, y| {
+        for (count, &x) in counts.iter_mut().zip(columns) {
+            *count = iteration.escape_time_double_double(Complex { re: re[x as usize], im: im[y as usize] });
+        }
+    }))
+}
+
+/// Like `render_with_progress`, but only evaluate the pixels `mask` marks,
+/// one for each pixel in row-major order; the rest get the default value of
+/// their kind of counts, as if in the set with a whole count, and cost
+/// nothing. The marked pixels are evaluated just as `render_with_progress`
+/// would, in tiles and with SIMD, but no row is mirrored from another, since
+/// the mask needn't be symmetric however the view is.
+pub fn render_masked(bounds : (usize, usize),
+                     upper_left : Complex<f64>,
+                     lower_right : Complex<f64>,
+                     iteration : Iteration,
+                     mask : &[bool],
+                     progress : &Progress) -> Counts {
+
+    assert_eq!(mask.len(), bounds.0 * bounds.1, "mask doesn't cover the image");
+    let (re, im) = pixel_coordinates(bounds, upper_left, lower_right);
+    let pixel_size = ((lower_right.re - upper_left.re) / bounds.0 as f64).abs();
+    render_grid_masked(&re, &im, Some(mask), pixel_size, iteration, progress)
+}
+
+/// For each row of a grid whose rows have imaginary parts `im`, return the
+/// row, among only the rows that aren't mirrored, to take its counts from,
+/// and whether to mirror them. A row is mirrored from the row `im.len() - y`
+/// when that one lies above it and `iteration` is symmetric across the real
+/// axis, as `pixel_coordinates` places the rows of a view centered on it.
+fn mirror_sources(im : &[f64], iteration : Iteration) -> Vec<(usize, bool)> {
+
+    let height = im.len();
+    let symmetric = iteration.is_symmetric();
+    let mut evaluated = 0;
+    let mut sources = Vec::with_capacity(height);
+
+    for y in 0..height {
+        let above = height - y;
+        if symmetric && y > 0 && above < y && im[above] == -im[y] {
+            // Rows are evaluated in order, so the row above already has its
+            // place among the evaluated rows.
+            let (source, _) = sources[above];
+            sources.push((source, true));
+        } else {
+            sources.push((evaluated, false));
+            evaluated += 1;
+        }
+    }
+
+    sources
+}
+
+/// Like `render_with_progress`, but place each pixel's point at a random
+/// offset within it, drawn from `rng`, instead of at its upper-left corner.
+///
+/// Averaging the colors of several such renders, each with fresh offsets,
+/// anti-aliases the image without the regular grid of supersampling. The
+/// offsets are drawn for each column and each row, as `pixel_coordinates`
+/// computes them, so every pixel gets its own, while the rows keep the shape
+/// the fast paths of `render` rely on. The same `rng` state always gives the
+/// same counts.
+pub fn render_jittered(bounds : (usize, usize),
+        upper_left : Complex<f64>,
+        lower_right : Complex<f64>,
+        iteration : Iteration,
+        rng : &mut Rng,
+        progress : &Progress) -> Counts {
+
+    let re = (0..bounds.0)
+        .map(|x| subpixel_to_point(bounds, (x, 0), (rng.next_f64(), 0.0), upper_left, lower_right).re)
+        .collect::<Vec<_>>();
+    let im = (0..bounds.1)
+        .map(|y| subpixel_to_point(bounds, (0, y), (0.0, rng.next_f64()), upper_left, lower_right).im)
+        .collect::<Vec<_>>();
+    let pixel_size = ((lower_right.re - upper_left.re) / bounds.0 as f64).abs();
+    render_grid(&re, &im, pixel_size, iteration, progress)
+}
+
+/// Like `render_with_progress`, but render the image `band` rows at a time,
+/// from the top down, and after each band but the last, call `checkpoint`
+/// with the counts of all the rows rendered so far. `band` must be positive.
+///
+/// Each band is rendered in parallel as usual, but `checkpoint` is only
+/// called from this thread, between bands, so it needn't worry about the
+/// render changing the counts under it. The counts come out exactly as
+/// `render_with_progress` would give them. If `checkpoint` returns an error,
+/// the render stops there and returns it.
+pub fn render_with_checkpoints<F>(bounds : (usize, usize),
+        upper_left : Complex<f64>,
+        lower_right : Complex<f64>,
+        iteration : Iteration,
+        band : usize,
+        progress : &Progress,
+        mut checkpoint : F) -> Result<Counts, MandelError>
+    where F : FnMut(&Counts) -> Result<(), MandelError>
+{
+    let (re, im) = pixel_coordinates(bounds, upper_left, lower_right);
+    let pixel_size = ((lower_right.re - upper_left.re) / bounds.0 as f64).abs();
+
+    let mut counts = render_grid(&re, &[], pixel_size, iteration, progress);
+    let mut bands = im.chunks(band).peekable();
+    while let Some(rows) = bands.next() {
+        counts.extend(render_grid(&re, rows, pixel_size, iteration, progress));
+        if bands.peek().is_some() {
+            checkpoint(&counts)?;
+        }
+    }
+
+    Ok(counts)
+}
+
+/// How many times over along each side `render_antialiased_edges`
+/// supersamples the pixels on edges.
+pub const EDGE_SUPERSAMPLE : usize = 3;
+
+/// Like `render_with_progress`, but return the image colored by `color`,
+/// with only its edges supersampled: the pixels whose value from
+/// `Counts::values` differs from any of its eight neighbours' by more than
+/// `threshold` are rendered again as `EDGE_SUPERSAMPLE` x `EDGE_SUPERSAMPLE`
+/// subpixels, which are colored together and averaged back into them,
+/// rounding to the nearest value.
+///
+/// The subpixels are the very points supersampling by that factor would
+/// use, so the edges come out as a full supersampled render would show them,
+/// while the smooth areas between them cost nothing extra. `color` must return the
+/// same number of samples for each pixel it's given, as `map_colors` and
+/// `map_gray16` do.
+pub fn render_antialiased_edges<T, F>(bounds : (usize, usize),
+        upper_left : Complex<f64>,
+        lower_right : Complex<f64>,
+        iteration : Iteration,
+        threshold : f64,
+        progress : &Progress,
+        color : F) -> Vec<T>
+    where T : Copy + Into<u64> + TryFrom<u64>, F : Fn(&Counts) -> Vec<T>
+{
+    let factor = EDGE_SUPERSAMPLE;
+    let counts = render_with_progress(bounds, upper_left, lower_right, iteration, progress);
+    let mut pixels = color(&counts);
+    let channels = pixels.len() / (bounds.0 * bounds.1).max(1);
+
+    let edges = find_edges(&counts.values(), bounds, threshold);
+    let fine = (bounds.0 * factor, bounds.1 * factor);
+    let (re, im) = pixel_coordinates(fine, upper_left, lower_right);
+    let pixel_size = ((lower_right.re - upper_left.re) / fine.0 as f64).abs();
+
+    // The subpixels of each row's edge pixels make one grid, `factor` rows
+    // high, so they're still rendered a whole row at a time.
+    let rows : Vec<&[(usize, usize)]> = edges.chunk_by(|a, b| a.1 == b.1).collect();
+    let hidden = Progress::hidden(rows.len() * factor);
+    let grids : Vec<Counts> = rows.par_iter()
+        .map(|row| {
+            let columns : Vec<f64> = row.iter()
+                .flat_map(|&(x, _)| re[x * factor..(x + 1) * factor].iter().copied())
+                .collect();
+            let y = row[0].1;
+            render_grid(&columns, &im[y * factor..(y + 1) * factor], pixel_size, iteration, &hidden)
+        })
+        .collect();
+    let mut subpixels = render_grid(&re, &[], pixel_size, iteration, &hidden);
+    for grid in grids {
+        subpixels.extend(grid);
+    }
+
+    // Each grid's samples are in row-major order, so a pixel's block is
+    // `factor` runs of `factor` pixels, a grid row apart.
+    let samples = (factor * factor) as u64;
+    let colored = color(&subpixels);
+    let mut start = 0;
+    for row in rows {
+        let width = row.len() * factor * channels;
+        for (i, &(x, y)) in row.iter().enumerate() {
+            for c in 0..channels {
+                let sum : u64 = (0..factor)
+                    .flat_map(|sy| (0..factor).map(move |sx| sy * width + (i * factor + sx) * channels + c))
+                    .map(|index| colored[start + index].into())
+                    .sum();
+                let average = T::try_from((sum + samples / 2) / samples).ok().unwrap();
+                pixels[(y * bounds.0 + x) * channels + c] = average;
+            }
+        }
+        start += width * factor;
+    }
+
+    pixels
+}
+
+/// Return the `(column, row)` of every pixel of an image of `bounds` pixels
+/// whose value in `values` differs from any of its neighbours', including
+/// the diagonal ones, by more than `threshold`, in row-major order.
+fn find_edges(values : &[f32], bounds : (usize, usize), threshold : f64) -> Vec<(usize, usize)> {
+
+    let (width, height) = bounds;
+    let differs = |x : usize, y : usize| {
+        let value = values[y * width + x] as f64;
+        (y.saturating_sub(1)..(y + 2).min(height)).any(|ny| {
+            (x.saturating_sub(1)..(x + 2).min(width))
+                .any(|nx| (values[ny * width + nx] as f64 - value).abs() > threshold)
+        })
+    };
+
+    (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).filter(|&(x, y)| differs(x, y)).collect()
+}
+
+/// An image rendered a few rows at a time, for a viewer that wants to show
+/// the rows done so far while the rest are still being computed. A viewer
+/// rendering frame after frame can `reset` it to each new view, and the
+/// buffers it owns are reused rather than allocated afresh.
+pub struct Renderer {
+    /// The width and height of the image in pixels.
+    bounds: (usize, usize),
+
+    /// How each point is evaluated.
+    iteration: Iteration,
+
+    /// The real part of each column's points, and the imaginary part of
+    /// each row's, from `pixel_coordinates`.
+    re: Vec<f64>,
+    im: Vec<f64>,
+
+    /// The width of a pixel on the complex plane, for distance estimates.
+    pixel_size: f64,
+
+    /// The number of rows rendered so far, from the top.
+    row: usize,
+
+    /// The counts of those rows.
+    counts: Counts,
+
+    /// The whole image's shades, black below `row`.
+    pixels: Vec<u8>
+}
+
+impl Renderer {
+
+    /// Prepare to render the rectangle from `upper_left` to `lower_right` as
+    /// an image of `bounds` pixels, as `iteration` says, without rendering
+    /// any of it yet.
+    pub fn new(bounds : (usize, usize),
+               upper_left : Complex<f64>,
+               lower_right : Complex<f64>,
+               iteration : Iteration) -> Renderer {
+
+        let (re, im) = pixel_coordinates(bounds, upper_left, lower_right);
+        let pixel_size = ((lower_right.re - upper_left.re) / bounds.0 as f64).abs();
+        let counts = render_grid(&re, &[], pixel_size, iteration, &Progress::hidden(0));
+
+        Renderer { bounds, iteration, re, im, pixel_size, row: 0, counts,
+                   pixels: vec![0; bounds.0 * bounds.1] }
+    }
+
+    /// Render the next `rows` rows, or as many as are left, in parallel, and
+    /// return whether any rows remain after them.
+    pub fn step(&mut self, rows : usize) -> bool {
+
+        let end = (self.row + rows).min(self.bounds.1);
+        let band = render_grid(&self.re, &self.im[self.row..end], self.pixel_size, self.iteration,
+                               &Progress::hidden(end - self.row));
+
+        let shades = map_gray(&band, self.iteration.limit);
+        self.pixels[self.row * self.bounds.0..end * self.bounds.0].copy_from_slice(&shades);
+        self.counts.extend(band);
+        self.row = end;
+
+        !self.is_done()
+    }
+
+    /// Render every row that's left, and return the whole image, as `pixels`
+    /// does.
+    pub fn render(&mut self) -> &[u8] {
+
+        while self.step(self.bounds.1) {}
+        &self.pixels
+    }
+
+    /// Start over on the rectangle from `upper_left` to `lower_right`, as an
+    /// image of `bounds` pixels, evaluated as before, without rendering any
+    /// of it yet. The pixel buffer is resized by `resize_if_needed`, so it
+    /// only grows when the new image is larger than any before it.
+    pub fn reset(&mut self, bounds : (usize, usize), upper_left : Complex<f64>, lower_right : Complex<f64>) {
+
+        pixel_coordinates_into(&mut self.re, &mut self.im, bounds, upper_left, lower_right);
+        self.pixel_size = ((lower_right.re - upper_left.re) / bounds.0 as f64).abs();
+        self.row = 0;
+        self.counts.clear();
+        self.resize_if_needed(bounds);
+        self.pixels.fill(0);
+    }
+
+    /// Make the pixel buffer hold exactly one byte for each of `bounds`
+    /// pixels, and take `bounds` as the image's size. A buffer of the right
+    /// length is left alone, and a longer one is shortened in place, so only
+    /// a larger image than the buffer has held before allocates. Return
+    /// whether the length had to change.
+    pub fn resize_if_needed(&mut self, bounds : (usize, usize)) -> bool {
+
+        self.bounds = bounds;
+        let len = bounds.0 * bounds.1;
+        if self.pixels.len() == len {
+            return false;
+        }
+        self.pixels.resize(len, 0);
+        true
+    }
+
+    /// Whether every row has been rendered.
+    pub fn is_done(&self) -> bool {
+
+        self.row == self.bounds.1
+    }
+
+    /// The counts of the rows rendered so far.
+    pub fn counts(&self) -> &Counts {
+
+        &self.counts
+    }
+
+    /// The whole image as grayscale, one byte per pixel as `map_gray` shades
+    /// them, with the rows not yet rendered black.
+    pub fn pixels(&self) -> &[u8] {
+
+        &self.pixels
+    }
+}
+
+/// The settings for a render, gathered one at a time and then rendered to RGB
+/// pixels with `render`. Whatever isn't set is as the command-line tool has
+/// it when no flags are given, and the view is the `"full"` preset's.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderBuilder {
+    /// The width and height of the image in pixels.
+    bounds: (usize, usize),
+
+    /// The corners of the rectangle on the complex plane to render.
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+
+    /// The most iterations to spend on each point.
+    limit: usize,
+
+    /// The colors to use for escaping points.
+    palette: Palette,
+
+    /// Which fractal to iterate.
+    fractal: Fractal,
+
+    /// How many threads to render with, or zero for one per logical core.
+    threads: usize
+}
+
+/// The size `RenderBuilder` renders at, unless it's given another.
+pub const DEFAULT_RENDER_BOUNDS : (usize, usize) = (800, 600);
+
+impl RenderBuilder {
+
+    /// Start from the defaults: a `DEFAULT_RENDER_BOUNDS` grayscale image of
+    /// the whole Mandelbrot set, 255 iterations deep, on every core.
+    pub fn new() -> RenderBuilder {
+
+        let full = &PRESETS[0];
+        RenderBuilder { bounds: DEFAULT_RENDER_BOUNDS, upper_left: full.upper_left,
+                        lower_right: full.lower_right, limit: full.limit, palette: Palette::Grayscale,
+                        fractal: Fractal::Mandelbrot, threads: 0 }
+    }
+
+    /// Render an image of `bounds` pixels, width first.
+    pub fn bounds(mut self, bounds : (usize, usize)) -> RenderBuilder {
+
+        self.bounds = bounds;
+        self
+    }
+
+    /// Render the rectangle from `upper_left` to `lower_right`.
+    pub fn corners(mut self, upper_left : Complex<f64>, lower_right : Complex<f64>) -> RenderBuilder {
+
+        self.upper_left = upper_left;
+        self.lower_right = lower_right;
+        self
+    }
+
+    /// Iterate each point at most `limit` times.
+    pub fn limit(mut self, limit : usize) -> RenderBuilder {
+
+        self.limit = limit;
+        self
+    }
+
+    /// Color escaping points from `palette`.
+    pub fn palette(mut self, palette : Palette) -> RenderBuilder {
+
+        self.palette = palette;
+        self
+    }
+
+    /// Iterate `fractal`'s step instead of the Mandelbrot set's.
+    pub fn fractal(mut self, fractal : Fractal) -> RenderBuilder {
+
+        self.fractal = fractal;
+        self
+    }
+
+    /// Render on `threads` threads, or one per logical core if it's zero.
+    pub fn threads(mut self, threads : usize) -> RenderBuilder {
+
+        self.threads = threads;
+        self
+    }
+
+    /// Render the image and color it, returning three bytes per pixel as
+    /// `map_colors` does. If a pool of `threads` threads can't be started,
+    /// render on rayon's global pool instead; the pixels come out the same.
+    pub fn render(&self) -> Vec<u8> {
+
+        let iteration = Iteration { limit: self.limit, fractal: self.fractal, ..Iteration::default() };
+        let scheme = ColorScheme { palette: self.palette, ..ColorScheme::default() };
+        let draw = || render(self.bounds, self.upper_left, self.lower_right, iteration);
+
+        let pool = if self.threads > 0 { thread_pool(self.threads).ok() } else { None };
+        let counts = match pool {
+            Some(pool) => pool.install(draw),
+            None => draw()
+        };
+        map_colors(&counts, self.limit, &scheme)
+    }
+}
+
+impl Default for RenderBuilder {
+
+    fn default() -> RenderBuilder {
+        RenderBuilder::new()
+    }
+}
+
+/// Return the escape counts of row `y` alone of an image of `bounds` pixels
+/// covering the rectangle from `upper_left` to `lower_right`, evaluating each
+/// point `pixel_to_point` gives with `iteration.escape_time`, one at a time.
+/// None of `render`'s faster paths are taken, so this is a plain reference to
+/// check a row of a full render against. The row must lie within the image.
+pub fn render_scanline(bounds : (usize, usize),
+        y : usize,
+        upper_left : Complex<f64>,
+        lower_right : Complex<f64>,
+        iteration : Iteration) -> Vec<Option<usize>> {
+
+    assert!(y < bounds.1, "row {} is outside an image {} rows high", y, bounds.1);
+
+    (0..bounds.0)
+        .map(|x| iteration.escape_time(pixel_to_point(bounds, (x, y), upper_left, lower_right)))
+        .collect()
+}
+
+/// Like `render`, but hand each row's shades, in order from the top, to
+/// `consume` along with the row's index, rather than keeping the whole image.
+///
+/// The shades are those `map_gray` gives. Rows are computed a band at a
+/// time, one row for each thread, in parallel; only one band of counts and
+/// shades is held at once, however tall the image.
+pub fn render_rows<F>(bounds : (usize, usize),
+        upper_left : Complex<f64>,
+        lower_right : Complex<f64>,
+        iteration : Iteration,
+        mut consume : F)
+    where F : FnMut(usize, &[u8])
+{
+    let (re, im) = pixel_coordinates(bounds, upper_left, lower_right);
+    let pixel_size = ((lower_right.re - upper_left.re) / bounds.0 as f64).abs();
+    let band = rayon::current_num_threads().max(1);
+
+    for (n, rows) in im.chunks(band).enumerate() {
+        let counts = render_grid(&re, rows, pixel_size, iteration, &Progress::hidden(rows.len()));
+        let shades = map_gray(&counts, iteration.limit);
+        for (y, row) in shades.chunks(bounds.0.max(1)).enumerate() {
+            consume(n * band + y, row);
+        }
+    }
+}
+
+/// Like `render`, but compute only the `tile_size` pixels whose upper-left
+/// pixel is `tile_origin`, out of a full image of `full_bounds` pixels.
+///
+/// Points are placed against the full image, so tiles rendered separately,
+/// even by different processes, fit back together into exactly the image
+/// `render` would have produced. The tile must lie within the image.
+pub fn render_tile(full_bounds : (usize, usize),
+        tile_origin : (usize, usize),
+        tile_size : (usize, usize),
+        upper_left : Complex<f64>,
+        lower_right : Complex<f64>,
+        iteration : Iteration) -> Counts {
+
+    assert!(tile_origin.0 + tile_size.0 <= full_bounds.0 && tile_origin.1 + tile_size.1 <= full_bounds.1,
+            "tile extends past the edge of the image");
+
+    let (re, im) = pixel_coordinates(full_bounds, upper_left, lower_right);
+    let pixel_size = ((lower_right.re - upper_left.re) / full_bounds.0 as f64).abs();
+    let re = &re[tile_origin.0..tile_origin.0 + tile_size.0];
+    let im = &im[tile_origin.1..tile_origin.1 + tile_size.1];
+
+    render_grid(re, im, pixel_size, iteration, &Progress::hidden(tile_size.1))
+}
+
+/// Like `render`, but skip the points in regions the escape counts don't
+/// change across, returning whole counts along with the number of points it
+/// evaluated.
+///
+/// Every `coarse_factor`th pixel across and down is evaluated first, along
+/// with the last column and row, splitting the image into cells. Where the
+/// corners of a cell and of the cells around it all have the same count, the
+/// cell is filled with that count; every other cell has each of its pixels
+/// evaluated. Uniform regions are cheap that way, and the work goes to the
+/// boundary. A feature that slips between the grid points, like a filament
+/// thinner than a cell, may be painted over, so the result can differ from
+/// `render`'s in a few pixels.
+///
+/// Counts are always whole, from `Iteration::escape_time`, whatever
+/// `iteration` says about smooth counts, traps or distances.
+pub fn render_adaptive(bounds : (usize, usize),
+        upper_left : Complex<f64>,
+        lower_right : Complex<f64>,
+        iteration : Iteration,
+        coarse_factor : usize) -> (Counts, usize) {
+
+    assert!(coarse_factor > 0, "coarse factor must be positive");
+
+    let (re, im) = pixel_coordinates(bounds, upper_left, lower_right);
+    let escape = |x : usize, y : usize| iteration.escape_time(Complex { re: re[x], im: im[y] });
+
+    let grid_lines = |len : usize| {
+        let mut lines : Vec<usize> = (0..len).step_by(coarse_factor).collect();
+        if len > 0 && lines.last() != Some(&(len - 1)) {
+            lines.push(len - 1);
+        }
+        lines
+    };
+    let (xs, ys) = (grid_lines(bounds.0), grid_lines(bounds.1));
+
+    // With a single grid line along either side, there are no cells to fill.
+    if xs.len() < 2 || ys.len() < 2 {
+        let counts : Vec<_> = (0..bounds.0 * bounds.1).map(|i| escape(i % bounds.0, i / bounds.0)).collect();
+        let evaluated = counts.len();
+        return (Counts::Whole(counts), evaluated);
+    }
+
+    let coarse : Vec<Option<usize>> = ys.par_iter()
+        .flat_map_iter(|&y| xs.iter().map(move |&x| escape(x, y)))
+        .collect();
+    let node = |i : usize, j : usize| coarse[j * xs.len() + i];
+
+    // A cell is uniform if the grid points of the 3 x 3 cells centered on it
+    // all agree.
+    let cells = (xs.len() - 1, ys.len() - 1);
+    let uniform : Vec<bool> = (0..cells.0 * cells.1).into_par_iter()
+        .map(|cell| {
+            let (i, j) = (cell % cells.0, cell / cells.0);
+            let count = node(i, j);
+            (j.saturating_sub(1)..=(j + 2).min(cells.1)).all(|j| {
+                (i.saturating_sub(1)..=(i + 2).min(cells.0)).all(|i| node(i, j) == count)
+            })
+        })
+        .collect();
+
+    // Pixels on the last grid line belong to the cells before it.
+    let cell_of = |lines : &[usize], v : usize| {
+        lines.partition_point(|&line| line <= v).min(lines.len() - 1) - 1
+    };
+
+    let mut counts = vec![None; bounds.0 * bounds.1];
+    let refined : usize = counts.par_chunks_mut(bounds.0)
+        .enumerate()
+        .map(|(y, row)| {
+            let j = cell_of(&ys, y);
+            let on_row = ys[j] == y || ys[j + 1] == y;
+            let mut evaluated = 0;
+            for (x, count) in row.iter_mut().enumerate() {
+                let i = cell_of(&xs, x);
+                *count = if on_row && (xs[i] == x || xs[i + 1] == x) {
+                    node(if xs[i] == x { i } else { i + 1 }, if ys[j] == y { j } else { j + 1 })
+                } else if uniform[j * cells.0 + i] {
+                    node(i, j)
+                } else {
+                    evaluated += 1;
+                    escape(x, y)
+                };
+            }
+            evaluated
+        })
+        .sum();
+
+    (Counts::Whole(counts), coarse.len() + refined)
+}
+
+/// How many rows `render_mariani_silver` traces at a time. Each band is
+/// traced separately, so the bands can be rendered in parallel.
+const BAND_HEIGHT : usize = 64;
+
+/// Like `render_with_progress`, but skip the insides of rectangles whose
+/// borders all have the same count, returning whole counts along with the
+/// number of points it evaluated.
+///
+/// This is the Mariani-Silver algorithm. The set is connected, and so is the
+/// region of each escape count, so if every pixel around the edge of a
+/// rectangle has the same count, every pixel within it should too, and it is
+/// filled without evaluating them. Otherwise the rectangle is split in two
+/// across its longer side, and each half is traced in turn, until it is no
+/// more than `min_size` pixels across or down, when each of its pixels is
+/// evaluated. The image is traced in bands of rows, in parallel.
+///
+/// The pixels pass through a grid of points, not the continuous plane, so a
+/// feature thinner than a pixel can slip through a border unseen, and be
+/// filled over. Counts are always whole, from `Iteration::escape_time`,
+/// whatever `iteration` says about smooth counts, traps or distances.
+pub fn render_mariani_silver(bounds : (usize, usize),
+        upper_left : Complex<f64>,
+        lower_right : Complex<f64>,
+        iteration : Iteration,
+        min_size : usize,
+        progress : &Progress) -> (Counts, usize) {
+
+    let (re, im) = pixel_coordinates(bounds, upper_left, lower_right);
+
+    let mut counts = vec![None; bounds.0 * bounds.1];
+    let evaluated = counts.par_chunks_mut((bounds.0 * BAND_HEIGHT).max(1))
+        .enumerate()
+        .map(|(band, counts)| {
+            let top = band * BAND_HEIGHT;
+            let rows = counts.len() / bounds.0;
+            let mut tracer = Tracer {
+                known: vec![false; counts.len()],
+                counts,
+                width: bounds.0,
+                evaluated: 0,
+                escape: |x : usize, y : usize| iteration.escape_time(Complex { re: re[x], im: im[top + y] })
+            };
+            tracer.trace((0, 0), (bounds.0 - 1, rows - 1), min_size.max(1));
+            for _ in 0..rows {
+                progress.row_done();
+            }
+            tracer.evaluated
+        })
+        .sum();
+
+    (Counts::Whole(counts), evaluated)
+}
+
+/// The state of `render_mariani_silver` as it traces one band of rows.
+struct Tracer<'a, F> {
+    /// The band's counts, in row-major order.
+    counts: &'a mut [Option<usize>],
+
+    /// Whether each of `counts` has been evaluated or filled in yet.
+    known: Vec<bool>,
+
+    /// The number of pixels in each row.
+    width: usize,
+
+    /// The number of points evaluated so far.
+    evaluated: usize,
+
+    /// Evaluates the point of the pixel in a column and row of the band.
+    escape: F
+}
+
+impl<F : Fn(usize, usize) -> Option<usize>> Tracer<'_, F> {
+
+    /// Return the count of the pixel at `(x, y)`, evaluating it if it isn't
+    /// known yet.
+    fn count(&mut self, x : usize, y : usize) -> Option<usize> {
+
+        let i = y * self.width + x;
+        if !self.known[i] {
+            self.counts[i] = (self.escape)(x, y);
+            self.known[i] = true;
+            self.evaluated += 1;
+        }
+        self.counts[i]
+    }
+
+    /// Fill in the rectangle from the pixel `from` to the pixel `to`, both
+    /// included, from the counts around its border if they agree, or
+    /// otherwise by splitting it, or at `min_size`, evaluating every pixel.
+    fn trace(&mut self, from : (usize, usize), to : (usize, usize), min_size : usize) {
+
+        let first = self.count(from.0, from.1);
+        let mut uniform = true;
+        for x in from.0..=to.0 {
+            uniform &= self.count(x, from.1) == first;
+            uniform &= self.count(x, to.1) == first;
+        }
+        for y in from.1..=to.1 {
+            uniform &= self.count(from.0, y) == first;
+            uniform &= self.count(to.0, y) == first;
+        }
+
+        let (width, height) = (to.0 - from.0 + 1, to.1 - from.1 + 1);
+        if width <= 2 || height <= 2 {
+            return; // it's all border
+        }
+
+        if uniform {
+            for y in from.1 + 1..to.1 {
+                for x in from.0 + 1..to.0 {
+                    let i = y * self.width + x;
+                    self.counts[i] = first;
+                    self.known[i] = true;
+                }
+            }
+        } else if width <= min_size || height <= min_size {
+            for y in from.1 + 1..to.1 {
+                for x in from.0 + 1..to.0 {
+                    self.count(x, y);
+                }
+            }
+        } else if width >= height {
+            let middle = (from.0 + to.0) / 2;
+            self.trace(from, (middle, to.1), min_size);
+            self.trace((middle, from.1), to, min_size);
+        } else {
+            let middle = (from.1 + to.1) / 2;
+            self.trace(from, (to.0, middle), min_size);
+            self.trace((from.0, middle), to, min_size);
+        }
+    }
+}
+
+/// Evaluate every point of the grid with real parts `re` and imaginary parts
+/// `im` by `iteration`, giving whichever kind of `Counts` it asks for.
+/// Distance estimates are divided by `pixel_size`, the grid's spacing.
+fn render_grid(re : &[f64], im : &[f64], pixel_size : f64, iteration : Iteration, progress : &Progress)
+    -> Counts {
+
+    render_grid_masked(re, im, None, pixel_size, iteration, progress)
+}
+
+/// Like `render_grid`, but with a `mask`, if given, of the pixels to
+/// evaluate, as `fill_rows` takes it.
+fn render_grid_masked(re : &[f64],
+                      im : &[f64],
+                      mask : Option<&[bool]>,
+                      pixel_size : f64,
+                      iteration : Iteration,
+                      progress : &Progress) -> Counts {
+
+    if iteration.distance {
+        Counts::Distance(fill_rows(re, im, mask, iteration.tile_size, progress, |row, re, im| {
+            render_row(row, re, im, |point| {
+                iteration.distance_estimate(point).map(|distance| distance / pixel_size)
+            })
+        }))
+    } else if let Some(trap) = iteration.trap {
+        Counts::Trapped(fill_rows(re, im, mask, iteration.tile_size, progress, |row, re, im| {
+            render_row(row, re, im, |point| iteration.escape_time_trapped(point, trap).1)
+        }))
+    } else if iteration.max_period > 0 {
+        Counts::Periodic(fill_rows(re, im, mask, iteration.tile_size, progress, |row, re, im| {
+            render_row(row, re, im, |point| iteration.escape_time_periodic(point))
+        }))
+    } else if iteration.interior {
+        Counts::Final(fill_rows(re, im, mask, iteration.tile_size, progress, |row, re, im| {
+            render_row(row, re, im, |point| iteration.escape_time_final(point))
+        }))
+    } else if iteration.smooth {
+        Counts::Smooth(fill_rows(re, im, mask, iteration.tile_size, progress, |row, re, im| {
+            render_row(row, re, im, |point| iteration.escape_time_smooth(point))
+        }))
+    } else {
+        Counts::Whole(fill_rows(re, im, mask, iteration.tile_size, progress, |row, re, im| {
+            render_whole_row(row, re, im, iteration)
+        }))
+    }
+}
+
+/// Return the real part of the point `pixel_to_point` gives for each column of
+/// an image, and the imaginary part for each row.
+///
+/// Each only depends on the column or the row, so computing them once up
+/// front saves recomputing them for every pixel, and gives exactly the values
+/// `pixel_to_point` would, but for one thing: in a view centered on the real
+/// axis, each row below the axis gets exactly the negation of the row
+/// mirroring it above, where rounding could leave `pixel_to_point` an ulp off.
+/// That lets `render` mirror every one of them.
+pub fn pixel_coordinates(bounds : (usize, usize),
+                         upper_left : Complex<f64>,
+                         lower_right : Complex<f64>) -> (Vec<f64>, Vec<f64>) {
+
+    let (mut re, mut im) = (Vec::with_capacity(bounds.0), Vec::with_capacity(bounds.1));
+    pixel_coordinates_into(&mut re, &mut im, bounds, upper_left, lower_right);
+    (re, im)
+}
+
+/// Like `pixel_coordinates`, but replace the contents of `re` and `im`
+/// rather than allocate new vectors, as `Renderer::reset` wants.
+fn pixel_coordinates_into(re : &mut Vec<f64>,
+                          im : &mut Vec<f64>,
+                          bounds : (usize, usize),
+                          upper_left : Complex<f64>,
+                          lower_right : Complex<f64>) {
+
+    re.clear();
+    re.extend((0..bounds.0).map(|x| lerp(upper_left.re, lower_right.re, x as f64 / bounds.0 as f64)));
+    im.clear();
+    im.extend((0..bounds.1).map(|y| lerp(upper_left.im, lower_right.im, y as f64 / bounds.1 as f64)));
+
+    if upper_left.im == -lower_right.im {
+        for y in bounds.1 / 2 + 1..bounds.1 {
+            im[y] = -im[bounds.1 - y];
+        }
+    }
+}
+
+/// Fill in every row of the grid with real parts `re` and imaginary parts
+/// `im`, in parallel, by calling `fill_row` with the row's results, `re` and
+/// the row's imaginary part. Return the results in row-major order.
+///
+/// One worker runs on each of the pool's threads, taking rows in order from
+/// a shared atomic counter, so a thread that drew cheap rows goes on to the
+/// next one rather than waiting for its share of the image to be handed out.
+///
+/// With a `tile_size` other than 0, the grid is filled in square blocks
+/// instead: the bands of `tile_size` rows are filled in parallel, and within
+/// each band, one block at a time, by calling `fill_row` with each block's
+/// slice of each row. Blocks are widened to a multiple of four columns, so
+/// that `render_whole_row` splits rows into the same groups of four either way.
+///
+/// With a `mask`, one for each pixel in row-major order, `fill_row` is only
+/// called with the runs of pixels it marks, and the rest are left at
+/// `T::default()` and cost nothing.
+fn fill_rows<T, F>(re : &[f64],
+                   im : &[f64],
+                   mask : Option<&[bool]>,
+                   tile_size : usize,
+                   progress : &Progress,
+                   fill_row : F) -> Vec<T>
+    where T : Send + Default + Clone, F : Fn(&mut [T], &[f64], f64) + Sync
+{
+    let width = re.len().max(1);
+    let mut counts = vec![T::default(); re.len() * im.len()];
+
+    let row_mask = |y : usize| mask.map(|mask| &mask[y * re.len()..][..re.len()]);
+    let fill = |row : &mut [T], re : &[f64], im : f64, mask : Option<&[bool]>| {
+        let Some(mask) = mask else {
+            return fill_row(row, re, im);
+        };
+        let mut start = 0;
+        while let Some(offset) = mask[start..].iter().position(|&set| set) {
+            let run = start + offset;
+            let end = mask[run..].iter().position(|&set| !set).map_or(mask.len(), |length| run + length);
+            fill_row(&mut row[run..end], &re[run..end], im);
+            start = end;
+        }
+    };
+
+    if tile_size == 0 {
+        // Each row's lock is only ever taken by the worker that drew it, so
+        // it never waits; it just hands that worker the row mutably.
+        let rows : Vec<Mutex<&mut [T]>> = counts.chunks_mut(width).map(Mutex::new).collect();
+        let next = AtomicUsize::new(0);
+        let worker = || loop {
+            let y = next.fetch_add(1, Ordering::Relaxed);
+            let Some((row, &im)) = rows.get(y).zip(im.get(y)) else {
+                break;
+            };
+            fill(&mut row.lock().unwrap(), re, im, row_mask(y));
+            progress.row_done();
+        };
+        rayon::scope(|scope| {
+            for _ in 0..rayon::current_num_threads().min(im.len()) {
+                scope.spawn(|_| worker());
+            }
+        });
+        drop(rows);
+        return counts;
+    }
+
+    let tile_width = tile_size.next_multiple_of(4);
+    counts.par_chunks_mut(width * tile_size)
+        .zip(im.par_chunks(tile_size))
+        .enumerate()
+        .for_each(|(n, (band, im))| {
+            for left in (0..re.len()).step_by(tile_width) {
+                let columns = left..(left + tile_width).min(re.len());
+                for (y, (row, &im)) in (n * tile_size..).zip(band.chunks_mut(width).zip(im)) {
+                    let mask = row_mask(y).map(|mask| &mask[columns.clone()]);
+                    fill(&mut row[columns.clone()], &re[columns.clone()], im, mask);
+                }
+            }
+            for _ in im {
+                progress.row_done();
+            }
+        });
+
+    counts
+}
+
+/// Like `render_row` with `Iteration::escape_time`, but four points at a
+/// time with `escape_time_simd`, when `iteration` allows it.
+fn render_whole_row(row : &mut [Option<usize>], re : &[f64], im : f64, iteration : Iteration) {
+
+    let escape = |point| iteration.escape_time(point);
+
+    if !iteration.vectorizes() {
+        return render_row(row, re, im, escape);
+    }
+
+    let mut counts = row.chunks_exact_mut(4);
+    let mut reals = re.chunks_exact(4);
+    for (counts, reals) in (&mut counts).zip(&mut reals) {
+        let points = [0, 1, 2, 3].map(|lane| Complex { re: reals[lane], im });
+        counts.copy_from_slice(&escape_time_simd(points, iteration.limit));
+    }
+
+    render_row(counts.into_remainder(), reals.remainder(), im, escape);
+}
+
+/// Apply `escape` to the row of points with imaginary part `im` and real parts
+/// `re`, storing the results in `row`, which must be exactly `re.len()` long.
+fn render_row<T, F>(row : &mut [T], re : &[f64], im : f64, escape : F)
+    where F : Fn(Complex<f64>) -> T
+{
+    for (count, &re) in row.iter_mut().zip(re) {
+        *count = escape(Complex { re, im });
+    }
+}
+
+/// Build a rayon thread pool with `threads` workers for `render` to run on. If
+/// `threads` is zero, use one worker per logical core.
+pub fn thread_pool(threads : usize) -> Result<rayon::ThreadPool, MandelError> {
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(MandelError::ThreadPool)
+}
+
+#[test]
+fn test_lerp() {
+
+    assert_eq!(lerp(10.0, 20.0, 0.0), 10.0);
+    assert_eq!(lerp(10.0, 20.0, 0.5), 15.0);
+    assert_eq!(lerp(10.0, 20.0, 1.0), 20.0);
+    assert_eq!(lerp(10.0f32, 20.0, 0.25), 12.5f32);
+    assert_eq!(lerp(10.0, 20.0, 1.5), 25.0);
+}
+
+#[test]
+fn test_lerp_clamped() {
+
+    assert_eq!(lerp_clamped(10.0, 20.0, -0.5), 10.0);
+    assert_eq!(lerp_clamped(10.0, 20.0, 1.5), 20.0);
+    assert_eq!(lerp_clamped(10.0, 20.0, 0.5), lerp(10.0, 20.0, 0.5));
+    assert_eq!(lerp_clamped(10.0f32, 20.0, -0.5), 10.0f32);
+    assert_eq!(lerp_clamped(10.0f32, 20.0, 1.5), 20.0f32);
+}
+
+#[test]
+fn test_pixel_to_point() {
+    assert_eq!(pixel_to_point((100, 200), (25, 175),
+                              Complex { re: -1.0, im:  1.0 },
+                              Complex { re:  1.0, im: -1.0 }),
+               Complex { re: -0.5, im: -0.75 });
+    assert_eq!(pixel_to_point((100, 200), (25, 175),
+                              Complex { re: -1.0f32, im:  1.0 },
+                              Complex { re:  1.0, im: -1.0 }),
+               Complex { re: -0.5, im: -0.75 });
+    assert_eq!(subpixel_to_point((4, 4), (1, 2), (0.5, 0.5),
+                                 Complex { re: -1.0, im:  1.0 },
+                                 Complex { re:  1.0, im: -1.0 }),
+               Complex { re: -0.25, im: -0.25 });
+}
+
+#[test]
+fn test_pixel_coordinates_match_pixel_to_point() {
+
+    let bounds = (333, 217);
+    let upper_left = Complex { re: -0.7436, im: 0.1319 };
+    let lower_right = Complex { re: -0.7434, im: 0.1317 };
+    let (re, im) = pixel_coordinates(bounds, upper_left, lower_right);
+
+    assert_eq!((re.len(), im.len()), bounds);
+    for pixel in [(0, 0), (1, 0), (0, 1), (100, 50), (332, 216), (166, 108)] {
+        let point = pixel_to_point(bounds, pixel, upper_left, lower_right);
+        assert_eq!(Complex { re: re[pixel.0], im: im[pixel.1] }, point);
+    }
+}
+
+#[test]
+fn test_render_parallel_matches_serial() {
+
+    let bounds = (64, 48);
+    let upper_left = Complex { re: -1.20, im: 0.35 };
+    let lower_right = Complex { re: -1.0, im: 0.20 };
+
+    let iteration = Iteration { limit: 255, ..Iteration::default() };
+    let parallel = render(bounds, upper_left, lower_right, iteration);
+
+    let mut serial = vec![None; bounds.0 * bounds.1];
+    let (re, im) = pixel_coordinates(bounds, upper_left, lower_right);
+    for (row, &im) in serial.chunks_mut(bounds.0).zip(&im) {
+        render_row(row, &re, im, |point| iteration.escape_time(point));
+    }
+
+    assert_eq!(parallel, Counts::Whole(serial));
+
+    // Rows whose width isn't a multiple of four finish without SIMD.
+    let bounds = (23, 5);
+    let (re, im) = pixel_coordinates(bounds, upper_left, lower_right);
+    let mut vectorized = vec![None; bounds.0];
+    let mut scalar = vec![None; bounds.0];
+    render_whole_row(&mut vectorized, &re, im[2], iteration);
+    render_row(&mut scalar, &re, im[2], |point| iteration.escape_time(point));
+    assert_eq!(vectorized, scalar);
+}
+
+#[test]
+fn test_render_counts() {
+
+    use crate::Trap;
+
+    let bounds = (30, 20);
+    let upper_left = Complex { re: -2.0, im: 1.2 };
+    let lower_right = Complex { re: 1.0, im: -1.2 };
+    let iteration = Iteration { limit: 100, ..Iteration::default() };
+
+    let Counts::Whole(counts) = render(bounds, upper_left, lower_right, iteration) else {
+        panic!("expected whole counts");
+    };
+    assert_eq!(counts.len(), bounds.0 * bounds.1);
+    for pixel in [(0, 0), (15, 10), (29, 19)] {
+        let point = pixel_to_point(bounds, pixel, upper_left, lower_right);
+        assert_eq!(counts[pixel.1 * bounds.0 + pixel.0], iteration.escape_time(point));
+    }
+
+    let smooth = Iteration { smooth: true, ..iteration };
+    let Counts::Smooth(counts) = render(bounds, upper_left, lower_right, smooth) else {
+        panic!("expected smooth counts");
+    };
+    let point = pixel_to_point(bounds, (3, 4), upper_left, lower_right);
+    assert_eq!(counts[4 * bounds.0 + 3], smooth.escape_time_smooth(point));
+
+    let trapped = Iteration { trap: Some(Trap::Cross), ..iteration };
+    let Counts::Trapped(distances) = render(bounds, upper_left, lower_right, trapped) else {
+        panic!("expected trap distances");
+    };
+    assert_eq!(distances[4 * bounds.0 + 3], trapped.escape_time_trapped(point, Trap::Cross).1);
+
+    // Distance estimates are measured in pixels, which are a tenth wide here.
+    let distance = Iteration { distance: true, ..iteration };
+    let Counts::Distance(distances) = render(bounds, upper_left, lower_right, distance) else {
+        panic!("expected distance estimates");
+    };
+    assert_eq!(distances[4 * bounds.0 + 3], distance.distance_estimate(point).map(|d| d / 0.1));
+}
+
+#[test]
+fn test_render_tiles_stitch_together() {
+
+    let bounds = (37, 24);
+    let upper_left = Complex { re: -2.0, im: 1.2 };
+    let lower_right = Complex { re: 1.0, im: -1.2 };
+    let iteration = Iteration { limit: 255, ..Iteration::default() };
+
+    let Counts::Whole(full) = render(bounds, upper_left, lower_right, iteration) else {
+        panic!("expected whole counts");
+    };
+
+    // Split unevenly, so that no tile is the same size as another.
+    let (left, top) = (20, 10);
+    let mut stitched = vec![None; bounds.0 * bounds.1];
+    for (origin, size) in [((0, 0), (left, top)), ((left, 0), (bounds.0 - left, top)),
+                           ((0, top), (left, bounds.1 - top)),
+                           ((left, top), (bounds.0 - left, bounds.1 - top))] {
+        let Counts::Whole(tile) = render_tile(bounds, origin, size, upper_left, lower_right, iteration) else {
+            panic!("expected whole counts");
+        };
+        assert_eq!(tile.len(), size.0 * size.1);
+        for (y, row) in tile.chunks(size.0).enumerate() {
+            let start = (origin.1 + y) * bounds.0 + origin.0;
+            stitched[start..start + size.0].copy_from_slice(row);
+        }
+    }
+
+    assert_eq!(stitched, full);
+}
+
+#[test]
+fn test_render_thread_count_does_not_matter() {
+
+    let bounds = (50, 37);
+    let upper_left = Complex { re: -2.0, im: 1.2 };
+    let lower_right = Complex { re: 1.0, im: -1.2 };
+    let iteration = Iteration { limit: 255, ..Iteration::default() };
+
+    let mut outputs = Vec::new();
+    for threads in [1, 4] {
+        let pool = thread_pool(threads).unwrap();
+        assert_eq!(pool.current_num_threads(), threads);
+        outputs.push(pool.install(|| render(bounds, upper_left, lower_right, iteration)));
+    }
+
+    assert_eq!(outputs[0], outputs[1]);
+    assert!(thread_pool(0).unwrap().current_num_threads() >= 1);
+}
+
+#[test]
+fn test_fill_rows_hands_out_every_row_once() {
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Rows near the middle take far longer than those at the edges, as rows
+    // through the set do, and each records which row it was filled as.
+    let re = [0.0, 1.0, 2.0];
+    let im : Vec<f64> = (0..25).map(|y| y as f64).collect();
+    let calls = AtomicUsize::new(0);
+    let fill = |row : &mut [(f64, f64)], re : &[f64], im : f64| {
+        calls.fetch_add(1, Ordering::Relaxed);
+        let cost = 12 - (im as i64 - 12).abs();
+        std::thread::sleep(std::time::Duration::from_micros(cost as u64 * 200));
+        for (cell, &re) in row.iter_mut().zip(re) {
+            *cell = (re, im);
+        }
+    };
+
+    let expected : Vec<(f64, f64)> = im.iter().flat_map(|&im| re.map(|re| (re, im))).collect();
+    for threads in [1, 3, 40] {
+        calls.store(0, Ordering::Relaxed);
+        let progress = Progress::hidden(im.len());
+        let counts = thread_pool(threads).unwrap().install(|| fill_rows(&re, &im, None, 0, &progress, fill));
+        assert_eq!(counts, expected);
+        assert_eq!((calls.load(Ordering::Relaxed), progress.completed()), (im.len(), im.len()));
+    }
+
+    // An empty grid has no rows to hand out.
+    let progress = Progress::hidden(0);
+    assert!(fill_rows(&re, &[], None, 0, &progress, fill).is_empty());
+}
+
+#[test]
+fn test_render_with_progress_counts_every_row() {
+
+    let bounds = (20, 33);
+    let iteration = Iteration { limit: 100, ..Iteration::default() };
+    let progress = Progress::hidden(bounds.1);
+
+    let counts = thread_pool(4).unwrap().install(|| {
+        render_with_progress(bounds, Complex { re: -2.0, im: 1.0 },
+                             Complex { re: 1.0, im: -1.0 }, iteration, &progress)
+    });
+
+    assert_eq!(counts.len(), bounds.0 * bounds.1);
+    assert_eq!(progress.completed(), bounds.1);
+}
+
+#[test]
+fn test_render_jittered_is_reproducible() {
+
+    use crate::{average, map_colors, ColorScheme};
+
+    let bounds = (48, 36);
+    let upper_left = Complex { re: -2.0, im: 1.2 };
+    let lower_right = Complex { re: 1.0, im: -1.2 };
+    let iteration = Iteration { limit: 64, ..Iteration::default() };
+
+    let image = |seed| {
+        let mut rng = Rng::new(seed);
+        average((0..4).map(|_| {
+            let counts = render_jittered(bounds, upper_left, lower_right, iteration, &mut rng,
+                                         &Progress::hidden(bounds.1));
+            map_colors(&counts, iteration.limit, &ColorScheme::default())
+        }))
+    };
+
+    assert_eq!(image(1), image(1));
+    assert_ne!(image(1), image(2));
+    assert_ne!(image(1), map_colors(&render(bounds, upper_left, lower_right, iteration), iteration.limit,
+                                    &ColorScheme::default()));
+}
+
+#[test]
+fn test_render_with_checkpoints() {
+
+    let bounds = (40, 25);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let iteration = Iteration { smooth: true, ..Iteration::default() };
+    let progress = Progress::hidden(bounds.1);
+
+    let mut seen = Vec::new();
+    let counts = render_with_checkpoints(bounds, upper_left, lower_right, iteration, 10, &progress, |counts| {
+        seen.push(counts.clone());
+        Ok(())
+    }).unwrap();
+
+    let full = render(bounds, upper_left, lower_right, iteration);
+    assert_eq!(counts, full);
+    assert_eq!(seen.iter().map(|counts| counts.len()).collect::<Vec<_>>(), [400, 800]);
+    let Counts::Smooth(full) = full else { unreachable!() };
+    assert_eq!(seen[1], Counts::Smooth(full[..800].to_vec()));
+
+    let failed = render_with_checkpoints(bounds, upper_left, lower_right, iteration, 10, &progress, |_| {
+        Err(MandelError::BadFlag("stop".to_string()))
+    });
+    assert!(matches!(failed, Err(MandelError::BadFlag(_))));
+}
+
+#[test]
+fn test_render_antialiased_edges() {
+
+    use crate::{downsample, map_colors, ColorScheme};
+
+    // A stretch of the boundary around the seahorse valley, with plenty of
+    // smooth ground on either side of it.
+    let bounds = (60, 40);
+    let (upper_left, lower_right) = (Complex { re: -0.9, im: 0.3 }, Complex { re: -0.6, im: 0.1 });
+    let iteration = Iteration { limit: 100, ..Iteration::default() };
+    let color = |counts : &Counts| map_colors(counts, iteration.limit, &ColorScheme::default());
+    let difference = |a : &[u8], b : &[u8]| {
+        a.iter().zip(b).map(|(&a, &b)| (a as f64 - b as f64).abs()).sum::<f64>() / a.len() as f64
+    };
+
+    let plain = color(&render(bounds, upper_left, lower_right, iteration));
+    let fine = (bounds.0 * 3, bounds.1 * 3);
+    let full = downsample(&color(&render(fine, upper_left, lower_right, iteration)), fine, 3);
+    let edges = render_antialiased_edges(bounds, upper_left, lower_right, iteration, 1.0,
+                                         &Progress::hidden(bounds.1), color);
+
+    let counts = render(bounds, upper_left, lower_right, iteration);
+    let edge_pixels = find_edges(&counts.values(), bounds, 1.0).len();
+    assert!(edge_pixels < bounds.0 * bounds.1 / 2, "{} edge pixels", edge_pixels);
+
+    let (before, after) = (difference(&plain, &full), difference(&edges, &full));
+    assert!(after < before / 2.0 && after < 1.0, "{} is too far from {}", after, before);
+
+    // With an unreachable threshold, nothing is resampled.
+    assert_eq!(render_antialiased_edges(bounds, upper_left, lower_right, iteration, f64::INFINITY,
+                                        &Progress::hidden(bounds.1), color), plain);
+}
+
+#[test]
+fn test_renderer_steps() {
+
+    use crate::{map_colors, ColorScheme};
+
+    let bounds = (40, 25);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let full = render(bounds, upper_left, lower_right, Iteration::default());
+
+    let mut renderer = Renderer::new(bounds, upper_left, lower_right, Iteration::default());
+    assert!(renderer.pixels().iter().all(|&shade| shade == 0));
+    assert!(renderer.step(7));
+    assert_eq!(renderer.counts().len(), 7 * bounds.0);
+    assert!(renderer.pixels()[7 * bounds.0..].iter().all(|&shade| shade == 0));
+    assert!(renderer.step(7) && renderer.step(7));
+    assert!(!renderer.step(7));
+    assert!(renderer.is_done());
+
+    assert_eq!(renderer.counts(), &full);
+    let rgb = map_colors(&full, 255, &ColorScheme::default());
+    assert_eq!(renderer.pixels(), rgb.iter().step_by(3).copied().collect::<Vec<u8>>());
+}
+
+#[test]
+fn test_renderer_reuses_its_buffer() {
+
+    let bounds = (40, 25);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut renderer = Renderer::new(bounds, upper_left, lower_right, Iteration::default());
+    let first = renderer.render().to_vec();
+    let (buffer, capacity) = (renderer.pixels.as_ptr(), renderer.pixels.capacity());
+    let counts_capacity = match &renderer.counts {
+        Counts::Whole(counts) => counts.capacity(),
+        _ => panic!("expected whole counts")
+    };
+
+    // The same view again comes out the same, in the same memory, from the
+    // same coordinates, mirrored rows and all.
+    renderer.reset(bounds, upper_left, lower_right);
+    let (re, im) = pixel_coordinates(bounds, upper_left, lower_right);
+    assert_eq!((&renderer.re, &renderer.im), (&re, &im));
+    assert!(renderer.pixels().iter().all(|&shade| shade == 0));
+    assert_eq!(renderer.render(), first);
+    assert_eq!((renderer.pixels.as_ptr(), renderer.pixels.capacity()), (buffer, capacity));
+    assert!(matches!(&renderer.counts, Counts::Whole(counts) if counts.capacity() == counts_capacity));
+
+    // A smaller view shrinks the buffer in place, and renders as a fresh
+    // renderer would.
+    let small = (20, 10);
+    renderer.reset(small, upper_left, lower_right);
+    let expected = Renderer::new(small, upper_left, lower_right, Iteration::default()).render().to_vec();
+    assert_eq!(renderer.render(), expected);
+    assert_eq!(renderer.pixels.as_ptr(), buffer);
+    assert!(!renderer.resize_if_needed(small));
+    assert!(renderer.resize_if_needed(bounds));
+}
+
+#[test]
+fn test_render_masked() {
+
+    use crate::Trap;
+
+    let bounds = (40, 30);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.2 }, Complex { re: 1.0, im: -1.2 });
+    let (re, im) = pixel_coordinates(bounds, upper_left, lower_right);
+    let progress = Progress::hidden(0);
+    let iteration = Iteration::default();
+
+    // Nothing is evaluated where the mask is clear.
+    let calls = AtomicUsize::new(0);
+    let counted = |point| {
+        calls.fetch_add(1, Ordering::Relaxed);
+        iteration.escape_time(point)
+    };
+    let mut mask = vec![false; 40 * 30];
+    for tile_size in [0, 6] {
+        let fill_masked = |mask : &[bool]| {
+            fill_rows(&re, &im, Some(mask), tile_size, &progress,
+                      |row, re, im| render_row(row, re, im, counted))
+        };
+        calls.store(0, Ordering::Relaxed);
+        assert_eq!(fill_masked(&mask), [None; 40 * 30]);
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+
+        mask[5 * 40 + 7] = true;
+        fill_masked(&mask);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        mask[5 * 40 + 7] = false;
+    }
+
+    // Each kind of counts is what `render` gives where the mask is set, and
+    // the default elsewhere, in rows or in tiles, with SIMD or without.
+    let mask : Vec<bool> = (0..40 * 30).map(|i| (i / 40 + i % 40 / 3) % 4 != 0).collect();
+    for iteration in [iteration, Iteration { smooth: true, ..iteration },
+                      Iteration { trap: Some(Trap::Cross), ..iteration },
+                      Iteration { distance: true, ..iteration }, Iteration { interior: true, ..iteration },
+                      Iteration { max_period: 8, ..iteration }, Iteration { tile_size: 7, ..iteration }] {
+        assert_eq!(render_masked(bounds, upper_left, lower_right, iteration, &[true; 40 * 30], &progress),
+                   render(bounds, upper_left, lower_right, iteration));
+
+        let (full, masked) = (render(bounds, upper_left, lower_right, iteration),
+                              render_masked(bounds, upper_left, lower_right, iteration, &mask, &progress));
+        assert_eq!(std::mem::discriminant(&full), std::mem::discriminant(&masked));
+        for ((full, masked), &set) in full.values().into_iter().zip(masked.values()).zip(&mask) {
+            assert!(if set { masked == full } else { masked <= 0.0 }, "{:?}", iteration);
+        }
+    }
+}
+
+#[test]
+fn test_render_tile_size_keeps_counts() {
+
+    use crate::{map_colors, ColorScheme, Trap};
+
+    let bounds = (101, 67);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let iteration = Iteration { limit: 300, ..Iteration::default() };
+    let rows = render(bounds, upper_left, lower_right, iteration);
+
+    for tile_size in [1, 5, 16, 200] {
+        for iteration in [iteration, Iteration { smooth: true, ..iteration },
+                          Iteration { trap: Some(Trap::Cross), ..iteration }] {
+            let blocks = render(bounds, upper_left, lower_right, Iteration { tile_size, ..iteration });
+            assert_eq!(blocks, render(bounds, upper_left, lower_right, iteration),
+                       "tile size {} changed the counts", tile_size);
+        }
+    }
+
+    // The colored image is byte for byte the same.
+    let blocks = render(bounds, upper_left, lower_right, Iteration { tile_size: 16, ..iteration });
+    let scheme = ColorScheme::default();
+    assert_eq!(map_colors(&blocks, 300, &scheme), map_colors(&rows, 300, &scheme));
+}
+
+#[test]
+fn test_render_adaptive_matches_render() {
+
+    let bounds = (300, 200);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let iteration = Iteration { limit: 500, ..Iteration::default() };
+    let pixels = bounds.0 * bounds.1;
+
+    let Counts::Whole(full) = render(bounds, upper_left, lower_right, iteration) else {
+        panic!("expected whole counts");
+    };
+    for (coarse_factor, most_evaluated) in [(4, pixels * 3 / 4), (8, pixels * 17 / 20)] {
+        let (Counts::Whole(adaptive), evaluated) =
+            render_adaptive(bounds, upper_left, lower_right, iteration, coarse_factor) else {
+            panic!("expected whole counts");
+        };
+        let differing = full.iter().zip(&adaptive).filter(|(a, b)| a != b).count();
+        assert!(differing <= pixels / 1000,
+                "{} pixels differ at a coarse factor of {}", differing, coarse_factor);
+        assert!(evaluated < most_evaluated, "{} of {} points evaluated", evaluated, pixels);
+    }
+
+    // With no cells to fill, every point is evaluated.
+    assert_eq!(render_adaptive(bounds, upper_left, lower_right, iteration, 1).1, pixels);
+    let (counts, evaluated) = render_adaptive((1, 5), upper_left, lower_right, iteration, 4);
+    assert_eq!((counts.len(), evaluated), (5, 5));
+}
+
+#[test]
+fn test_render_mariani_silver_matches_render() {
+
+    let bounds = (300, 200);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let iteration = Iteration { limit: 500, ..Iteration::default() };
+    let pixels = bounds.0 * bounds.1;
+    let progress = Progress::hidden(bounds.1);
+
+    let Counts::Whole(full) = render(bounds, upper_left, lower_right, iteration) else {
+        panic!("expected whole counts");
+    };
+    let (Counts::Whole(traced), evaluated) =
+        render_mariani_silver(bounds, upper_left, lower_right, iteration, 8, &progress) else {
+        panic!("expected whole counts");
+    };
+    let differing = full.iter().zip(&traced).filter(|(a, b)| a != b).count();
+    assert!(differing <= pixels / 1000, "{} pixels differ", differing);
+    assert!(evaluated < pixels * 3 / 4, "{} of {} points evaluated", evaluated, pixels);
+
+    // Wholly within the main cardioid, only the borders of the bands need
+    // evaluating.
+    let (upper_left, lower_right) = (Complex { re: -0.3, im: 0.2 }, Complex { re: 0.0, im: -0.2 });
+    let (counts, evaluated) =
+        render_mariani_silver((64, 128), upper_left, lower_right, iteration, 8, &progress);
+    assert_eq!(counts, Counts::Whole(vec![None; 64 * 128]));
+    assert_eq!(evaluated, 2 * (64 * 2 + 62 * 2));
+
+    let (counts, evaluated) = render_mariani_silver((1, 3), upper_left, lower_right, iteration, 8, &progress);
+    assert_eq!((counts.len(), evaluated), (3, 3));
+}
+
+#[test]
+fn test_symmetric_render_matches_naive_render() {
+
+    use crate::{Fractal, Trap};
+
+    let bounds = (41, 30);
+    let upper_left = Complex { re: -2.0, im: 1.2 };
+    let lower_right = Complex { re: 1.0, im: -1.2 };
+    let iteration = Iteration::default();
+
+    // Every row below the axis is mirrored, leaving only the top row, which
+    // has no row to mirror it, and the axis itself if a row lies along it.
+    for height in [30, 31, 217, 600] {
+        let (_, im) = pixel_coordinates((41, height), upper_left, lower_right);
+        let mirrored = mirror_sources(&im, iteration).iter().filter(|&&(_, mirrored)| mirrored).count();
+        assert_eq!(mirrored, (height - 1) / 2, "{} rows of {} mirrored", mirrored, height);
+
+        // A renderer starts from the same coordinates, whether it's new or
+        // reset from another view.
+        let mut renderer = Renderer::new((41, height), upper_left, lower_right, iteration);
+        assert_eq!(renderer.im, im);
+        renderer.reset((41, height), Complex { re: -1.0, im: 0.5 }, Complex { re: 0.0, im: -0.2 });
+        renderer.reset((41, height), upper_left, lower_right);
+        assert_eq!(renderer.im, im);
+    }
+    let (_, im) = pixel_coordinates(bounds, upper_left, lower_right);
+    let burning_ship = Iteration { fractal: Fractal::BurningShip, ..iteration };
+    assert!(mirror_sources(&im, burning_ship).iter().all(|&(_, mirrored)| !mirrored));
+
+    // render_tile evaluates every row of the image itself.
+    for iteration in [iteration, Iteration { smooth: true, ..iteration },
+                      Iteration { trap: Some(Trap::Cross), ..iteration },
+                      Iteration { distance: true, ..iteration }, Iteration { interior: true, ..iteration },
+                      Iteration { max_period: 8, ..iteration },
+                      Iteration { julia: Some(Complex { re: -0.75, im: 0.0 }), ..iteration }, burning_ship] {
+        assert_eq!(render(bounds, upper_left, lower_right, iteration),
+                   render_tile(bounds, (0, 0), bounds, upper_left, lower_right, iteration),
+                   "{:?}", iteration);
+    }
+}
+
+#[test]
+fn test_render_rows_streams_the_image() {
+
+    let bounds = (37, 23);
+    let upper_left = Complex { re: -2.0, im: 1.2 };
+    let lower_right = Complex { re: 1.0, im: -1.0 };
+
+    for iteration in [Iteration::default(), Iteration { smooth: true, limit: 100, ..Iteration::default() }] {
+        let mut pixels = Vec::new();
+        let mut next = 0;
+        render_rows(bounds, upper_left, lower_right, iteration, |y, row| {
+            assert_eq!((y, row.len()), (next, bounds.0));
+            next += 1;
+            pixels.extend_from_slice(row);
+        });
+        assert_eq!(next, bounds.1);
+        assert_eq!(pixels, map_gray(&render(bounds, upper_left, lower_right, iteration), iteration.limit));
+    }
+}
+
+#[test]
+fn test_render_builder() {
+
+    use crate::{map_colors, ColorScheme, Fractal, Palette, PRESETS};
+
+    let full = &PRESETS[0];
+    let counts = render(DEFAULT_RENDER_BOUNDS, full.upper_left, full.lower_right, Iteration::default());
+    assert_eq!(RenderBuilder::new().render(), map_colors(&counts, 255, &ColorScheme::default()));
+    assert_eq!(RenderBuilder::default(), RenderBuilder::new());
+
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let iteration = Iteration { limit: 100, fractal: Fractal::Multibrot(3), ..Iteration::default() };
+    let counts = render((40, 30), upper_left, lower_right, iteration);
+    let scheme = ColorScheme { palette: Palette::Fire, ..ColorScheme::default() };
+    let built = RenderBuilder::new().bounds((40, 30)).corners(upper_left, lower_right).limit(100)
+        .palette(Palette::Fire).fractal(Fractal::Multibrot(3)).threads(2).render();
+    assert_eq!(built, map_colors(&counts, 100, &scheme));
+}
+
+#[test]
+fn test_double_double_resolves_deeper() {
+
+    use crate::{Precision, rect_from_center};
+
+    // Neighbouring pixels of this view, around a Misiurewicz point, are an
+    // eighth of an f64's spacing apart along the imaginary axis, so f64
+    // gives bands of rows the same points and counts.
+    let bounds = (48, 48);
+    let center = Complex { re: -0.1010963638456221, im: 0.9562865108091415 };
+    let (upper_left, lower_right) = rect_from_center(center, 1.2e-15, bounds);
+    let double = Iteration { limit: 1000, ..Iteration::default() };
+    let double_double = Iteration { precision: Precision::DoubleDouble, ..double };
+
+    // Count the pairs of neighbouring pixels, across and down, with the same
+    // count.
+    let repeats = |counts : &Counts| match counts {
+        Counts::Whole(counts) => {
+            let across = counts.chunks(bounds.0)
+                .map(|row| row.windows(2).filter(|pair| pair[0] == pair[1]).count())
+                .sum::<usize>();
+            let down = counts.iter().zip(&counts[bounds.0..]).filter(|(above, below)| above == below).count();
+            across + down
+        }
+        _ => panic!("expected whole counts")
+    };
+    let coarse = repeats(&render(bounds, upper_left, lower_right, double));
+    let fine = repeats(&render(bounds, upper_left, lower_right, double_double));
+    assert!(fine < coarse, "{} repeats in double-double, {} in f64", fine, coarse);
+
+    // Each pixel's point lies between the corners, and on a shallow view it
+    // rounds to the one `pixel_to_point` gives.
+    let point = pixel_to_point_double_double(bounds, (12, 36), upper_left, lower_right);
+    assert!(upper_left.re < point.re.to_f64() && point.re.to_f64() < lower_right.re);
+    let (shallow_ul, shallow_lr) = (Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let point = pixel_to_point_double_double((100, 200), (25, 175), shallow_ul, shallow_lr);
+    assert_eq!((point.re.to_f64(), point.im.to_f64()), (-0.5, -0.75));
+}
@@ -0,0 +1,473 @@
+//! Changing the resolution and layout of rendered pixel buffers.
+
+use std::f64::consts::PI;
+
+use num::Bounded;
+
+use crate::render::lerp;
+
+/// Shrink the grayscale, RGB or RGBA buffer `pixels`, of dimensions `bounds`,
+/// by `factor` in each direction, replacing every `factor` x `factor` block
+/// with its average. Which kind of buffer it is is worked out from its length.
+///
+/// Both dimensions of `bounds` must be multiples of `factor`. Colors are
+/// averaged channel by channel, rounding to the nearest value, so a factor of
+/// 1 returns an exact copy. Samples may be 8 or 16 bits wide.
+pub fn downsample<T>(pixels : &[T], bounds : (usize, usize), factor : usize) -> Vec<T>
+    where T : Copy + Default + Into<u64> + TryFrom<u64>
+{
+    assert!(factor >= 1 && bounds.0.is_multiple_of(factor) && bounds.1.is_multiple_of(factor));
+    let channels = pixels.len() / (bounds.0 * bounds.1).max(1);
+    assert!(pixels.len() == bounds.0 * bounds.1 * channels && matches!(channels, 1 | 3 | 4));
+
+    let small = (bounds.0 / factor, bounds.1 / factor);
+    let samples = (factor * factor) as u64;
+    let mut output = vec![T::default(); small.0 * small.1 * channels];
+
+    for y in 0..small.1 {
+        for x in 0..small.0 {
+
+            let mut sum = [0u64; 4];
+            for sy in y * factor..(y + 1) * factor {
+                for sx in x * factor..(x + 1) * factor {
+                    let i = (sy * bounds.0 + sx) * channels;
+                    for c in 0..channels {
+                        sum[c] += pixels[i + c].into();
+                    }
+                }
+            }
+
+            // An average never exceeds the largest sample, so it always fits.
+            let o = (y * small.0 + x) * channels;
+            for c in 0..channels {
+                output[o + c] = T::try_from((sum[c] + samples / 2) / samples).ok().unwrap();
+            }
+        }
+    }
+
+    output
+}
+
+/// How `downsample_with` shrinks a supersampled buffer back down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Downscale {
+    /// Average each block of samples, as `downsample` does.
+    Box,
+    /// Filter with a Lanczos kernel of two lobes, as `downsample_lanczos`
+    /// does: crisper edges than the box, at the cost of a little ringing.
+    Lanczos
+}
+
+impl std::str::FromStr for Downscale {
+
+    type Err = String;
+
+    fn from_str(s : &str) -> Result<Downscale, String> {
+
+        match s {
+            "box" => Ok(Downscale::Box),
+            "lanczos" => Ok(Downscale::Lanczos),
+            _ => Err(format!("unknown downscale filter '{}': expected box or lanczos", s))
+        }
+    }
+}
+
+/// Shrink `pixels` by `factor` as `downsample` does, but with the filter
+/// `filter`.
+pub fn downsample_with<T>(pixels : &[T], bounds : (usize, usize), factor : usize, filter : Downscale)
+    -> Vec<T>
+    where T : Copy + Default + Into<u64> + TryFrom<u64> + Bounded
+{
+    match filter {
+        Downscale::Box => downsample(pixels, bounds, factor),
+        Downscale::Lanczos => downsample_lanczos(pixels, bounds, factor)
+    }
+}
+
+/// The Lanczos kernel with two lobes: a sinc windowed by a wider sinc, zero
+/// from 2 on out.
+fn lanczos2(x : f64) -> f64 {
+
+    if x == 0.0 {
+        1.0
+    } else if x.abs() < 2.0 {
+        2.0 * (PI * x).sin() * (PI * x / 2.0).sin() / (PI * PI * x * x)
+    } else {
+        0.0
+    }
+}
+
+/// For each of the pixels a side `length` samples long shrinks to by
+/// `factor`, the samples to blend into it and their weights, which add up
+/// to 1. The kernel is stretched by `factor`, so it spans two output pixels
+/// either side, and taps past the ends are clamped to the outermost sample.
+fn lanczos_taps(length : usize, factor : usize) -> Vec<Vec<(usize, f64)>> {
+
+    let scale = factor as f64;
+    (0..length / factor).map(|i| {
+        let center = (i as f64 + 0.5) * scale - 0.5;
+        let first = (center - 2.0 * scale).floor() as isize + 1;
+        let last = (center + 2.0 * scale).ceil() as isize - 1;
+        let mut taps : Vec<(usize, f64)> = (first..=last)
+            .map(|s| (s.clamp(0, length as isize - 1) as usize, lanczos2((s as f64 - center) / scale)))
+            .collect();
+        let total : f64 = taps.iter().map(|&(_, weight)| weight).sum();
+        for (_, weight) in &mut taps {
+            *weight /= total;
+        }
+        taps
+    }).collect()
+}
+
+/// Like `downsample`, but blend each output pixel from the samples around it
+/// with a separable Lanczos-2 filter, across each row and then down each
+/// column, rather than averaging its block. The kernel's negative lobes can
+/// overshoot, so results are clamped to the samples' range. A factor of 1
+/// returns an exact copy.
+pub fn downsample_lanczos<T>(pixels : &[T], bounds : (usize, usize), factor : usize) -> Vec<T>
+    where T : Copy + Default + Into<u64> + TryFrom<u64> + Bounded
+{
+    assert!(factor >= 1 && bounds.0.is_multiple_of(factor) && bounds.1.is_multiple_of(factor));
+    let channels = pixels.len() / (bounds.0 * bounds.1).max(1);
+    assert!(pixels.len() == bounds.0 * bounds.1 * channels && matches!(channels, 1 | 3 | 4));
+
+    let small = (bounds.0 / factor, bounds.1 / factor);
+    let sample = |i : usize| -> f64 { let value : u64 = pixels[i].into(); value as f64 };
+
+    let columns = lanczos_taps(bounds.0, factor);
+    let mut across = vec![0.0; small.0 * bounds.1 * channels];
+    for y in 0..bounds.1 {
+        for (x, taps) in columns.iter().enumerate() {
+            for c in 0..channels {
+                across[(y * small.0 + x) * channels + c] = taps.iter()
+                    .map(|&(sx, weight)| weight * sample((y * bounds.0 + sx) * channels + c))
+                    .sum();
+            }
+        }
+    }
+
+    let max : u64 = T::max_value().into();
+    let mut output = vec![T::default(); small.0 * small.1 * channels];
+    for (y, taps) in lanczos_taps(bounds.1, factor).iter().enumerate() {
+        for x in 0..small.0 {
+            for c in 0..channels {
+                let value : f64 = taps.iter()
+                    .map(|&(sy, weight)| weight * across[(sy * small.0 + x) * channels + c])
+                    .sum();
+                let value = value.round().clamp(0.0, max as f64) as u64;
+                output[(y * small.0 + x) * channels + c] = T::try_from(value).ok().unwrap();
+            }
+        }
+    }
+
+    output
+}
+
+/// Halve the grayscale, RGB or RGBA buffer `pixels`, of dimensions `bounds`,
+/// in each direction, as one level of an image pyramid: each 2 x 2 block
+/// becomes its average. An odd side keeps its last row or column, averaged
+/// only with itself, so the result is `bounds` halved and rounded up.
+pub fn halve<T>(pixels : &[T], bounds : (usize, usize)) -> Vec<T>
+    where T : Copy + Default + Into<u64> + TryFrom<u64>
+{
+    let channels = pixels.len() / (bounds.0 * bounds.1).max(1);
+    assert!(pixels.len() == bounds.0 * bounds.1 * channels && matches!(channels, 1 | 3 | 4));
+
+    let small = (bounds.0.div_ceil(2), bounds.1.div_ceil(2));
+    let mut output = vec![T::default(); small.0 * small.1 * channels];
+
+    for y in 0..small.1 {
+        for x in 0..small.0 {
+            let (columns, rows) = (2 * x..(2 * x + 2).min(bounds.0), 2 * y..(2 * y + 2).min(bounds.1));
+            let samples = (columns.len() * rows.len()) as u64;
+            let o = (y * small.0 + x) * channels;
+            for c in 0..channels {
+                let sum : u64 = rows.clone()
+                    .flat_map(|sy| columns.clone().map(move |sx| (sy * bounds.0 + sx) * channels + c))
+                    .map(|i| pixels[i].into())
+                    .sum();
+                // As in `downsample`, the average always fits.
+                output[o + c] = T::try_from((sum + samples / 2) / samples).ok().unwrap();
+            }
+        }
+    }
+
+    output
+}
+
+/// Stretch the grayscale, RGB or RGBA buffer `pixels`, of dimensions `from`,
+/// to dimensions `to` by bilinear interpolation: each output pixel's center is
+/// mapped back onto the input, and its samples blended from the four input
+/// pixels around that point, with `lerp` across and then `lerp` down, and
+/// rounded. Points beyond the outermost pixel centers take the edge's
+/// samples. Stretching a buffer to its own size returns an exact copy.
+pub fn upscale_bilinear<T>(pixels : &[T], from : (usize, usize), to : (usize, usize)) -> Vec<T>
+    where T : Copy + Default + Into<u64> + TryFrom<u64>
+{
+    assert!(from.0 >= 1 && from.1 >= 1);
+    let channels = pixels.len() / (from.0 * from.1);
+    assert!(pixels.len() == from.0 * from.1 * channels && matches!(channels, 1 | 3 | 4));
+
+    // The input pixels on either side of output column or row `n`, and how
+    // far along from the first to the second it lies.
+    let neighbours = |n : usize, from : usize, to : usize| {
+        let position = ((n as f64 + 0.5) * from as f64 / to as f64 - 0.5).clamp(0.0, (from - 1) as f64);
+        let first = position.floor() as usize;
+        (first, (first + 1).min(from - 1), position - first as f64)
+    };
+    let sample = |x : usize, y : usize, c : usize| pixels[(y * from.0 + x) * channels + c].into() as f64;
+
+    let mut output = vec![T::default(); to.0 * to.1 * channels];
+    for y in 0..to.1 {
+        let (top, bottom, down) = neighbours(y, from.1, to.1);
+        for x in 0..to.0 {
+            let (left, right, across) = neighbours(x, from.0, to.0);
+            for c in 0..channels {
+                let upper = lerp(sample(left, top, c), sample(right, top, c), across);
+                let lower = lerp(sample(left, bottom, c), sample(right, bottom, c), across);
+                // A blend of samples lies between them, so it always fits.
+                let blend = lerp(upper, lower, down).round() as u64;
+                output[(y * to.0 + x) * channels + c] = T::try_from(blend).ok().unwrap();
+            }
+        }
+    }
+
+    output
+}
+
+/// Average the equally sized pixel buffers `images` sample by sample,
+/// rounding to the nearest value, as `downsample` does within a block.
+/// Return an empty buffer if there are no images.
+///
+/// The images are summed one at a time as they arrive, so a long run of
+/// stochastic samples needs only one image's worth of memory beyond the sums.
+pub fn average<T, I>(images : I) -> Vec<T>
+    where T : Copy + Into<u64> + TryFrom<u64>, I : IntoIterator<Item = Vec<T>>
+{
+    let mut sums : Vec<u64> = Vec::new();
+    let mut count = 0;
+
+    for image in images {
+        if count == 0 {
+            sums = vec![0; image.len()];
+        }
+        assert!(image.len() == sums.len(), "images to average differ in size");
+        for (sum, sample) in sums.iter_mut().zip(image) {
+            *sum += sample.into();
+        }
+        count += 1;
+    }
+
+    // As in `downsample`, an average always fits.
+    sums.into_iter()
+        .map(|sum| T::try_from((sum + count / 2) / count.max(1)).ok().unwrap())
+        .collect()
+}
+
+/// Which half of a finished image to reflect onto the other, for a
+/// symmetric composition whether or not the fractal itself is symmetric.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mirror {
+    /// Leave the image as it was rendered.
+    None,
+    /// Reflect the top half onto the bottom, across the horizontal center line.
+    Horizontal,
+    /// Reflect the left half onto the right, across the vertical center line.
+    Vertical
+}
+
+impl std::str::FromStr for Mirror {
+
+    type Err = String;
+
+    fn from_str(s : &str) -> Result<Mirror, String> {
+
+        match s {
+            "none" => Ok(Mirror::None),
+            "horizontal" => Ok(Mirror::Horizontal),
+            "vertical" => Ok(Mirror::Vertical),
+            _ => Err(format!("unknown mirror '{}': expected none, horizontal or vertical", s))
+        }
+    }
+}
+
+/// Reflect half of the buffer `pixels`, of dimensions `bounds`, onto the
+/// other half as `mirror` says. Each pixel may be any number of samples wide,
+/// worked out from the buffer's length, so this works on grayscale or RGB
+/// samples and on the bytes of 16-bit ones alike. When the side being
+/// reflected across is odd, the center row or column is left where it is.
+pub fn mirror_pixels<T : Copy>(pixels : &mut [T], bounds : (usize, usize), mirror : Mirror) {
+
+    let (width, height) = bounds;
+    let pixel = pixels.len() / (width * height).max(1);
+    assert_eq!(pixels.len(), width * height * pixel);
+    let stride = width * pixel;
+
+    match mirror {
+        Mirror::None => {}
+        Mirror::Horizontal => {
+            for y in 0..height / 2 {
+                let (top, bottom) = pixels.split_at_mut((height - 1 - y) * stride);
+                bottom[..stride].copy_from_slice(&top[y * stride..(y + 1) * stride]);
+            }
+        }
+        Mirror::Vertical => {
+            for row in pixels.chunks_exact_mut(stride.max(1)) {
+                for x in 0..width / 2 {
+                    let (left, right) = row.split_at_mut((width - 1 - x) * pixel);
+                    right[..pixel].copy_from_slice(&left[x * pixel..(x + 1) * pixel]);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_downsample_averages_blocks() {
+
+    // A 4x2 image of two 2x2 blocks: one averaging to gray, one flat red.
+    let pixels : [u8; 24] = [
+        0, 0, 0,        255, 255, 255,  255, 0, 0,  255, 0, 0,
+        255, 255, 255,  0, 0, 0,        255, 0, 0,  255, 0, 0,
+    ];
+    assert_eq!(downsample(&pixels, (4, 2), 2), [128, 128, 128, 255, 0, 0]);
+
+    let gray : [u16; 4] = [0, 65535, 65535, 65535];
+    assert_eq!(downsample(&gray, (2, 2), 2), [49151]);
+
+    // Alpha is averaged like any other channel, so edges come out partly
+    // transparent.
+    let rgba : [u8; 8] = [10, 20, 30, 255, 30, 40, 50, 0];
+    assert_eq!(downsample(&rgba, (2, 1), 1), rgba);
+    assert_eq!(downsample(&[rgba, rgba].concat(), (2, 2), 2), [20, 30, 40, 128]);
+}
+
+#[test]
+fn test_downsample_factor_one_is_identity() {
+
+    use crate::{map_colors, render, ColorScheme, Iteration, Palette};
+    use num::Complex;
+
+    let bounds = (40, 30);
+    let iteration = Iteration { limit: 255, ..Iteration::default() };
+    let counts = render(bounds, Complex { re: -2.0, im: 1.2 }, Complex { re: 1.0, im: -1.2 }, iteration);
+    let pixels = map_colors(&counts, iteration.limit,
+                            &ColorScheme { palette: Palette::Fire, ..ColorScheme::default() });
+
+    assert_eq!(downsample(&pixels, bounds, 1), pixels);
+}
+
+#[test]
+fn test_supersampling_softens_edges() {
+
+    use crate::{map_colors, render, ColorScheme, Iteration};
+    use num::Complex;
+    use std::collections::HashSet;
+
+    // Strictly two-toned at one sample per pixel, boundary pixels pick
+    // up intermediate grays once several samples are averaged.
+    let iteration = Iteration { limit: 2, ..Iteration::default() };
+    let upper_left = Complex { re: -2.5, im: 2.5 };
+    let lower_right = Complex { re: 2.5, im: -2.5 };
+
+    let colors = |bounds| map_colors(&render(bounds, upper_left, lower_right, iteration),
+                                     iteration.limit, &ColorScheme::default());
+    let plain = colors((20, 20));
+    let big = colors((80, 80));
+    let smoothed = downsample(&big, (80, 80), 4);
+
+    let levels = |pixels: &[u8]| pixels.iter().collect::<HashSet<_>>().len();
+    assert_eq!(levels(&plain), 2);
+    assert!(levels(&smoothed) > 2);
+}
+
+#[test]
+fn test_downsample_lanczos() {
+
+    // Both filters shrink a buffer to the same size, and leave a flat one flat.
+    let flat = vec![77u8; 12 * 8 * 3];
+    for filter in [Downscale::Box, Downscale::Lanczos] {
+        assert_eq!(downsample_with(&flat, (12, 8), 4, filter), vec![77u8; 3 * 2 * 3]);
+        assert_eq!(downsample_with(&[9000u16; 6 * 6], (6, 6), 3, filter), [9000u16; 4]);
+    }
+
+    let pixels : Vec<u8> = (0..64).map(|i| (i * 37 % 256) as u8).collect();
+    assert_eq!(downsample_lanczos(&pixels, (8, 8), 1), pixels);
+
+    // A hard edge between black and white stays ordered, and the ringing
+    // near it is clamped to the samples' range rather than wrapping.
+    let edge : Vec<u8> = (0..16 * 2).map(|i| if i % 16 < 8 { 0 } else { 255 }).collect();
+    let shrunk = downsample_lanczos(&edge, (16, 2), 2);
+    assert_eq!(shrunk.len(), 8);
+    assert_eq!((shrunk[0], shrunk[7]), (0, 255));
+    assert!(shrunk[..8].windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", shrunk);
+    assert_eq!("lanczos".parse(), Ok(Downscale::Lanczos));
+    assert!("bicubic".parse::<Downscale>().is_err());
+}
+
+#[test]
+fn test_halve() {
+
+    // Whole blocks are averaged; the odd last column only with itself.
+    let pixels : [u8; 6] = [0, 100, 200, 50, 150, 255];
+    assert_eq!(halve(&pixels, (3, 2)), [75, 228]);
+    assert_eq!(halve(&[10u16], (1, 1)), [10]);
+
+    let rgb = halve(&[255u8, 0, 0, 0, 0, 255, 255, 0, 0, 0, 0, 255], (2, 2));
+    assert_eq!(rgb, [128, 0, 128]);
+    assert_eq!(halve(&[7u8; 5 * 7 * 3], (5, 7)), [7u8; 3 * 4 * 3]);
+}
+
+#[test]
+fn test_average() {
+
+    assert_eq!(average(vec![vec![0u8, 255, 10], vec![255, 255, 11]]), [128, 255, 11]);
+    assert_eq!(average(vec![vec![7u16, 65535]]), [7, 65535]);
+    assert!(average(Vec::<Vec<u8>>::new()).is_empty());
+}
+
+#[test]
+fn test_upscale_bilinear() {
+
+    let pixels : [u8; 6] = [0, 100, 200, 30, 130, 230];
+    assert_eq!(upscale_bilinear(&pixels, (3, 2), (3, 2)), pixels);
+
+    // Doubling a two-pixel row puts the outer pixels on the originals' and
+    // blends the inner ones a quarter and three quarters of the way across.
+    assert_eq!(upscale_bilinear(&[0u8, 200], (2, 1), (4, 1)), [0, 50, 150, 200]);
+    assert_eq!(upscale_bilinear(&[0u16, 40000], (1, 2), (3, 4)).len(), 12);
+
+    let rgb = upscale_bilinear(&[255u8, 0, 0, 0, 0, 255], (2, 1), (5, 3));
+    assert_eq!(rgb.len(), 5 * 3 * 3);
+    assert_eq!(rgb[..3], [255, 0, 0]);
+    assert_eq!(rgb[rgb.len() - 3..], [0, 0, 255]);
+}
+
+#[test]
+fn test_mirror_pixels() {
+
+    // A horizontally mirrored image is its own upside-down flip, keeping its
+    // top half and the center row of an odd height.
+    for height in [4, 5] {
+        let pixels : Vec<u8> = (0..3 * height * 2).map(|i| i as u8).collect();
+        let mut mirrored = pixels.clone();
+        mirror_pixels(&mut mirrored, (3, height), Mirror::Horizontal);
+        let flipped : Vec<u8> = mirrored.chunks(3 * 2).rev().flatten().copied().collect();
+        assert_eq!(mirrored, flipped);
+        assert_eq!(mirrored[..3 * 2 * height.div_ceil(2)], pixels[..3 * 2 * height.div_ceil(2)]);
+    }
+
+    // Vertical mirroring reflects each row's left half, whole pixels at a time.
+    let mut rgb : Vec<u8> = (1..=15).collect();
+    mirror_pixels(&mut rgb, (5, 1), Mirror::Vertical);
+    assert_eq!(rgb, [1, 2, 3, 4, 5, 6, 7, 8, 9, 4, 5, 6, 1, 2, 3]);
+
+    let mut gray = [1u16, 2, 3, 4];
+    mirror_pixels(&mut gray, (2, 2), Mirror::None);
+    assert_eq!(gray, [1, 2, 3, 4]);
+    mirror_pixels(&mut gray, (2, 2), Mirror::Vertical);
+    assert_eq!(gray, [1, 1, 3, 3]);
+
+    assert_eq!("horizontal".parse(), Ok(Mirror::Horizontal));
+    assert!("diagonal".parse::<Mirror>().is_err());
+}
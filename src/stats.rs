@@ -0,0 +1,201 @@
+//! Summing up how much work a render took, and what the set looks like.
+
+use num::Complex;
+use rayon::prelude::*;
+use std::ops::AddAssign;
+
+use crate::escape::escape_time;
+use crate::random::Rng;
+use crate::render::Counts;
+
+/// Totals over the pixels of one or more renders, for `--stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    /// The escape-time iterations the pixels took: each escaping point's
+    /// count, and the full limit for each point in the set. The cardioid and
+    /// cycle checks let many points in the set stop early, so this is the
+    /// work a plain loop would do, an upper bound on the work actually done.
+    pub iterations: u64,
+
+    /// The number of pixels whose points seem to be in the set.
+    pub in_set: usize,
+
+    /// The number of pixels counted.
+    pub pixels: usize
+}
+
+impl RenderStats {
+
+    /// Total up `counts`, rendered with the iteration limit `limit`. Trap
+    /// distances and distance estimates don't record escape counts, so give
+    /// `None` for them. The pixels are summed in parallel.
+    pub fn of(counts : &Counts, limit : usize) -> Option<RenderStats> {
+
+        let iterations = |count : Option<usize>| count.unwrap_or(limit) as u64;
+
+        let (iterations, in_set) = match counts {
+            Counts::Whole(counts) => counts.par_iter()
+                .map(|&count| (iterations(count), count.is_none() as usize))
+                .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1)),
+            Counts::Smooth(counts) => counts.par_iter()
+                .map(|&count| (iterations(count.map(|count| count as usize)), count.is_none() as usize))
+                .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1)),
+            Counts::Final(orbits) => orbits.par_iter()
+                .map(|&(count, _)| (iterations(count), count.is_none() as usize))
+                .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1)),
+            Counts::Periodic(orbits) => orbits.par_iter()
+                .map(|&(count, _)| (iterations(count), count.is_none() as usize))
+                .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1)),
+            Counts::Trapped(_) | Counts::Distance(_) => return None
+        };
+
+        Some(RenderStats { iterations, in_set, pixels: counts.len() })
+    }
+
+    /// Total up each row of `counts` on its own, for an image `width` pixels
+    /// wide, as `of` would total it, and return the rows' totals from the
+    /// top. Smooth counts are rounded down, as `of` rounds them; unlike `of`,
+    /// trap distances and distance estimates give `None`.
+    pub fn of_rows(counts : &Counts, width : usize, limit : usize) -> Option<Vec<RenderStats>> {
+
+        let whole : Vec<Option<usize>> = match counts {
+            Counts::Whole(counts) => counts.clone(),
+            Counts::Smooth(counts) => counts.iter().map(|&count| count.map(|count| count as usize)).collect(),
+            Counts::Final(orbits) => orbits.iter().map(|&(count, _)| count).collect(),
+            Counts::Periodic(orbits) => orbits.iter().map(|&(count, _)| count).collect(),
+            Counts::Trapped(_) | Counts::Distance(_) => return None
+        };
+
+        let iterations = |row : &[Option<usize>]| {
+            row.iter().map(|&count| count.unwrap_or(limit) as u64).sum()
+        };
+        Some(whole.par_chunks(width.max(1))
+             .map(|row| RenderStats { iterations: iterations(row),
+                                      in_set: row.iter().filter(|count| count.is_none()).count(),
+                                      pixels: row.len() })
+             .collect())
+    }
+
+    /// The fraction of the pixels that are in the set, from 0 to 1.
+    pub fn in_set_fraction(&self) -> f64 {
+
+        self.in_set as f64 / self.pixels.max(1) as f64
+    }
+}
+
+impl AddAssign for RenderStats {
+
+    fn add_assign(&mut self, other : RenderStats) {
+
+        self.iterations += other.iterations;
+        self.in_set += other.in_set;
+        self.pixels += other.pixels;
+    }
+}
+
+/// The rectangle `estimate_area` samples, as its upper-left and lower-right
+/// corners. The whole set lies within it.
+pub const AREA_BOX: (Complex<f64>, Complex<f64>) = (Complex { re: -2.0, im: 1.25 },
+                                                    Complex { re: 0.5, im: -1.25 });
+
+/// An estimate of the area of the Mandelbrot set, from `estimate_area`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AreaEstimate {
+    /// The estimated area.
+    pub area: f64,
+
+    /// The standard error of `area`: about two times in three, the area the
+    /// same iteration limit would give with endless samples is within this
+    /// much of it.
+    pub standard_error: f64
+}
+
+/// Estimate the area of the Mandelbrot set by Monte Carlo integration: pick
+/// `samples` points uniformly from `AREA_BOX` with `rng`, and scale the box's
+/// area by the fraction that don't escape within `limit` iterations.
+///
+/// Points near the boundary that escape only after more than `limit`
+/// iterations are counted in, so a low limit overestimates the area. It is
+/// thought to be about 1.5066.
+pub fn estimate_area(samples : usize, limit : usize, rng : &mut Rng) -> AreaEstimate {
+
+    let (upper_left, lower_right) = AREA_BOX;
+    let width = lower_right.re - upper_left.re;
+    let height = upper_left.im - lower_right.im;
+
+    let origin = Complex { re: 0.0, im: 0.0 };
+    let mut in_set = 0;
+    for _ in 0..samples {
+        let c = Complex { re: upper_left.re + rng.next_f64() * width,
+                          im: lower_right.im + rng.next_f64() * height };
+        if escape_time(origin, c, limit, 2.0).is_none() {
+            in_set += 1;
+        }
+    }
+
+    // Each sample is a coin toss that lands in the set with probability p.
+    let p = in_set as f64 / samples.max(1) as f64;
+    let box_area = width * height;
+    AreaEstimate { area: box_area * p,
+                   standard_error: box_area * (p * (1.0 - p) / samples.max(1) as f64).sqrt() }
+}
+
+#[test]
+fn test_render_stats() {
+
+    let stats = RenderStats::of(&Counts::Whole(vec![Some(3), None, Some(0), None]), 100).unwrap();
+    assert_eq!(stats, RenderStats { iterations: 203, in_set: 2, pixels: 4 });
+    assert_eq!(stats.in_set_fraction(), 0.5);
+
+    let mut total = stats;
+    total += RenderStats::of(&Counts::Smooth(vec![Some(2.5)]), 100).unwrap();
+    assert_eq!(total, RenderStats { iterations: 205, in_set: 2, pixels: 5 });
+
+    assert_eq!(RenderStats::of(&Counts::Trapped(vec![0.5]), 100), None);
+}
+
+#[test]
+fn test_row_stats_sum_to_totals() {
+
+    use crate::{render, Iteration};
+    use num::Complex;
+
+    let iteration = Iteration { smooth: true, ..Iteration::default() };
+    let counts = render((35, 30), Complex { re: -2.5, im: 1.5 }, Complex { re: 1.0, im: -1.5 }, iteration);
+    let rows = RenderStats::of_rows(&counts, 35, iteration.limit).unwrap();
+    assert_eq!(rows.len(), 30);
+    assert!(rows.iter().all(|row| row.pixels == 35));
+
+    let mut total = RenderStats::default();
+    rows.into_iter().for_each(|row| total += row);
+    assert_eq!(total, RenderStats::of(&counts, iteration.limit).unwrap());
+
+    assert_eq!(RenderStats::of_rows(&Counts::Distance(vec![Some(0.5)]), 1, 100), None);
+}
+
+#[test]
+fn test_full_set_in_set_fraction() {
+
+    use crate::{render, Iteration};
+    use num::Complex;
+
+    // The set's area is about 1.5066, out of the 10.5 of this view. At a
+    // limit of 255, points at the boundary still count, nudging it up a bit.
+    let iteration = Iteration::default();
+    let counts = render((350, 300), Complex { re: -2.5, im: 1.5 }, Complex { re: 1.0, im: -1.5 }, iteration);
+    let fraction = RenderStats::of(&counts, iteration.limit).unwrap().in_set_fraction();
+    assert!((0.14..0.16).contains(&fraction), "in-set fraction {} is out of bounds", fraction);
+}
+
+#[test]
+fn test_estimate_area() {
+
+    let estimate = estimate_area(200_000, 1000, &mut Rng::new(1));
+    assert!((1.48..1.54).contains(&estimate.area), "estimated area {} is implausible", estimate.area);
+    assert!((0.004..0.008).contains(&estimate.standard_error),
+            "standard error {} is implausible", estimate.standard_error);
+    assert!((estimate.area - 1.5066).abs() < 4.0 * estimate.standard_error + 0.01);
+
+    assert_eq!(estimate_area(1000, 100, &mut Rng::new(5)), estimate_area(1000, 100, &mut Rng::new(5)));
+    assert_eq!(estimate_area(0, 100, &mut Rng::new(5)), AreaEstimate { area: 0.0, standard_error: 0.0 });
+}
@@ -0,0 +1,542 @@
+//! Choosing the rectangle of the complex plane an image covers.
+
+use num::Complex;
+use std::cmp::Ordering;
+
+use crate::error::MandelError;
+use crate::escape::Iteration;
+use crate::render::{lerp, pixel_to_point, render, Counts};
+
+/// Check that `upper_left` really is above and to the left of `lower_right`,
+/// returning `MandelError::FlippedCorners` naming the first coordinate that
+/// isn't. Swapped corners still render, but mirrored or upside down.
+pub fn check_corners(upper_left : Complex<f64>, lower_right : Complex<f64>) -> Result<(), MandelError> {
+
+    if upper_left.re.partial_cmp(&lower_right.re) != Some(Ordering::Less) {
+        return Err(MandelError::FlippedCorners { part: "real", upper_left: upper_left.re,
+                                                 lower_right: lower_right.re });
+    }
+    if upper_left.im.partial_cmp(&lower_right.im) != Some(Ordering::Greater) {
+        return Err(MandelError::FlippedCorners { part: "imaginary", upper_left: upper_left.im,
+                                                 lower_right: lower_right.im });
+    }
+
+    Ok(())
+}
+
+/// Check that the rectangle from `upper_left` to `lower_right` has both a
+/// width and a height, returning `MandelError::DegenerateCorners` naming the
+/// first coordinate the corners share if it doesn't. Unlike flipped corners,
+/// a degenerate rectangle can only render a uniform image, so there's no
+/// flag to allow it.
+pub fn check_nondegenerate(upper_left : Complex<f64>, lower_right : Complex<f64>) -> Result<(), MandelError> {
+
+    if upper_left.re == lower_right.re {
+        return Err(MandelError::DegenerateCorners { part: "real", value: upper_left.re });
+    }
+    if upper_left.im == lower_right.im {
+        return Err(MandelError::DegenerateCorners { part: "imaginary", value: upper_left.im });
+    }
+
+    Ok(())
+}
+
+/// The most pixels `check_pixel_count` allows, unless told otherwise: 256
+/// megapixels, whose escape counts alone take 4GB.
+pub const DEFAULT_MAX_PIXELS : usize = 256_000_000;
+
+/// Check that rendering an image of `bounds` pixels, each supersampled
+/// `supersample` times along each side, means no more than `max` pixels,
+/// returning `MandelError::TooManyPixels` if it does. Counts too large for a
+/// `usize` are too many, rather than overflowing.
+pub fn check_pixel_count(bounds : (usize, usize), supersample : usize, max : usize)
+    -> Result<(), MandelError> {
+
+    let pixels = bounds.0.checked_mul(bounds.1)
+        .and_then(|pixels| pixels.checked_mul(supersample))
+        .and_then(|pixels| pixels.checked_mul(supersample));
+
+    match pixels {
+        Some(pixels) if pixels <= max => Ok(()),
+        _ => Err(MandelError::TooManyPixels { bounds, supersample, max })
+    }
+}
+
+/// Return the lower-right corner of a rectangle with the given `upper_left`
+/// corner and real-axis `width`, whose height is chosen to give the same
+/// aspect ratio as an image of `bounds` pixels, so that nothing is stretched.
+pub fn lower_right_for_width(bounds : (usize, usize),
+                             upper_left : Complex<f64>,
+                             width : f64) -> Complex<f64> {
+
+    let height = width * bounds.1 as f64 / bounds.0 as f64;
+    Complex { re: upper_left.re + width, im: upper_left.im - height }
+}
+
+/// Return the `(upper_left, lower_right)` corners of the unstretched
+/// rectangle `width` wide around `center`, for an image of `bounds` pixels.
+pub fn rect_from_center_width(center : Complex<f64>,
+                              width : f64,
+                              bounds : (usize, usize)) -> (Complex<f64>, Complex<f64>) {
+
+    let height = width * bounds.1 as f64 / bounds.0 as f64;
+    let upper_left = Complex { re: center.re - width / 2.0, im: center.im + height / 2.0 };
+
+    (upper_left, lower_right_for_width(bounds, upper_left, width))
+}
+
+/// Return the `(upper_left, lower_right)` corners of the unstretched
+/// rectangle around `center` whose height along the imaginary axis is
+/// `scale`, for an image of `bounds` pixels.
+pub fn rect_from_center(center : Complex<f64>,
+                        scale : f64,
+                        bounds : (usize, usize)) -> (Complex<f64>, Complex<f64>) {
+
+    let half_height = scale / 2.0;
+    let half_width = half_height * bounds.0 as f64 / bounds.1 as f64;
+
+    (Complex { re: center.re - half_width, im: center.im + half_height },
+     Complex { re: center.re + half_width, im: center.im - half_height })
+}
+
+/// Return the distance between neighbouring pixels of an image of `bounds`
+/// pixels covering the rectangle from `upper_left` to `lower_right`: the real
+/// part is the step from one column to the next, and the imaginary part the
+/// step from one row to the next, which is negative for an upright image.
+pub fn pixel_step(bounds : (usize, usize),
+                  upper_left : Complex<f64>,
+                  lower_right : Complex<f64>) -> Complex<f64> {
+
+    Complex { re: (lower_right.re - upper_left.re) / bounds.0 as f64,
+              im: (lower_right.im - upper_left.im) / bounds.1 as f64 }
+}
+
+/// Return true if `f32` can tell neighbouring pixels of an image of `bounds`
+/// pixels covering the rectangle from `upper_left` to `lower_right` apart.
+///
+/// Past that depth, `f32` would give whole runs of pixels the same point, so
+/// deeper views need `f64`.
+pub fn f32_resolves(bounds : (usize, usize),
+                    upper_left : Complex<f64>,
+                    lower_right : Complex<f64>) -> bool {
+
+    let spacing = ((lower_right.re - upper_left.re) / bounds.0 as f64).abs()
+        .min(((upper_left.im - lower_right.im) / bounds.1 as f64).abs());
+    let magnitude = [upper_left.re, upper_left.im, lower_right.re, lower_right.im]
+        .iter().fold(0.0, |max : f64, x| max.max(x.abs()));
+
+    spacing > magnitude * f32::EPSILON as f64
+}
+
+/// The iteration limit `auto_limit` gives a view as wide as the whole set, or
+/// wider.
+pub const AUTO_LIMIT_BASE : f64 = 256.0;
+
+/// How many times `AUTO_LIMIT_BASE` `auto_limit` adds for each tenfold zoom.
+pub const AUTO_LIMIT_GROWTH : f64 = 1.0;
+
+/// Return an iteration limit for a view `width` wide on the complex plane:
+/// `AUTO_LIMIT_BASE * (1 + AUTO_LIMIT_GROWTH * -log10(width))`, so that deeper
+/// zooms, whose points take longer to escape, get more iterations. Views a
+/// unit wide or wider get the base limit.
+pub fn auto_limit(width : f64) -> usize {
+
+    let depth = (-width.abs().log10()).max(0.0);
+    (AUTO_LIMIT_BASE * (1.0 + AUTO_LIMIT_GROWTH * depth)).round() as usize
+}
+
+/// Return the corners of the rectangle an interactive viewer should show
+/// after a click on `pixel` of an image of `bounds` pixels covering the
+/// rectangle from `upper_left` to `lower_right`: the clicked point becomes
+/// the center, and the width and height are multiplied by `factor`, so a
+/// factor below 1 zooms in.
+pub fn zoom_at(bounds : (usize, usize),
+               pixel : (usize, usize),
+               upper_left : Complex<f64>,
+               lower_right : Complex<f64>,
+               factor : f64) -> (Complex<f64>, Complex<f64>) {
+
+    let center = pixel_to_point(bounds, pixel, upper_left, lower_right);
+    let half_width = (lower_right.re - upper_left.re) * factor / 2.0;
+    let half_height = (upper_left.im - lower_right.im) * factor / 2.0;
+
+    (Complex { re: center.re - half_width, im: center.im + half_height },
+     Complex { re: center.re + half_width, im: center.im - half_height })
+}
+
+/// Interpolate geometrically between the positive numbers `a` and `b`: each
+/// equal step in `t` multiplies the result by the same factor, as a steady
+/// zoom should. `t = 0` gives exactly `a`, and `t = 1` exactly `b`.
+pub fn log_lerp(a : f64, b : f64, t : f64) -> f64 {
+
+    a.powf(1.0 - t) * b.powf(t)
+}
+
+/// Return the corners of frame number `frame`, counting from zero, of a
+/// `frames`-frame zoom from the rectangle `start` to the rectangle `end`,
+/// each given as `(upper_left, lower_right)`.
+///
+/// The center moves linearly from one rectangle's to the other's, while the
+/// width and height shrink (or grow) by a constant factor each frame.
+pub fn zoom_frame(start : (Complex<f64>, Complex<f64>),
+                  end : (Complex<f64>, Complex<f64>),
+                  frame : usize,
+                  frames : usize) -> (Complex<f64>, Complex<f64>) {
+
+    let t = if frames > 1 { frame as f64 / (frames - 1) as f64 } else { 0.0 };
+
+    let (from, to) = ((start.0 + start.1) / 2.0, (end.0 + end.1) / 2.0);
+    let center = Complex { re: lerp(from.re, to.re, t), im: lerp(from.im, to.im, t) };
+
+    let half_width = log_lerp(start.1.re - start.0.re, end.1.re - end.0.re, t) / 2.0;
+    let half_height = log_lerp(start.0.im - start.1.im, end.0.im - end.1.im, t) / 2.0;
+
+    (Complex { re: center.re - half_width, im: center.im + half_height },
+     Complex { re: center.re + half_width, im: center.im - half_height })
+}
+
+/// The size of the coarse grid `find_boundary_point` scans.
+pub const BOUNDARY_SCAN_BOUNDS : (usize, usize) = (64, 64);
+
+/// Scan a coarse grid of `BOUNDARY_SCAN_BOUNDS` points over the rectangle
+/// from `upper_left` to `lower_right`, and return the one that looks most
+/// like it lies on the set's boundary, for `--auto-center`: of the points
+/// that escape but have a neighbour in the set, the one that takes longest
+/// to, as counted by `iteration`'s whole escape times. Ties go to the first
+/// in reading order. Return `None` if no escaping point borders the set.
+pub fn find_boundary_point(upper_left : Complex<f64>,
+                           lower_right : Complex<f64>,
+                           iteration : Iteration) -> Option<Complex<f64>> {
+
+    let bounds = BOUNDARY_SCAN_BOUNDS;
+    let iteration = Iteration { smooth: false, distance: false, trap: None, interior: false, max_period: 0,
+                                ..iteration };
+    let Counts::Whole(counts) = render(bounds, upper_left, lower_right, iteration) else {
+        unreachable!("render gives whole counts when nothing else is asked for");
+    };
+
+    let in_set = |x : usize, y : usize| counts[y * bounds.0 + x].is_none();
+    let mut best : Option<(usize, (usize, usize))> = None;
+    for y in 0..bounds.1 {
+        for x in 0..bounds.0 {
+            let Some(count) = counts[y * bounds.0 + x] else {
+                continue;
+            };
+            let borders_set = (x > 0 && in_set(x - 1, y)) || (x + 1 < bounds.0 && in_set(x + 1, y))
+                || (y > 0 && in_set(x, y - 1)) || (y + 1 < bounds.1 && in_set(x, y + 1));
+            if borders_set && best.is_none_or(|(most, _)| count > most) {
+                best = Some((count, (x, y)));
+            }
+        }
+    }
+
+    best.map(|(_, pixel)| pixel_to_point(bounds, pixel, upper_left, lower_right))
+}
+
+/// A rectangle of pixels within an image, for `--crop`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crop {
+    /// The column and row of the rectangle's upper-left pixel.
+    pub origin: (usize, usize),
+
+    /// The width and height of the rectangle in pixels, neither of them zero.
+    pub size: (usize, usize)
+}
+
+impl std::str::FromStr for Crop {
+
+    type Err = String;
+
+    /// Parse a crop written `X,Y,W,H`.
+    fn from_str(s : &str) -> Result<Crop, String> {
+
+        let numbers : Vec<usize> = s.split(',').map(|n| n.trim().parse()).collect::<Result<_, _>>()
+            .map_err(|_| format!("error parsing crop '{}': expected X,Y,W,H", s))?;
+        match numbers[..] {
+            [x, y, width, height] if width > 0 && height > 0 =>
+                Ok(Crop { origin: (x, y), size: (width, height) }),
+            _ => Err(format!("error parsing crop '{}': expected X,Y,W,H, with W and H positive", s))
+        }
+    }
+}
+
+/// Return the corners of the part of the rectangle from `upper_left` to
+/// `lower_right` that the pixels `crop` of an image of `bounds` pixels cover,
+/// by `pixel_to_point` on the crop's corners, or `None` if the crop extends
+/// past the edge of the image. A crop of the whole image gives back exactly
+/// `upper_left` and `lower_right`.
+pub fn crop_view(bounds : (usize, usize),
+                 crop : Crop,
+                 upper_left : Complex<f64>,
+                 lower_right : Complex<f64>) -> Option<(Complex<f64>, Complex<f64>)> {
+
+    let far = (crop.origin.0 + crop.size.0, crop.origin.1 + crop.size.1);
+    if far.0 > bounds.0 || far.1 > bounds.1 {
+        return None;
+    }
+
+    Some((pixel_to_point(bounds, crop.origin, upper_left, lower_right),
+          pixel_to_point(bounds, far, upper_left, lower_right)))
+}
+
+/// A well-known view of the Mandelbrot set, for `--preset`.
+#[derive(Debug, PartialEq)]
+pub struct Preset {
+    /// The name `find_preset` knows it by.
+    pub name: &'static str,
+
+    /// The upper-left corner of the view.
+    pub upper_left: Complex<f64>,
+
+    /// The lower-right corner of the view.
+    pub lower_right: Complex<f64>,
+
+    /// An iteration limit high enough to bring out the view's detail.
+    pub limit: usize
+}
+
+/// The views `find_preset` can return, in the order its error lists them.
+pub static PRESETS : [Preset; 4] = [
+    Preset { name: "full", upper_left: Complex { re: -2.5, im: 1.5 },
+             lower_right: Complex { re: 1.0, im: -1.5 }, limit: 255 },
+    Preset { name: "seahorse", upper_left: Complex { re: -0.76, im: 0.14 },
+             lower_right: Complex { re: -0.72, im: 0.11 }, limit: 1000 },
+    Preset { name: "elephant", upper_left: Complex { re: 0.25, im: 0.03 },
+             lower_right: Complex { re: 0.31, im: -0.015 }, limit: 1000 },
+    Preset { name: "triple-spiral", upper_left: Complex { re: -0.09, im: 0.6555 },
+             lower_right: Complex { re: -0.086, im: 0.6525 }, limit: 2000 }
+];
+
+/// Look up the preset called `name` in `PRESETS`. If there's no such preset,
+/// the error lists the ones there are.
+pub fn find_preset(name : &str) -> Result<&'static Preset, String> {
+
+    PRESETS.iter().find(|preset| preset.name == name).ok_or_else(|| {
+        let names : Vec<_> = PRESETS.iter().map(|preset| preset.name).collect();
+        format!("unknown preset '{}': use one of {}", name, names.join(", "))
+    })
+}
+
+#[test]
+fn test_lower_right_for_width() {
+
+    assert_eq!(lower_right_for_width((400, 200), Complex { re: -2.0, im: 1.0 }, 3.0),
+               Complex { re: 1.0, im: -0.5 });
+}
+
+#[test]
+fn test_rect_from_center_width_keeps_aspect() {
+
+    for bounds in [(1024, 768), (300, 900), (5, 5)] {
+        let (upper_left, lower_right) =
+            rect_from_center_width(Complex { re: -0.75, im: 0.1 }, 2.5, bounds);
+
+        let width = lower_right.re - upper_left.re;
+        let height = upper_left.im - lower_right.im;
+        assert!((width - 2.5).abs() < 1e-12);
+        assert!((width / height - bounds.0 as f64 / bounds.1 as f64).abs() < 1e-12);
+        assert!(((upper_left.re + lower_right.re) / 2.0 + 0.75).abs() < 1e-12);
+        assert!(((upper_left.im + lower_right.im) / 2.0 - 0.1).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn test_rect_from_center_round_trips() {
+
+    let center = Complex { re: -0.743643887, im: 0.131825904 };
+    for bounds in [(1024, 768), (640, 480), (200, 1000)] {
+        let (upper_left, lower_right) = rect_from_center(center, 0.01, bounds);
+
+        assert!((upper_left.im - lower_right.im - 0.01).abs() < 1e-15);
+        let point = pixel_to_point(bounds, (bounds.0 / 2, bounds.1 / 2), upper_left, lower_right);
+        assert!((point - center).norm() < 1e-15, "{} != {}", point, center);
+    }
+}
+
+#[test]
+fn test_log_lerp() {
+
+    assert_eq!(log_lerp(4.0, 0.001, 0.0), 4.0);
+    assert_eq!(log_lerp(4.0, 0.001, 1.0), 0.001);
+    assert!((log_lerp(1.0, 100.0, 0.5) - 10.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_zoom_frame_endpoints() {
+
+    let close = |a: (Complex<f64>, Complex<f64>), b: (Complex<f64>, Complex<f64>)| {
+        (a.0 - b.0).norm() < 1e-12 && (a.1 - b.1).norm() < 1e-12
+    };
+
+    let start = (Complex { re: -2.5, im: 1.5 }, Complex { re: 1.0, im: -1.5 });
+    let end = rect_from_center(Complex { re: -0.743643887, im: 0.131825904 }, 1e-4, (350, 300));
+
+    assert!(close(zoom_frame(start, end, 0, 30), start));
+    assert!(close(zoom_frame(start, end, 29, 30), end));
+
+    // Every frame zooms in by the same factor.
+    let height = |frame| { let (ul, lr) = zoom_frame(start, end, frame, 30); ul.im - lr.im };
+    assert!((height(1) / height(0) - height(21) / height(20)).abs() < 1e-9);
+
+    assert_eq!(zoom_frame(start, end, 0, 1), zoom_frame(start, end, 0, 30));
+}
+
+#[test]
+fn test_zoom_at() {
+
+    let close = |a: (Complex<f64>, Complex<f64>), b: (Complex<f64>, Complex<f64>)| {
+        (a.0 - b.0).norm() < 1e-12 && (a.1 - b.1).norm() < 1e-12
+    };
+
+    let (upper_left, lower_right) = (Complex { re: -2.5, im: 1.5 }, Complex { re: 1.0, im: -1.5 });
+    assert!(close(zoom_at((700, 600), (350, 300), upper_left, lower_right, 1.0), (upper_left, lower_right)));
+
+    // Zooming in at a corner pixel centers the view there.
+    let (ul, lr) = zoom_at((700, 600), (0, 0), upper_left, lower_right, 0.5);
+    assert!(close((ul, lr), (Complex { re: -3.375, im: 2.25 }, Complex { re: -1.625, im: 0.75 })));
+}
+
+#[test]
+fn test_f32_resolves() {
+
+    let (upper_left, lower_right) = rect_from_center(Complex { re: -0.75, im: 0.0 }, 2.5, (800, 600));
+    assert!(f32_resolves((800, 600), upper_left, lower_right));
+
+    let (upper_left, lower_right) = rect_from_center(Complex { re: -0.75, im: 0.1 }, 1e-5, (800, 600));
+    assert!(!f32_resolves((800, 600), upper_left, lower_right));
+}
+
+#[test]
+fn test_auto_limit() {
+
+    assert_eq!(auto_limit(3.0), 256);
+    assert_eq!(auto_limit(1.0), 256);
+    assert_eq!(auto_limit(-0.01), 768);
+    assert!(auto_limit(1e-6) > auto_limit(1e-5));
+    for width in [2.0, 0.5, 1e-3, 1e-12] {
+        assert!(auto_limit(width / 10.0) > auto_limit(width), "{}", width);
+    }
+}
+
+#[test]
+fn test_find_boundary_point() {
+
+    use crate::escape_time;
+
+    // Scanning the whole set finds a point just outside it, within the
+    // rectangle known to hold it.
+    let iteration = Iteration { limit: 255, ..Iteration::default() };
+    let center = find_boundary_point(Complex { re: -2.5, im: 1.5 }, Complex { re: 1.0, im: -1.5 }, iteration)
+        .unwrap();
+    assert!((-2.0..=0.5).contains(&center.re) && (-1.2..=1.2).contains(&center.im), "{}", center);
+    assert!(escape_time(Complex { re: 0.0, im: 0.0 }, center, 255, 2.0).is_some());
+
+    // Far from the set there's no boundary to find.
+    assert_eq!(find_boundary_point(Complex { re: 3.0, im: 3.0 }, Complex { re: 4.0, im: 2.0 }, iteration),
+               None);
+
+    // Whatever the render colors by, the scan goes by whole escape times.
+    for colored in [Iteration { max_period: 8, ..iteration }, Iteration { smooth: true, ..iteration },
+                    Iteration { interior: true, ..iteration }, Iteration { distance: true, ..iteration }] {
+        assert_eq!(find_boundary_point(Complex { re: -2.5, im: 1.5 }, Complex { re: 1.0, im: -1.5 }, colored),
+                   Some(center), "{:?}", colored);
+    }
+}
+
+#[test]
+fn test_crop_view() {
+
+    use crate::{render, Iteration};
+
+    let bounds = (40, 30);
+    let upper_left = Complex { re: -2.0, im: 1.2 };
+    let lower_right = Complex { re: 1.0, im: -1.2 };
+    let iteration = Iteration { limit: 100, ..Iteration::default() };
+
+    // Cropping the whole image renders exactly what the whole image does.
+    let whole = Crop { origin: (0, 0), size: bounds };
+    let (crop_upper_left, crop_lower_right) = crop_view(bounds, whole, upper_left, lower_right).unwrap();
+    assert_eq!((crop_upper_left, crop_lower_right), (upper_left, lower_right));
+    assert_eq!(render(bounds, crop_upper_left, crop_lower_right, iteration),
+               render(bounds, upper_left, lower_right, iteration));
+
+    // A crop covers the points of its pixels.
+    let crop = Crop { origin: (10, 5), size: (20, 15) };
+    let (crop_upper_left, crop_lower_right) = crop_view(bounds, crop, upper_left, lower_right).unwrap();
+    assert!((crop_upper_left - Complex { re: -1.25, im: 0.8 }).norm() < 1e-12);
+    assert!((crop_lower_right - Complex { re: 0.25, im: -0.4 }).norm() < 1e-12);
+    assert_eq!(crop_view(bounds, Crop { origin: (30, 0), size: (11, 1) }, upper_left, lower_right), None);
+
+    assert_eq!("10, 5,20,15".parse(), Ok(crop));
+    for bad in ["10,5,20", "10,5,0,15", "a,b,c,d", "1,2,3,4,5", ""] {
+        assert!(bad.parse::<Crop>().is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_check_nondegenerate() {
+
+    let corner = |re, im| Complex { re, im };
+
+    assert!(check_nondegenerate(corner(-2.0, 1.0), corner(1.0, -1.0)).is_ok());
+    assert!(check_nondegenerate(corner(1.0, -1.0), corner(-2.0, 1.0)).is_ok());
+    assert!(matches!(check_nondegenerate(corner(-0.5, 0.25), corner(-0.5, 0.25)),
+                     Err(MandelError::DegenerateCorners { part: "real", value }) if value == -0.5));
+    assert!(matches!(check_nondegenerate(corner(0.5, 1.0), corner(0.5, -1.0)),
+                     Err(MandelError::DegenerateCorners { part: "real", .. })));
+    assert!(matches!(check_nondegenerate(corner(-2.0, 0.3), corner(1.0, 0.3)),
+                     Err(MandelError::DegenerateCorners { part: "imaginary", .. })));
+}
+
+#[test]
+fn test_check_corners() {
+
+    let corner = |re, im| Complex { re, im };
+
+    assert!(check_corners(corner(-2.0, 1.0), corner(1.0, -1.0)).is_ok());
+    assert!(matches!(check_corners(corner(1.0, 1.0), corner(-2.0, -1.0)),
+                     Err(MandelError::FlippedCorners { part: "real", .. })));
+    assert!(matches!(check_corners(corner(-2.0, -1.0), corner(1.0, 1.0)),
+                     Err(MandelError::FlippedCorners { part: "imaginary", .. })));
+
+    let message = check_corners(corner(-2.0, -1.0), corner(1.0, 1.0)).unwrap_err().to_string();
+    assert!(message.contains("imaginary part -1 must be greater than"), "{}", message);
+    let message = check_corners(corner(0.5, 1.0), corner(0.5, -1.0)).unwrap_err().to_string();
+    assert!(message.contains("real part 0.5 must be less than"), "{}", message);
+}
+
+#[test]
+fn test_find_preset() {
+
+    let full = find_preset("full").unwrap();
+    assert_eq!((full.upper_left, full.lower_right),
+               (Complex { re: -2.5, im: 1.5 }, Complex { re: 1.0, im: -1.5 }));
+    assert_eq!(find_preset("mandelbrot").unwrap_err(),
+               "unknown preset 'mandelbrot': use one of full, seahorse, elephant, triple-spiral");
+
+    for preset in &PRESETS {
+        assert!(check_corners(preset.upper_left, preset.lower_right).is_ok(), "{} is flipped", preset.name);
+    }
+}
+
+#[test]
+fn test_pixel_step() {
+
+    let step = pixel_step((400, 300), Complex { re: -2.0, im: 1.5 }, Complex { re: 2.0, im: -1.5 });
+    assert_eq!(step, Complex { re: 0.01, im: -0.01 });
+}
+
+#[test]
+fn test_check_pixel_count() {
+
+    assert!(check_pixel_count((1920, 1080), 4, DEFAULT_MAX_PIXELS).is_ok());
+    assert!(check_pixel_count((100, 100), 1, 10000).is_ok());
+    assert!(matches!(check_pixel_count((100, 101), 1, 10000),
+                     Err(MandelError::TooManyPixels { bounds: (100, 101), supersample: 1, max: 10000 })));
+    assert!(check_pixel_count((100, 100), 2, 10000).is_err());
+
+    // Products that don't fit in a usize are caught rather than wrapping
+    // around to something small.
+    assert!(check_pixel_count((usize::MAX / 2, 3), 1, usize::MAX).is_err());
+    assert!(check_pixel_count((1 << 20, 1 << 20), 1 << 20, usize::MAX).is_err());
+}
@@ -0,0 +1,40 @@
+//! An entry point for rendering in a browser, through wasm-bindgen.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use num::Complex;
+
+use crate::color::{map_rgba, ColorScheme};
+use crate::escape::Iteration;
+use crate::render::render;
+
+/// Render a `width` by `height` image of the Mandelbrot set, from the
+/// upper-left corner `ul_re + ul_im i` to the lower-right one `lr_re + lr_im i`,
+/// iterating each point at most `limit` times, and return its pixels as
+/// red, green, blue and alpha bytes, ready for a canvas's `ImageData`.
+///
+/// The image is colored in grayscale, as the command-line tool colors it by
+/// default. The parameters are all plain numbers, so that JavaScript can
+/// pass them.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn render_rgba(width : usize, height : usize, ul_re : f64, ul_im : f64, lr_re : f64, lr_im : f64,
+                   limit : usize) -> Vec<u8> {
+
+    let iteration = Iteration { limit, ..Iteration::default() };
+    let counts = render((width, height), Complex { re: ul_re, im: ul_im }, Complex { re: lr_re, im: lr_im },
+                        iteration);
+    map_rgba(&counts, limit, &ColorScheme::default())
+}
+
+#[test]
+fn test_render_rgba() {
+
+    let rgba = render_rgba(40, 30, -2.0, 1.2, 1.0, -1.2, 300);
+    assert_eq!(rgba.len(), 40 * 30 * 4);
+    assert!(rgba.chunks(4).all(|pixel| pixel[3] == 255));
+
+    let iteration = Iteration { limit: 300, ..Iteration::default() };
+    let counts = render((40, 30), Complex { re: -2.0, im: 1.2 }, Complex { re: 1.0, im: -1.2 }, iteration);
+    assert_eq!(rgba, map_rgba(&counts, 300, &ColorScheme::default()));
+}